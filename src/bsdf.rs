@@ -0,0 +1,526 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+
+use crate::color::{Material, get_luminance};
+use crate::vector3d::{
+    Vector3D, Onb, sample_cosine_hemisphere,
+    vec_dot, vec_normalize,
+    vec_sum, vec_sub, vec_multiplication, vec_division, vec_hadamard
+};
+
+// Smallest GGX alpha, perfect mirrors make the distribution a delta.
+const MIN_ALPHA: f32 = 1e-3;
+
+// Reflectance at normal incidence of the dielectric part, 4% is common to most materials.
+const DIELECTRIC_F0: f32 = 0.04;
+
+/**
+ * Direction chosen by a BSDF.
+ */
+#[derive(Clone, Copy)]
+pub struct BsdfSample {
+    // Incoming direction, pointing away from the surface.
+    pub direction: Vector3D,
+
+    // f * |cos| / pdf, the factor applied to the path throughput.
+    pub weight: Vector3D,
+
+    pub pdf: f32,
+
+    // Perfect mirror or refraction, `evaluate` and `pdf` can't find these directions.
+    pub is_delta: bool
+}
+
+/**
+ * Scattering at a surface point. All the directions point away from the surface
+ * and the normal is on the side of the outgoing direction.
+ */
+pub trait Bsdf {
+    /**
+     * Value of the BSDF for a pair of directions, without the cosine term.
+     */
+    fn evaluate(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> Vector3D;
+
+    /**
+     * Solid angle density of `sample` choosing the incoming direction.
+     */
+    fn pdf(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> f32;
+
+    /**
+     * Chooses an incoming direction, None if the path should end.
+     */
+    fn sample<R: Rng + ?Sized>(&self, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Option<BsdfSample>;
+
+    /**
+     * Only scatters in delta directions, lights can't be evaluated on it.
+     */
+    fn is_delta(&self) -> bool {
+        return false;
+    }
+}
+
+// ================================================
+// Microfacet helpers
+// ================================================
+
+fn lerp(a: &Vector3D, b: &Vector3D, t: f32) -> Vector3D {
+    return vec_sum(&vec_multiplication(a, 1.0 - t), &vec_multiplication(b, t));
+}
+
+/**
+ * Schlick approximation of the Fresnel reflectance.
+ *
+ * @param {Vector3D} f0 Reflectance at normal incidence.
+ * @param {f32} cos_theta
+ *
+ * @return {Vector3D}
+ */
+pub fn fresnel_schlick(f0: &Vector3D, cos_theta: f32) -> Vector3D {
+    let factor = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+
+    return lerp(f0, &Vector3D::new_as_one(), factor);
+}
+
+/**
+ * GGX (Trowbridge-Reitz) normal distribution.
+ *
+ * @param {f32} cos_theta_h Cosine between the normal and the half vector.
+ * @param {f32} alpha
+ *
+ * @return {f32}
+ */
+pub fn ggx_distribution(cos_theta_h: f32, alpha: f32) -> f32 {
+    if cos_theta_h <= 0.0 {
+        return 0.0;
+    }
+
+    let alpha2 = alpha * alpha;
+    let denominator = cos_theta_h * cos_theta_h * (alpha2 - 1.0) + 1.0;
+
+    return alpha2 / (PI * denominator * denominator);
+}
+
+/**
+ * Smith masking term of one direction for the GGX distribution.
+ *
+ * @param {f32} cos_theta
+ * @param {f32} alpha
+ *
+ * @return {f32}
+ */
+pub fn smith_g1(cos_theta: f32, alpha: f32) -> f32 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+
+    let alpha2 = alpha * alpha;
+
+    return 2.0 * cos_theta / (cos_theta + (alpha2 + (1.0 - alpha2) * cos_theta * cos_theta).sqrt());
+}
+
+// ================================================
+// Lambertian BSDF
+// ================================================
+
+/**
+ * Ideal diffuse reflection, the same radiance in every direction.
+ */
+#[derive(Clone, Copy)]
+pub struct Lambertian {
+    pub albedo: Vector3D
+}
+
+impl Lambertian {
+    pub fn new(albedo: Vector3D) -> Lambertian {
+        return Lambertian { albedo: albedo };
+    }
+}
+
+impl Bsdf for Lambertian {
+    fn evaluate(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> Vector3D {
+        if vec_dot(normal, outgoing) <= 0.0 || vec_dot(normal, incoming) <= 0.0 {
+            return Vector3D::new_as_zero();
+        }
+
+        return vec_division(&self.albedo, PI);
+    }
+
+    fn pdf(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> f32 {
+        if vec_dot(normal, outgoing) <= 0.0 {
+            return 0.0;
+        }
+
+        return vec_dot(normal, incoming).max(0.0) / PI;
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Option<BsdfSample> {
+        if vec_dot(normal, outgoing) <= 0.0 {
+            return None;
+        }
+
+        let local = sample_cosine_hemisphere(rng.gen::<f32>(), rng.gen::<f32>());
+        if local.z <= 0.0 {
+            return None;
+        }
+
+        let incoming = Onb::new_from_w(normal).to_world(&local);
+
+        // The cosine and PI cancel out with the density.
+        return Some(BsdfSample {
+            direction: incoming,
+            weight: self.albedo,
+            pdf: local.z / PI,
+            is_delta: false
+        });
+    }
+}
+
+// ================================================
+// Metallic-roughness BSDF
+// ================================================
+
+/**
+ * Metallic-roughness model used by most authoring tools: a GGX specular lobe with
+ * Smith masking and Schlick Fresnel over a Lambertian base that fades out with the metalness.
+ */
+#[derive(Clone, Copy)]
+pub struct MetallicRoughness {
+    pub base_color: Vector3D,
+    pub roughness: f32,
+    pub metallic: f32
+}
+
+impl MetallicRoughness {
+    pub fn new(base_color: Vector3D, roughness: f32, metallic: f32) -> MetallicRoughness {
+        return MetallicRoughness {
+            base_color: base_color,
+            roughness: roughness.clamp(0.0, 1.0),
+            metallic: metallic.clamp(0.0, 1.0)
+        };
+    }
+
+    pub fn from_material(material: &Material) -> MetallicRoughness {
+        return MetallicRoughness::new(material.base_color, material.roughness, material.metalic);
+    }
+
+    /**
+     * GGX alpha, the perceptual roughness is squared.
+     */
+    pub fn get_alpha(&self) -> f32 {
+        return (self.roughness * self.roughness).max(MIN_ALPHA);
+    }
+
+    /**
+     * Reflectance at normal incidence, metals tint it with the base color.
+     */
+    pub fn get_f0(&self) -> Vector3D {
+        return lerp(&Vector3D::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0), &self.base_color, self.metallic);
+    }
+
+    /**
+     * Lambertian base, it fades out with the metalness.
+     */
+    pub fn get_diffuse(&self) -> Lambertian {
+        return Lambertian::new(vec_multiplication(&self.base_color, 1.0 - self.metallic));
+    }
+
+    /**
+     * Chance of sampling the specular lobe, from the approximate energy of both lobes.
+     */
+    fn get_specular_probability(&self, cos_theta_o: f32) -> f32 {
+        let specular = get_luminance(&fresnel_schlick(&self.get_f0(), cos_theta_o));
+        let diffuse = get_luminance(&self.base_color) * (1.0 - self.metallic);
+
+        // Never leave a lobe without samples unless it's empty.
+        if diffuse <= 0.0 {
+            return 1.0;
+        }
+
+        return (specular / (specular + diffuse)).clamp(0.1, 0.9);
+    }
+}
+
+impl Bsdf for MetallicRoughness {
+    fn evaluate(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> Vector3D {
+        let cos_theta_o = vec_dot(normal, outgoing);
+        let cos_theta_i = vec_dot(normal, incoming);
+
+        if cos_theta_o <= 0.0 || cos_theta_i <= 0.0 {
+            return Vector3D::new_as_zero();
+        }
+
+        let half = vec_normalize(&vec_sum(outgoing, incoming));
+        let alpha = self.get_alpha();
+
+        let fresnel = fresnel_schlick(&self.get_f0(), vec_dot(incoming, &half));
+        let distribution = ggx_distribution(vec_dot(normal, &half), alpha);
+        let masking = smith_g1(cos_theta_o, alpha) * smith_g1(cos_theta_i, alpha);
+
+        let specular = vec_multiplication(&fresnel, distribution * masking / (4.0 * cos_theta_o * cos_theta_i));
+
+        // The light reflected by the dielectric coating on the way in and out doesn't
+        // reach the diffuse base. Using both directions keeps it reciprocal.
+        let f0 = Vector3D::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        let transmitted = vec_hadamard(
+            &vec_sub(&Vector3D::new_as_one(), &fresnel_schlick(&f0, cos_theta_i)),
+            &vec_sub(&Vector3D::new_as_one(), &fresnel_schlick(&f0, cos_theta_o))
+        );
+        let diffuse = vec_hadamard(&self.get_diffuse().evaluate(normal, outgoing, incoming), &transmitted);
+
+        return vec_sum(&specular, &diffuse);
+    }
+
+    fn pdf(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> f32 {
+        let cos_theta_o = vec_dot(normal, outgoing);
+        let cos_theta_i = vec_dot(normal, incoming);
+
+        if cos_theta_o <= 0.0 || cos_theta_i <= 0.0 {
+            return 0.0;
+        }
+
+        let half = vec_normalize(&vec_sum(outgoing, incoming));
+        let cos_theta_h = vec_dot(normal, &half);
+
+        // Half vector density converted to the incoming direction.
+        let specular_pdf = ggx_distribution(cos_theta_h, self.get_alpha()) * cos_theta_h
+            / (4.0 * vec_dot(outgoing, &half));
+
+        let diffuse_pdf = self.get_diffuse().pdf(normal, outgoing, incoming);
+
+        let specular_probability = self.get_specular_probability(cos_theta_o);
+
+        return specular_probability * specular_pdf + (1.0 - specular_probability) * diffuse_pdf;
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Option<BsdfSample> {
+        let cos_theta_o = vec_dot(normal, outgoing);
+        if cos_theta_o <= 0.0 {
+            return None;
+        }
+
+        let incoming = if rng.gen::<f32>() < self.get_specular_probability(cos_theta_o) {
+            let basis = Onb::new_from_w(normal);
+
+            let u1 = rng.gen::<f32>();
+            let u2 = rng.gen::<f32>();
+
+            // Half vector from the GGX distribution, then mirror the outgoing direction.
+            let alpha = self.get_alpha();
+            let cos_theta_h = ((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1)).sqrt();
+            let sin_theta_h = (1.0 - cos_theta_h * cos_theta_h).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+
+            let half = basis.to_world(&Vector3D::new(sin_theta_h * phi.cos(), sin_theta_h * phi.sin(), cos_theta_h));
+
+            vec_sub(&vec_multiplication(&half, 2.0 * vec_dot(outgoing, &half)), outgoing)
+        } else {
+            self.get_diffuse().sample(normal, outgoing, rng)?.direction
+        };
+
+        let cos_theta_i = vec_dot(normal, &incoming);
+        if cos_theta_i <= 0.0 {
+            return None;
+        }
+
+        let pdf = self.pdf(normal, outgoing, &incoming);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+
+        let value = self.evaluate(normal, outgoing, &incoming);
+
+        return Some(BsdfSample {
+            direction: incoming,
+            weight: vec_division(&vec_multiplication(&value, cos_theta_i), pdf),
+            pdf: pdf,
+            is_delta: false
+        });
+    }
+}
+
+// ================================================
+// Dielectric BSDF
+// ================================================
+
+/**
+ * Fresnel reflectance of a smooth boundary between two dielectrics.
+ *
+ * @param {f32} cos_theta_i Cosine of the incident direction with the normal.
+ * @param {f32} eta Index of refraction of the far side over the near side.
+ *
+ * @return {f32} 1 on total internal reflection.
+ */
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+
+    return (parallel * parallel + perpendicular * perpendicular) / 2.0;
+}
+
+/**
+ * Mirror direction of the outgoing direction around the normal.
+ *
+ * @param {Vector3D} normal
+ * @param {Vector3D} outgoing
+ *
+ * @return {Vector3D}
+ */
+pub fn reflect(normal: &Vector3D, outgoing: &Vector3D) -> Vector3D {
+    return vec_sub(&vec_multiplication(normal, 2.0 * vec_dot(normal, outgoing)), outgoing);
+}
+
+/**
+ * Direction refracted through the boundary by Snell's law.
+ *
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ * @param {f32} eta Index of refraction of the far side over the near side.
+ *
+ * @return {Option<Vector3D>} None on total internal reflection.
+ */
+pub fn refract(normal: &Vector3D, outgoing: &Vector3D, eta: f32) -> Option<Vector3D> {
+    let cos_theta_o = vec_dot(normal, outgoing);
+    let sin2_theta_t = (1.0 - cos_theta_o * cos_theta_o) / (eta * eta);
+
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    return Some(vec_normalize(&vec_sum(
+        &vec_multiplication(outgoing, -1.0 / eta),
+        &vec_multiplication(normal, cos_theta_o / eta - cos_theta_t)
+    )));
+}
+
+/**
+ * Smooth boundary that reflects or refracts by the Fresnel reflectance.
+ */
+#[derive(Clone, Copy)]
+pub struct Dielectric {
+    // Index of refraction of the far side over the side of the normal.
+    pub eta: f32
+}
+
+impl Dielectric {
+    pub fn new(eta: f32) -> Dielectric {
+        return Dielectric { eta: eta };
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn evaluate(&self, _normal: &Vector3D, _outgoing: &Vector3D, _incoming: &Vector3D) -> Vector3D {
+        return Vector3D::new_as_zero();
+    }
+
+    fn pdf(&self, _normal: &Vector3D, _outgoing: &Vector3D, _incoming: &Vector3D) -> f32 {
+        return 0.0;
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Option<BsdfSample> {
+        let cos_theta_o = vec_dot(normal, outgoing);
+        if cos_theta_o <= 0.0 {
+            return None;
+        }
+
+        let reflectance = fresnel_dielectric(cos_theta_o, self.eta);
+
+        if rng.gen::<f32>() < reflectance {
+            return Some(BsdfSample {
+                direction: reflect(normal, outgoing),
+                weight: Vector3D::new_as_one(),
+                pdf: reflectance,
+                is_delta: true
+            });
+        }
+
+        // The total internal reflection always takes the branch above.
+        let refraction = refract(normal, outgoing, self.eta)?;
+
+        return Some(BsdfSample {
+            direction: refraction,
+            weight: Vector3D::new_as_one(),
+            pdf: 1.0 - reflectance,
+            is_delta: true
+        });
+    }
+
+    fn is_delta(&self) -> bool {
+        return true;
+    }
+}
+
+// ================================================
+// Material BSDF
+// ================================================
+
+/**
+ * The BSDF chosen for a material at a hit.
+ */
+#[derive(Clone, Copy)]
+pub enum MaterialBsdf {
+    MetallicRoughness(MetallicRoughness),
+    Dielectric(Dielectric)
+}
+
+impl MaterialBsdf {
+    /**
+     * Picks the BSDF of a material, the transmission is the chance of the dielectric.
+     *
+     * @param {Material} material
+     * @param {bool} front_face The hit is on the outside of the surface.
+     * @param {R} rng
+     *
+     * @return {MaterialBsdf}
+     */
+    pub fn from_material<R: Rng + ?Sized>(material: &Material, front_face: bool, rng: &mut R) -> MaterialBsdf {
+        if material.transmission > 0.0 && rng.gen::<f32>() < material.transmission {
+            let eta = if front_face { material.ior } else { 1.0 / material.ior };
+
+            return MaterialBsdf::Dielectric(Dielectric::new(eta));
+        }
+
+        return MaterialBsdf::MetallicRoughness(MetallicRoughness::from_material(material));
+    }
+}
+
+impl Bsdf for MaterialBsdf {
+    fn evaluate(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> Vector3D {
+        return match self {
+            MaterialBsdf::MetallicRoughness(bsdf) => bsdf.evaluate(normal, outgoing, incoming),
+            MaterialBsdf::Dielectric(bsdf) => bsdf.evaluate(normal, outgoing, incoming)
+        };
+    }
+
+    fn pdf(&self, normal: &Vector3D, outgoing: &Vector3D, incoming: &Vector3D) -> f32 {
+        return match self {
+            MaterialBsdf::MetallicRoughness(bsdf) => bsdf.pdf(normal, outgoing, incoming),
+            MaterialBsdf::Dielectric(bsdf) => bsdf.pdf(normal, outgoing, incoming)
+        };
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Option<BsdfSample> {
+        return match self {
+            MaterialBsdf::MetallicRoughness(bsdf) => bsdf.sample(normal, outgoing, rng),
+            MaterialBsdf::Dielectric(bsdf) => bsdf.sample(normal, outgoing, rng)
+        };
+    }
+
+    fn is_delta(&self) -> bool {
+        return match self {
+            MaterialBsdf::MetallicRoughness(bsdf) => bsdf.is_delta(),
+            MaterialBsdf::Dielectric(bsdf) => bsdf.is_delta()
+        };
+    }
+}
@@ -0,0 +1,365 @@
+use crate::geometry::{
+    Aabb, Line, Hit, Intersect, Primitive
+};
+
+use crate::vector3d::{Vector3D};
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+
+// Relative costs used by the surface area heuristic.
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+
+fn get_axis(v: &Vector3D, axis: usize) -> f32 {
+    return match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z
+    };
+}
+
+/**
+ * A node is a leaf when `count` is not zero, then `first` is the offset in the
+ * primitive index list. Otherwise `first` is the index of the left child and
+ * the right child is the next node.
+ */
+#[derive(Clone, Copy)]
+struct BvhNode {
+    bounds: Aabb,
+    first: usize,
+    count: usize
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector3D
+}
+
+/**
+ * Bounding volume hierarchy over the bounded primitives of a list.
+ * Unbounded primitives (planes) are kept aside and tested one by one.
+ */
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+
+    primitive_count: usize
+}
+
+impl Default for Bvh {
+    fn default() -> Bvh {
+        return Bvh::new();
+    }
+}
+
+impl Bvh {
+    pub fn new() -> Bvh {
+        return Bvh {
+            nodes: Vec::new(),
+            indices: Vec::new(),
+            unbounded: Vec::new(),
+
+            primitive_count: 0
+        };
+    }
+
+    /**
+     * Builds the hierarchy using the surface area heuristic.
+     *
+     * @param {&[Primitive]} primitives
+     *
+     * @return {Bvh}
+     */
+    pub fn build(primitives: &[Primitive]) -> Bvh {
+        let mut bvh = Bvh::new();
+        bvh.primitive_count = primitives.len();
+
+        let mut items: Vec<BuildItem> = Vec::with_capacity(primitives.len());
+
+        for (index, primitive) in primitives.iter().enumerate() {
+            match primitive.get_bounds() {
+                Some(bounds) => {
+                    items.push(BuildItem {
+                        index: index,
+                        bounds: bounds,
+                        centroid: bounds.get_centroid()
+                    });
+                },
+                None => {
+                    bvh.unbounded.push(index);
+                }
+            }
+        }
+
+        if !items.is_empty() {
+            bvh.nodes.reserve(2 * items.len());
+            bvh.nodes.push(BvhNode { bounds: Aabb::new_empty(), first: 0, count: 0 });
+
+            let count = items.len();
+            bvh.subdivide(&mut items, 0, 0, count);
+
+            bvh.indices = items.iter().map(|item| item.index).collect();
+        }
+
+        return bvh;
+    }
+
+    /**
+     * Number of primitives in the list the hierarchy was built from.
+     */
+    pub fn get_primitive_count(&self) -> usize {
+        return self.primitive_count;
+    }
+
+    fn subdivide(&mut self, items: &mut [BuildItem], node_index: usize, first: usize, count: usize) {
+        let node_items = &mut items[first..first + count];
+
+        let mut bounds = Aabb::new_empty();
+        let mut centroid_bounds = Aabb::new_empty();
+        for item in node_items.iter() {
+            bounds = bounds.union(&item.bounds);
+            centroid_bounds = centroid_bounds.grow(&item.centroid);
+        }
+
+        self.nodes[node_index] = BvhNode { bounds: bounds, first: first, count: count };
+
+        if count <= 1 {
+            return;
+        }
+
+        // Find the cheapest split along the three axes.
+        let leaf_cost = INTERSECTION_COST * count as f32;
+        let mut best_cost = f32::MAX;
+        let mut best_axis = 0;
+        let mut best_split = 0.0;
+
+        for axis in 0..3 {
+            let axis_min = get_axis(&centroid_bounds.min, axis);
+            let axis_max = get_axis(&centroid_bounds.max, axis);
+
+            if axis_max - axis_min <= 0.0 {
+                continue;
+            }
+
+            let mut bin_bounds = [Aabb::new_empty(); BIN_COUNT];
+            let mut bin_counts = [0usize; BIN_COUNT];
+
+            let scale = BIN_COUNT as f32 / (axis_max - axis_min);
+            for item in node_items.iter() {
+                let bin = (((get_axis(&item.centroid, axis) - axis_min) * scale) as usize).min(BIN_COUNT - 1);
+
+                bin_bounds[bin] = bin_bounds[bin].union(&item.bounds);
+                bin_counts[bin] += 1;
+            }
+
+            // Sweep from the right to know the cost of every right side.
+            let mut right_areas = [0.0f32; BIN_COUNT];
+            let mut right_counts = [0usize; BIN_COUNT];
+            let mut right_bounds = Aabb::new_empty();
+            let mut right_count = 0;
+            for bin in (1..BIN_COUNT).rev() {
+                right_bounds = right_bounds.union(&bin_bounds[bin]);
+                right_count += bin_counts[bin];
+
+                right_areas[bin] = right_bounds.get_surface_area();
+                right_counts[bin] = right_count;
+            }
+
+            let mut left_bounds = Aabb::new_empty();
+            let mut left_count = 0;
+            for bin in 0..BIN_COUNT - 1 {
+                left_bounds = left_bounds.union(&bin_bounds[bin]);
+                left_count += bin_counts[bin];
+
+                let cost = left_bounds.get_surface_area() * left_count as f32
+                    + right_areas[bin + 1] * right_counts[bin + 1] as f32;
+
+                if cost < best_cost {
+                    best_cost = cost;
+                    best_axis = axis;
+                    best_split = axis_min + (bin + 1) as f32 / scale;
+                }
+            }
+        }
+
+        let parent_area = bounds.get_surface_area();
+        let split_cost = if parent_area > 0.0 {
+            TRAVERSAL_COST + INTERSECTION_COST * best_cost / parent_area
+        } else {
+            f32::MAX
+        };
+
+        if best_cost == f32::MAX || (split_cost >= leaf_cost && count <= MAX_LEAF_SIZE) {
+            return;
+        }
+
+        // Partition the items in place.
+        let mut left_count = 0;
+        for i in 0..count {
+            if get_axis(&node_items[i].centroid, best_axis) < best_split {
+                node_items.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+
+        // Float error can leave one side empty, split by the middle then.
+        // The total order keeps broken input with NaN centroids from panicking.
+        if left_count == 0 || left_count == count {
+            node_items.sort_by(|a, b| {
+                get_axis(&a.centroid, best_axis).total_cmp(&get_axis(&b.centroid, best_axis))
+            });
+            left_count = count / 2;
+        }
+
+        let left_index = self.nodes.len();
+        self.nodes.push(BvhNode { bounds: Aabb::new_empty(), first: 0, count: 0 });
+        self.nodes.push(BvhNode { bounds: Aabb::new_empty(), first: 0, count: 0 });
+
+        self.nodes[node_index].first = left_index;
+        self.nodes[node_index].count = 0;
+
+        self.subdivide(items, left_index, first, left_count);
+        self.subdivide(items, left_index + 1, first + left_count, count - left_count);
+    }
+
+    /**
+     * Finds the closest primitive hit by the line.
+     *
+     * @param {&[Primitive]} primitives The same list used to build the hierarchy.
+     * @param {Line} line
+     *
+     * @return {Option<Hit>} The closest hit, with the primitive index filled.
+     */
+    pub fn closest_hit(&self, primitives: &[Primitive], line: &Line) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        let mut hit_distance: f32 = f32::MAX;
+
+        for index in self.unbounded.iter() {
+            if let Some(mut hit) = primitives[*index].intersects(line) {
+                if hit.t < hit_distance {
+                    hit_distance = hit.t;
+                    hit.primitive_index = *index;
+                    closest = Some(hit);
+                }
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let inverse_direction = Vector3D::new(
+            1.0 / line.direction.x,
+            1.0 / line.direction.y,
+            1.0 / line.direction.z
+        );
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.bounds.intersects(line, &inverse_direction, hit_distance).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for index in self.indices[node.first..node.first + node.count].iter() {
+                    if let Some(mut hit) = primitives[*index].intersects(line) {
+                        if hit.t < hit_distance {
+                            hit_distance = hit.t;
+                            hit.primitive_index = *index;
+                            closest = Some(hit);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearest child first, it's pushed last.
+            let left = node.first;
+            let right = node.first + 1;
+
+            let left_t = self.nodes[left].bounds.intersects(line, &inverse_direction, hit_distance);
+            let right_t = self.nodes[right].bounds.intersects(line, &inverse_direction, hit_distance);
+
+            match (left_t, right_t) {
+                (Some(lt), Some(rt)) => {
+                    if lt < rt {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                },
+                (Some(_), None) => { stack.push(left); },
+                (None, Some(_)) => { stack.push(right); },
+                (None, None) => {}
+            }
+        }
+
+        return closest;
+    }
+
+    /**
+     * Checks if the line hits any primitive before the given distance.
+     *
+     * @param {&[Primitive]} primitives The same list used to build the hierarchy.
+     * @param {Line} line
+     * @param {f32} max_t
+     *
+     * @return {bool}
+     */
+    pub fn any_hit(&self, primitives: &[Primitive], line: &Line, max_t: f32) -> bool {
+        for index in self.unbounded.iter() {
+            if let Some(hit) = primitives[*index].intersects(line) {
+                if hit.t < max_t {
+                    return true;
+                }
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inverse_direction = Vector3D::new(
+            1.0 / line.direction.x,
+            1.0 / line.direction.y,
+            1.0 / line.direction.z
+        );
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.bounds.intersects(line, &inverse_direction, max_t).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for index in self.indices[node.first..node.first + node.count].iter() {
+                    if let Some(hit) = primitives[*index].intersects(line) {
+                        if hit.t < max_t {
+                            return true;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            stack.push(node.first);
+            stack.push(node.first + 1);
+        }
+
+        return false;
+    }
+}
@@ -1,11 +1,36 @@
-use crate::vector3d::Vector3D;
+use crate::vector3d::{Vector3D, vec_multiplication};
 
 #[derive(Clone, Copy)]
 pub struct Material {
     pub base_color: Vector3D,
     pub emision_color: Vector3D,
     pub roughness: f32,
-    pub metalic: f32
+    pub metalic: f32,
+
+    // Chance of the light going through the surface as in glass, 0 is opaque.
+    pub transmission: f32,
+    // Index of refraction of the inside.
+    pub ior: f32,
+    // Color of the light after going through one unit inside the material.
+    pub absorption_color: Vector3D,
+
+    // Chance of a line hitting the surface at all, the rest go straight through
+    // as if it wasn't there. Less than 1 for cut outs like leaves on a card.
+    pub opacity: f32,
+
+    // Indices of the world textures that multiply the values above at every hit,
+    // roughness and metalic use the luminance of the texture.
+    pub base_color_texture: Option<usize>,
+    pub emission_texture: Option<usize>,
+    pub roughness_texture: Option<usize>,
+    pub metalic_texture: Option<usize>,
+
+    // Tangent space normals stored as colors, the scale bends them more or less.
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    // Heights from the luminance, the scale is the height of white in world units.
+    pub bump_texture: Option<usize>,
+    pub bump_scale: f32
 }
 
 impl Material {
@@ -14,10 +39,42 @@ impl Material {
             base_color: base,
             emision_color: emit,
             roughness: roughness,
-            metalic: metal
+            metalic: metal,
+
+            transmission: 0.0,
+            ior: 1.5,
+            absorption_color: Vector3D::new_as_one(),
+            opacity: 1.0,
+
+            base_color_texture: None,
+            emission_texture: None,
+            roughness_texture: None,
+            metalic_texture: None,
+
+            normal_texture: None,
+            normal_scale: 1.0,
+            bump_texture: None,
+            bump_scale: 1.0
         };
     }
 
+    /**
+     * Smooth transparent material like glass or water.
+     *
+     * @param {f32} ior Index of refraction, 1.33 for water, 1.5 for glass.
+     * @param {Vector3D} absorption_color Color after one unit inside, white is clear.
+     *
+     * @return {Material}
+     */
+    pub fn new_dielectric(ior: f32, absorption_color: Vector3D) -> Material {
+        let mut material = Material::new(Vector3D::new_as_one(), Vector3D::new_as_zero(), 0.0, 0.0);
+        material.transmission = 1.0;
+        material.ior = ior;
+        material.absorption_color = absorption_color;
+
+        return material;
+    }
+
     pub fn new_base(base: Vector3D) -> Material {
         return Material::new(base, Vector3D::new_as_zero(), 0.25, 0.1);
     }
@@ -91,4 +148,199 @@ fn gamma_correct(input: f32) -> f32 {
     }
 
     return l;
-}
\ No newline at end of file
+}
+
+/**
+ * Converts a sRGB color, like the ones stored in 8 bit images, to the linear color space.
+ *
+ * @param {Vector3D} color
+ * @return {Vector3D} Color in linear color space.
+ */
+pub fn srgb_color_to_linear(color: &Vector3D) -> Vector3D {
+    return Vector3D::new(
+        gamma_expand(color.x),
+        gamma_expand(color.y),
+        gamma_expand(color.z)
+    );
+}
+
+/**
+ * Uses the sRGB to linear formula to a single value
+ *
+ * @param {f32} input
+ *
+ * @return {f32}
+ */
+fn gamma_expand(input: f32) -> f32 {
+    let l = input.clamp(0.0, 1.0);
+
+    if l <= 0.04045 {
+        return l / 12.92;
+    }
+
+    return ((l + 0.055) / 1.055).powf(2.4);
+}
+
+// ================================================
+// Tone mapping
+// ================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+    // Clips the values above one.
+    Clamp,
+    // L / (1 + L) on the luminance.
+    Reinhard,
+    // Reinhard that maps the white point to one.
+    ExtendedReinhard,
+    // Narkowicz fit of the ACES filmic curve.
+    Aces,
+    // Uncharted 2 filmic curve by John Hable.
+    Hable
+}
+
+impl ToneMapOperator {
+    /**
+     * Finds the operator by the name used in scene files and the command line.
+     *
+     * @param {&str} name
+     *
+     * @return {Option<ToneMapOperator>}
+     */
+    pub fn from_name(name: &str) -> Option<ToneMapOperator> {
+        return match name.to_lowercase().as_str() {
+            "clamp" | "none" => Some(ToneMapOperator::Clamp),
+            "reinhard" => Some(ToneMapOperator::Reinhard),
+            "extended_reinhard" | "reinhard_extended" => Some(ToneMapOperator::ExtendedReinhard),
+            "aces" => Some(ToneMapOperator::Aces),
+            "hable" | "uncharted" => Some(ToneMapOperator::Hable),
+            _ => None
+        };
+    }
+}
+
+/**
+ * Maps the linear radiance of the film to the [0, 1] range before the sRGB encoding.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+
+    // Exposure value, every step doubles the radiance.
+    pub exposure: f32,
+
+    // Smallest radiance mapped to white by the extended Reinhard and Hable operators.
+    pub white_point: f32
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f32, white_point: f32) -> ToneMapping {
+        return ToneMapping {
+            operator: operator,
+            exposure: exposure,
+            white_point: white_point
+        };
+    }
+
+    /**
+     * Keeps the radiance as it is and clips it, like the renders before tone mapping.
+     */
+    pub fn new_clamp() -> ToneMapping {
+        return ToneMapping::new(ToneMapOperator::Clamp, 0.0, 4.0);
+    }
+
+    /**
+     * Applies the exposure and the operator.
+     *
+     * @param {Vector3D} color Linear radiance.
+     *
+     * @return {Vector3D} Linear color in the [0, 1] range.
+     */
+    pub fn apply(&self, color: &Vector3D) -> Vector3D {
+        let exposed = vec_multiplication(color, 2.0f32.powf(self.exposure));
+        let white = self.white_point.max(1e-4);
+
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => exposed,
+            ToneMapOperator::Reinhard => {
+                let luminance = get_luminance(&exposed);
+                scale_luminance(&exposed, luminance, luminance / (1.0 + luminance))
+            },
+            ToneMapOperator::ExtendedReinhard => {
+                let luminance = get_luminance(&exposed);
+                let mapped_luminance = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+                scale_luminance(&exposed, luminance, mapped_luminance)
+            },
+            ToneMapOperator::Aces => Vector3D::new(
+                aces_curve(exposed.x),
+                aces_curve(exposed.y),
+                aces_curve(exposed.z)
+            ),
+            ToneMapOperator::Hable => {
+                let white_scale = 1.0 / hable_curve(white);
+                Vector3D::new(
+                    hable_curve(exposed.x) * white_scale,
+                    hable_curve(exposed.y) * white_scale,
+                    hable_curve(exposed.z) * white_scale
+                )
+            }
+        };
+
+        return Vector3D::new(
+            clamp_unit(mapped.x),
+            clamp_unit(mapped.y),
+            clamp_unit(mapped.z)
+        );
+    }
+
+    /**
+     * Tone maps the radiance and encodes it for display.
+     *
+     * @param {Vector3D} color Linear radiance.
+     *
+     * @return {Vector3D} Color in sRGB color space.
+     */
+    pub fn to_srgb(&self, color: &Vector3D) -> Vector3D {
+        return linear_color_to_srgb(&self.apply(color));
+    }
+}
+
+/**
+ * Relative luminance of a linear Rec. 709 color.
+ *
+ * @param {Vector3D} color
+ *
+ * @return {f32}
+ */
+pub fn get_luminance(color: &Vector3D) -> f32 {
+    return 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+}
+
+fn scale_luminance(color: &Vector3D, luminance: f32, mapped_luminance: f32) -> Vector3D {
+    if luminance <= 0.0 {
+        return Vector3D::new_as_zero();
+    }
+
+    return vec_multiplication(color, mapped_luminance / luminance);
+}
+
+fn aces_curve(x: f32) -> f32 {
+    let x = x.max(0.0);
+
+    return (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+}
+
+fn hable_curve(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    let x = x.max(0.0);
+
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+fn clamp_unit(value: f32) -> f32 {
+    if value.is_nan() {
+        return 0.0;
+    }
+
+    return value.clamp(0.0, 1.0);
+}
@@ -0,0 +1,111 @@
+/**
+ * Discrete distribution proportional to a list of weights, sampled by inverting the CDF.
+ */
+#[derive(Clone)]
+pub struct Distribution1D {
+    // Running sums of the weights, normalized so the last one is 1.
+    cdf: Vec<f32>,
+    // Last index with a positive weight, samples never go past it.
+    last_index: usize,
+
+    total: f32
+}
+
+impl Distribution1D {
+    /**
+     * Negative and NaN weights count as zero.
+     *
+     * @param {&[f32]} weights
+     *
+     * @return {Distribution1D}
+     */
+    pub fn new(weights: &[f32]) -> Distribution1D {
+        let mut cdf: Vec<f32> = Vec::with_capacity(weights.len());
+
+        let mut total: f32 = 0.0;
+        let mut last_index: usize = 0;
+        for (index, weight) in weights.iter().enumerate() {
+            if *weight > 0.0 {
+                total += weight;
+                last_index = index;
+            }
+
+            cdf.push(total);
+        }
+
+        if total > 0.0 {
+            for value in cdf.iter_mut() {
+                *value /= total;
+            }
+
+            // Float error must not leave room past the last weight.
+            for value in cdf[last_index..].iter_mut() {
+                *value = 1.0;
+            }
+        }
+
+        return Distribution1D {
+            cdf: cdf,
+            last_index: last_index,
+            total: total
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.cdf.len();
+    }
+
+    /**
+     * There is nothing to sample when all the weights are zero.
+     *
+     * @return {bool}
+     */
+    pub fn is_empty(&self) -> bool {
+        return self.total <= 0.0;
+    }
+
+    /**
+     * Sum of the weights.
+     *
+     * @return {f32}
+     */
+    pub fn get_total(&self) -> f32 {
+        return self.total;
+    }
+
+    /**
+     * Chance of sampling an index, its weight over the total.
+     *
+     * @param {usize} index
+     *
+     * @return {f32}
+     */
+    pub fn get_probability(&self, index: usize) -> f32 {
+        if self.is_empty() || index >= self.cdf.len() {
+            return 0.0;
+        }
+
+        let previous = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+
+        return self.cdf[index] - previous;
+    }
+
+    /**
+     * Chooses an index.
+     *
+     * @param {f32} u Uniform number in [0, 1).
+     *
+     * @return {Option<(usize, f32)>} The index and its probability, None if the distribution is empty.
+     */
+    pub fn sample(&self, u: f32) -> Option<(usize, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        // First entry whose running sum goes past u, zero weights are skipped.
+        // A u rounded up to 1 ends on the last positive weight, not on the zeros after it.
+        let index = self.cdf.partition_point(|value| *value <= u).min(self.last_index);
+
+        return Some((index, self.get_probability(index)));
+    }
+}
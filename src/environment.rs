@@ -0,0 +1,427 @@
+use std::f32;
+
+use rand::Rng;
+
+use crate::color::{get_luminance};
+use crate::distribution::{Distribution1D};
+use crate::lights::{Light, LightSample};
+use crate::renderer::{Film};
+use crate::sky::{PreethamSky};
+use crate::vector3d::{
+    Vector3D, sample_uniform_hemisphere,
+    vec_sum, vec_multiplication, vec_normalize
+};
+
+/**
+ * Direction chosen uniformly over the whole sphere.
+ *
+ * @param {f32} u1
+ * @param {f32} u2
+ *
+ * @return {Vector3D}
+ */
+fn sample_uniform_sphere(u1: f32, u2: f32) -> Vector3D {
+    let y = 1.0 - 2.0 * u1;
+    let ring = (1.0 - y * y).max(0.0).sqrt();
+    let phi = 2.0 * f32::consts::PI * u2;
+
+    return Vector3D::new(ring * phi.cos(), y, ring * phi.sin());
+}
+
+// Density of the directions chosen over the whole sphere.
+const UNIFORM_SPHERE_PDF: f32 = 1.0 / (4.0 * f32::consts::PI);
+
+// Density of the directions chosen over the upper hemisphere.
+const UNIFORM_HEMISPHERE_PDF: f32 = 1.0 / (2.0 * f32::consts::PI);
+
+/**
+ * Linear interpolation between two colors.
+ *
+ * @param {Vector3D} a
+ * @param {Vector3D} b
+ * @param {f32} t
+ *
+ * @return {Vector3D}
+ */
+fn mix(a: &Vector3D, b: &Vector3D, t: f32) -> Vector3D {
+    return vec_sum(&vec_multiplication(a, 1.0 - t), &vec_multiplication(b, t));
+}
+
+fn is_black(color: &Vector3D) -> bool {
+    return color.x <= 0.0 && color.y <= 0.0 && color.z <= 0.0;
+}
+
+// ================================================
+// Gradient
+// ================================================
+
+/**
+ * Sky that fades from the horizon to the zenith, with a flat ground below the horizon.
+ */
+#[derive(Clone, Copy)]
+pub struct Gradient {
+    pub zenith: Vector3D,
+    pub horizon: Vector3D,
+    pub ground: Vector3D
+}
+
+impl Gradient {
+    pub fn new(zenith: Vector3D, horizon: Vector3D, ground: Vector3D) -> Gradient {
+        return Gradient {
+            zenith: zenith,
+            horizon: horizon,
+            ground: ground
+        };
+    }
+
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        if direction.y < 0.0 {
+            return self.ground;
+        }
+
+        return mix(&self.horizon, &self.zenith, direction.y.min(1.0));
+    }
+}
+
+// ================================================
+// Environment map
+// ================================================
+
+/**
+ * Equirectangular image around the scene. The center of the image is seen looking
+ * down the -Z axis and its top row is straight up.
+ * Pixels are chosen proportionally to their luminance times the solid angle they
+ * cover, then a direction is chosen uniformly inside the pixel.
+ */
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    image: Film,
+
+    // Turn around the up axis, in radians.
+    rotation: f32,
+
+    // Scales the radiance of the image.
+    intensity: f32,
+
+    // Chooses the row, then the column inside it.
+    rows: Distribution1D,
+    columns: Vec<Distribution1D>,
+
+    // Solid angle of the pixels of every row.
+    solid_angles: Vec<f32>,
+
+    // Mean radiance over the sphere.
+    average: Vector3D
+}
+
+impl EnvironmentMap {
+    /**
+     * @param {Film} image Linear radiance, in equirectangular layout.
+     * @param {f32} rotation Turn around the up axis, in radians.
+     * @param {f32} intensity
+     *
+     * @return {EnvironmentMap}
+     */
+    pub fn new(image: Film, rotation: f32, intensity: f32) -> EnvironmentMap {
+        let mut row_weights: Vec<f32> = Vec::with_capacity(image.height);
+        let mut columns: Vec<Distribution1D> = Vec::with_capacity(image.height);
+        let mut solid_angles: Vec<f32> = Vec::with_capacity(image.height);
+
+        let mut average = Vector3D::new_as_zero();
+
+        for y in 0..image.height {
+            // Rows near the poles cover less of the sphere.
+            let (top, bottom) = EnvironmentMap::get_row_cosines(image.height, y);
+            let solid_angle = 2.0 * f32::consts::PI * (top - bottom) / image.width as f32;
+
+            let mut weights: Vec<f32> = Vec::with_capacity(image.width);
+            for x in 0..image.width {
+                let color = image.get_pixel(x, y);
+
+                weights.push(get_luminance(&color) * solid_angle);
+                average = vec_sum(&average, &vec_multiplication(&color, solid_angle));
+            }
+
+            let row = Distribution1D::new(&weights);
+            row_weights.push(row.get_total());
+            columns.push(row);
+            solid_angles.push(solid_angle);
+        }
+
+        let average = vec_multiplication(&average, intensity / (4.0 * f32::consts::PI));
+
+        return EnvironmentMap {
+            image: image,
+
+            rotation: rotation,
+            intensity: intensity,
+
+            rows: Distribution1D::new(&row_weights),
+            columns: columns,
+            solid_angles: solid_angles,
+
+            average: average
+        };
+    }
+
+    /**
+     * Cosines of the polar angle at the top and the bottom edges of a row.
+     *
+     * @param {usize} height
+     * @param {usize} y
+     *
+     * @return {(f32, f32)}
+     */
+    fn get_row_cosines(height: usize, y: usize) -> (f32, f32) {
+        let top = (f32::consts::PI * y as f32 / height as f32).cos();
+        let bottom = (f32::consts::PI * (y + 1) as f32 / height as f32).cos();
+
+        return (top, bottom);
+    }
+
+    /**
+     * Turns a direction around the up axis.
+     *
+     * @param {Vector3D} direction
+     * @param {f32} angle Radians.
+     *
+     * @return {Vector3D}
+     */
+    fn rotate(direction: &Vector3D, angle: f32) -> Vector3D {
+        let (sin, cos) = angle.sin_cos();
+
+        return Vector3D::new(
+            direction.x * cos + direction.z * sin,
+            direction.y,
+            direction.z * cos - direction.x * sin
+        );
+    }
+
+    /**
+     * Image coordinates of a direction.
+     *
+     * @param {Vector3D} direction
+     *
+     * @return {(f32, f32)} u and v in [0, 1], v grows downwards.
+     */
+    fn get_image_coordinates(&self, direction: &Vector3D) -> (f32, f32) {
+        let local = EnvironmentMap::rotate(&vec_normalize(direction), -self.rotation);
+
+        let phi = local.x.atan2(-local.z);
+        let theta = local.y.clamp(-1.0, 1.0).acos();
+
+        return (0.5 + phi / (2.0 * f32::consts::PI), theta / f32::consts::PI);
+    }
+
+    /**
+     * Direction of a horizontal image coordinate and height.
+     *
+     * @param {f32} u
+     * @param {f32} cos_theta Cosine of the angle with the up axis.
+     *
+     * @return {Vector3D}
+     */
+    fn get_direction(&self, u: f32, cos_theta: f32) -> Vector3D {
+        let phi = (u - 0.5) * 2.0 * f32::consts::PI;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+        let local = Vector3D::new(sin_theta * phi.sin(), cos_theta, -sin_theta * phi.cos());
+
+        return EnvironmentMap::rotate(&local, self.rotation);
+    }
+
+    /**
+     * Pixel that contains the image coordinates.
+     *
+     * @param {f32} u
+     * @param {f32} v
+     *
+     * @return {(usize, usize)}
+     */
+    fn to_pixel(&self, u: f32, v: f32) -> (usize, usize) {
+        let x = ((u * self.image.width as f32) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f32) as usize).min(self.image.height - 1);
+
+        return (x, y);
+    }
+
+    /**
+     * Converts the chance of choosing a pixel to solid angle density.
+     *
+     * @param {usize} x
+     * @param {usize} y
+     *
+     * @return {f32}
+     */
+    fn get_pixel_pdf(&self, x: usize, y: usize) -> f32 {
+        let solid_angle = self.solid_angles[y];
+        if solid_angle <= 0.0 {
+            return 0.0;
+        }
+
+        return self.rows.get_probability(y) * self.columns[y].get_probability(x) / solid_angle;
+    }
+
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        if self.image.pixels.is_empty() {
+            return Vector3D::new_as_zero();
+        }
+
+        let (u, v) = self.get_image_coordinates(direction);
+        let (x, y) = self.to_pixel(u, v);
+
+        return vec_multiplication(&self.image.get_pixel(x, y), self.intensity);
+    }
+
+    pub fn get_average_radiance(&self) -> Vector3D {
+        return self.average;
+    }
+}
+
+impl Light for EnvironmentMap {
+    fn sample<R: Rng + ?Sized>(&self, _position: &Vector3D, rng: &mut R) -> Option<LightSample> {
+        if self.intensity <= 0.0 {
+            return None;
+        }
+
+        let (y, _) = self.rows.sample(rng.gen::<f32>())?;
+        let (x, _) = self.columns[y].sample(rng.gen::<f32>())?;
+
+        let pdf = self.get_pixel_pdf(x, y);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        // Uniform over the solid angle of the pixel.
+        let (top, bottom) = EnvironmentMap::get_row_cosines(self.image.height, y);
+        let u = (x as f32 + rng.gen::<f32>()) / self.image.width as f32;
+        let cos_theta = top + (bottom - top) * rng.gen::<f32>();
+
+        return Some(LightSample {
+            direction: self.get_direction(u, cos_theta),
+            distance: f32::INFINITY,
+            radiance: vec_multiplication(&self.image.get_pixel(x, y), self.intensity),
+            pdf: pdf,
+            is_delta: false
+        });
+    }
+
+    fn pdf(&self, _position: &Vector3D, direction: &Vector3D) -> f32 {
+        if self.intensity <= 0.0 || self.rows.is_empty() {
+            return 0.0;
+        }
+
+        let (u, v) = self.get_image_coordinates(direction);
+        let (x, y) = self.to_pixel(u, v);
+
+        return self.get_pixel_pdf(x, y);
+    }
+
+    fn is_delta(&self) -> bool {
+        return false;
+    }
+}
+
+// ================================================
+// Environment
+// ================================================
+
+/**
+ * Light coming from infinitely far away in every direction the scene doesn't cover.
+ */
+#[derive(Clone)]
+pub enum Environment {
+    Constant(Vector3D),
+    Gradient(Gradient),
+    Map(EnvironmentMap),
+    Sky(PreethamSky)
+}
+
+impl Environment {
+    /**
+     * Radiance arriving from a direction.
+     *
+     * @param {Vector3D} direction Towards the environment.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        return match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient(gradient) => gradient.get_radiance(direction),
+            Environment::Map(map) => map.get_radiance(direction),
+            Environment::Sky(sky) => sky.get_radiance(direction)
+        };
+    }
+
+    /**
+     * Mean radiance over all the directions, used as ambient light.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_average_radiance(&self) -> Vector3D {
+        return match self {
+            Environment::Constant(color) => *color,
+            // The heights are uniform over the sphere.
+            Environment::Gradient(gradient) => {
+                mix(&mix(&gradient.horizon, &gradient.zenith, 0.5), &gradient.ground, 0.5)
+            },
+            Environment::Map(map) => map.get_average_radiance(),
+            Environment::Sky(sky) => sky.get_average_radiance()
+        };
+    }
+
+    pub fn is_black(&self) -> bool {
+        return match self {
+            Environment::Constant(color) => is_black(color),
+            Environment::Gradient(gradient) => {
+                is_black(&gradient.zenith) && is_black(&gradient.horizon) && is_black(&gradient.ground)
+            },
+            Environment::Map(map) => map.intensity <= 0.0 || map.rows.is_empty(),
+            Environment::Sky(sky) => sky.intensity <= 0.0
+        };
+    }
+}
+
+impl Light for Environment {
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, rng: &mut R) -> Option<LightSample> {
+        if self.is_black() {
+            return None;
+        }
+
+        let (direction, pdf) = match self {
+            Environment::Map(map) => { return map.sample(position, rng); },
+            // Nothing comes from below the horizon.
+            Environment::Sky(_) => {
+                let local = sample_uniform_hemisphere(rng.gen::<f32>(), rng.gen::<f32>());
+
+                (Vector3D::new(local.x, local.z, local.y), UNIFORM_HEMISPHERE_PDF)
+            },
+            _ => (sample_uniform_sphere(rng.gen::<f32>(), rng.gen::<f32>()), UNIFORM_SPHERE_PDF)
+        };
+
+        return Some(LightSample {
+            direction: direction,
+            distance: f32::INFINITY,
+            radiance: self.get_radiance(&direction),
+            pdf: pdf,
+            is_delta: false
+        });
+    }
+
+    fn pdf(&self, position: &Vector3D, direction: &Vector3D) -> f32 {
+        if self.is_black() {
+            return 0.0;
+        }
+
+        return match self {
+            Environment::Map(map) => map.pdf(position, direction),
+            Environment::Sky(_) => if direction.y > 0.0 { UNIFORM_HEMISPHERE_PDF } else { 0.0 },
+            _ => UNIFORM_SPHERE_PDF
+        };
+    }
+
+    fn is_delta(&self) -> bool {
+        return false;
+    }
+}
@@ -1,15 +1,113 @@
+use std::f32::consts::PI;
+
 use crate::vector3d::{
-    Vector3D,
-    vec_normalize, vec_dot, vec_cross,
-    vec_sum, vec_sub, vec_multiplication
+    Vector3D, Onb, J, K,
+    vec_normalize, vec_dot, vec_cross, vec_get_length,
+    vec_sum, vec_sub, vec_multiplication,
+    vec_min, vec_max
 };
 
 const MARGIN: f32 = 0.001f32;
 
 pub trait Intersect {
-    fn intersects(&self, line: &Line) -> Option<f32>;
-    fn get_normal(&self, surface_point: &Vector3D) -> Vector3D;
-    fn get_material_index(&self) -> usize;
+    fn intersects(&self, line: &Line) -> Option<Hit>;
+}
+
+// ================================================
+// Hit record implementation
+// ================================================
+
+/**
+ * Everything known about a line and primitive intersection.
+ *
+ * Normals always point to the outside of the surface, `front_face` tells
+ * whether the line arrived from that side.
+ */
+#[derive(Clone, Copy)]
+pub struct Hit {
+    pub t: f32,
+    pub position: Vector3D,
+
+    pub normal: Vector3D,
+    pub shading_normal: Vector3D,
+
+    // Weights of the a, b and c vertices on triangles.
+    pub barycentric: Vector3D,
+    pub uv: (f32, f32),
+
+    // Change of the position along u and v, zero without a texture mapping.
+    pub dpdu: Vector3D,
+    pub dpdv: Vector3D,
+
+    pub front_face: bool,
+
+    pub primitive_index: usize,
+    pub material_index: usize
+}
+
+impl Hit {
+    pub fn new(line: &Line, t: f32, normal: Vector3D, uv: (f32, f32), material_index: usize) -> Hit {
+        return Hit {
+            t: t,
+            position: line.get_point(t),
+
+            normal: normal,
+            shading_normal: normal,
+
+            barycentric: Vector3D::new_as_zero(),
+            uv: uv,
+
+            dpdu: Vector3D::new_as_zero(),
+            dpdv: Vector3D::new_as_zero(),
+
+            front_face: vec_dot(&line.direction, &normal) < 0.0,
+
+            primitive_index: 0,
+            material_index: material_index
+        };
+    }
+
+    /**
+     * Gets the shading normal flipped to the side the line came from.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_facing_normal(&self) -> Vector3D {
+        if self.front_face {
+            return self.shading_normal;
+        }
+
+        return vec_multiplication(&self.shading_normal, -1.0);
+    }
+
+    /**
+     * Tangent space around the shading normal, as used by normal maps.
+     * The tangent follows u and the bitangent v, any frame is used without a texture mapping.
+     *
+     * @return {Onb} Tangent in `u`, bitangent in `v` and the shading normal in `w`.
+     */
+    pub fn get_tangent_frame(&self) -> Onb {
+        let normal = self.shading_normal;
+        let tangent = vec_sub(&self.dpdu, &vec_multiplication(&normal, vec_dot(&normal, &self.dpdu)));
+
+        if vec_get_length(&tangent) <= 1e-8 {
+            return Onb::new_from_w(&normal);
+        }
+
+        let tangent = vec_normalize(&tangent);
+        let mut bitangent = vec_cross(&normal, &tangent);
+
+        // Mirrored mappings have v on the other side.
+        if vec_dot(&bitangent, &self.dpdv) < 0.0 {
+            bitangent = vec_multiplication(&bitangent, -1.0);
+        }
+
+        return Onb {
+            u: tangent,
+            v: bitangent,
+            w: normal
+        };
+    }
 }
 
 // ================================================
@@ -35,33 +133,121 @@ impl Line {
     }
 }
 
+// ================================================
+// Axis aligned bounding box implementation
+// ================================================
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vector3D,
+    pub max: Vector3D
+}
+
+impl Aabb {
+    pub fn new(min: Vector3D, max: Vector3D) -> Aabb {
+        return Aabb {min: min, max: max};
+    }
+
+    /**
+     * Creates an inverted box, growing it with any point or box gives that point or box.
+     */
+    pub fn new_empty() -> Aabb {
+        return Aabb::new(
+            Vector3D::new(f32::MAX, f32::MAX, f32::MAX),
+            Vector3D::new(f32::MIN, f32::MIN, f32::MIN)
+        );
+    }
+
+    pub fn grow(&self, point: &Vector3D) -> Aabb {
+        return Aabb::new(vec_min(&self.min, point), vec_max(&self.max, point));
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        return Aabb::new(vec_min(&self.min, &other.min), vec_max(&self.max, &other.max));
+    }
+
+    pub fn get_centroid(&self) -> Vector3D {
+        return vec_multiplication(&vec_sum(&self.min, &self.max), 0.5);
+    }
+
+    pub fn get_surface_area(&self) -> f32 {
+        let size = vec_sub(&self.max, &self.min);
+
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0;
+        }
+
+        return 2.0 * (size.x * size.y + size.y * size.z + size.z * size.x);
+    }
+
+    /**
+     * Slab test against the given line.
+     *
+     * @param {Line} line
+     * @param {Vector3D} inverse_direction The per component inverse of the line direction.
+     * @param {f32} max_t Farthest 't' value that is still interesting.
+     *
+     * @return {Option<f32>} The 't' value where the line enters the box or None.
+     */
+    pub fn intersects(&self, line: &Line, inverse_direction: &Vector3D, max_t: f32) -> Option<f32> {
+        let mut t_min: f32 = 0.0;
+        let mut t_max: f32 = max_t;
+
+        let axes = [
+            (line.origin.x, inverse_direction.x, self.min.x, self.max.x),
+            (line.origin.y, inverse_direction.y, self.min.y, self.max.y),
+            (line.origin.z, inverse_direction.z, self.min.z, self.max.z)
+        ];
+
+        for (origin, inverse, min, max) in axes.iter() {
+            let mut t0 = (min - origin) * inverse;
+            let mut t1 = (max - origin) * inverse;
+
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // NaN comparisons are false, so degenerate slabs don't reject the line.
+            if t0 > t_min { t_min = t0; }
+            if t1 < t_max { t_max = t1; }
+
+            if t_min > t_max {
+                return None;
+            }
+        }
+
+        return Some(t_min);
+    }
+}
+
 // ================================================
 // Plane implementation
 // ================================================
 #[derive(Clone, Copy)]
 pub struct Plane {
     pub normal: Vector3D,
-    pub point: Vector3D
+    pub point: Vector3D,
+
+    pub material_index: usize
 }
 
 impl Plane {
-    pub fn new(n: Vector3D, p: Vector3D) -> Plane {
-        return Plane {normal: vec_normalize(&n), point: p};
+    pub fn new(n: Vector3D, p: Vector3D, mat_index: usize) -> Plane {
+        return Plane {normal: vec_normalize(&n), point: p, material_index: mat_index};
     }
 }
 
 impl Intersect for Plane {
     /**
-     * Checks the intersection point on the given line and plane
+     * Checks the intersection point on the given line and plane, from both sides.
      *
      * @param {Line} line
      *
-     * @return {Option<f32>} The 't' line offset value or None.
+     * @return {Option<Hit>}
      */
-    fn intersects(&self, line: &Line) -> Option<f32> {
+    fn intersects(&self, line: &Line) -> Option<Hit> {
         let denom = vec_dot(&self.normal, &line.direction);
 
-        if denom.abs() < MARGIN || denom >= 0.0 {
+        if denom.abs() < MARGIN {
             return None;
         }
 
@@ -71,15 +257,19 @@ impl Intersect for Plane {
             return None;
         }
 
-        return Some(t);
-    }
+        let mut hit = Hit::new(line, t, self.normal, (0.0, 0.0), self.material_index);
 
-    fn get_normal(&self, _: &Vector3D) -> Vector3D {
-        return self.normal;
-    }
+        // Planar mapping, one unit per texture repetition.
+        let helper = if self.normal.y.abs() < 0.9 { J } else { K };
+        let tangent = vec_normalize(&vec_cross(&helper, &self.normal));
+        let bitangent = vec_cross(&self.normal, &tangent);
+
+        let local = vec_sub(&hit.position, &self.point);
+        hit.uv = (vec_dot(&local, &tangent), vec_dot(&local, &bitangent));
+        hit.dpdu = tangent;
+        hit.dpdv = bitangent;
 
-    fn get_material_index(&self) -> usize {
-        return 0;
+        return Some(hit);
     }
 }
 
@@ -106,9 +296,9 @@ impl Intersect for Sphere {
      *
      * @param Line line
      *
-     * @return {Option<f32>} The 't' line offset value or None.
+     * @return {Option<Hit>}
      */
-    fn intersects(&self, line: &Line) -> Option<f32> {
+    fn intersects(&self, line: &Line) -> Option<Hit> {
         // Quadratic ecuation
         // -b +- SQRT( b*b -4*a*c ) / 2*a
 
@@ -129,11 +319,11 @@ impl Intersect for Sphere {
             return None;
         }
 
-        let tn: f32 = (- b - root.sqrt()) / 2.0 * a;
-        let tp: f32  = (- b + root.sqrt()) / 2.0 * a;
+        let tn: f32 = (- b - root.sqrt()) / (2.0 * a);
+        let tp: f32  = (- b + root.sqrt()) / (2.0 * a);
 
         let mut t: f32 = tp;
-        if tn > 0.0 && tn < tp {
+        if tn > MARGIN && tn < tp {
             t = tn;
         }
 
@@ -141,17 +331,26 @@ impl Intersect for Sphere {
             return None;
         }
 
-        return Some(t);
-    }
+        let point = line.get_point(t);
+        let normal = vec_normalize(&vec_sub(&point, &self.origin));
 
-    fn get_normal(&self, surface_point: &Vector3D) -> Vector3D {
-        let normal = vec_sub(&surface_point, &self.origin);
+        // Spherical mapping, 'u' goes around the Y axis and 'v' from bottom to top.
+        let phi = (-normal.z).atan2(normal.x) + PI;
+        let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+        let uv = (phi / (2.0 * PI), theta / PI);
 
-        return vec_normalize(&normal);
-    }
+        let mut hit = Hit::new(line, t, normal, uv, self.material_index);
+
+        // Around the Y axis for u and along the meridian for v, both vanish at the poles.
+        let local = vec_sub(&point, &self.origin);
+        let ring = (local.x * local.x + local.z * local.z).sqrt();
 
-    fn get_material_index(&self) -> usize {
-        return self.material_index;
+        hit.dpdu = Vector3D::new(2.0 * PI * local.z, 0.0, -2.0 * PI * local.x);
+        if ring > 0.0 {
+            hit.dpdv = vec_multiplication(&Vector3D::new(-local.y * local.x / ring, ring, -local.y * local.z / ring), PI);
+        }
+
+        return Some(hit);
     }
 }
 
@@ -166,7 +365,13 @@ pub struct Triangle {
     pub c: Vector3D,
 
     pub inner_plane: Plane,
-    pub material_index: usize
+    pub material_index: usize,
+
+    // Optional per vertex attributes, in the a, b, c order.
+    pub normals: Option<[Vector3D; 3]>,
+    pub uvs: Option<[(f32, f32); 3]>,
+    // Smooth directions of increasing u, they need the uvs.
+    pub tangents: Option<[Vector3D; 3]>
 }
 
 impl Triangle {
@@ -176,64 +381,229 @@ impl Triangle {
 
         let normal: Vector3D = vec_normalize(&vec_cross(&ac, &ab));
 
-        let the_plane: Plane = Plane::new(normal, a);
+        let the_plane: Plane = Plane::new(normal, a, mat_index);
 
         return Triangle {
             a: a, b: b, c: c,
             material_index: mat_index,
-            inner_plane: the_plane
+            inner_plane: the_plane,
+
+            normals: None,
+            uvs: None,
+            tangents: None
+        }
+    }
+
+    /**
+     * Sets the vertex normals used to smooth the shading normal.
+     * Zero or broken normals, written by some exporters, are replaced by the face normal.
+     *
+     * @param {[Vector3D; 3]} normals Normals of the a, b and c vertices.
+     */
+    pub fn set_normals(&mut self, normals: [Vector3D; 3]) {
+        let face_normal = self.inner_plane.normal;
+        let fix = |normal: &Vector3D| {
+            let length = vec_get_length(normal);
+            if !(length > 0.0 && length.is_finite()) {
+                return face_normal;
+            }
+
+            return vec_multiplication(normal, 1.0 / length);
+        };
+
+        self.normals = Some([fix(&normals[0]), fix(&normals[1]), fix(&normals[2])]);
+    }
+
+    /**
+     * Sets the vertex texture coordinates.
+     *
+     * @param {[(f32, f32); 3]} uvs Coordinates of the a, b and c vertices.
+     */
+    pub fn set_uvs(&mut self, uvs: [(f32, f32); 3]) {
+        self.uvs = Some(uvs);
+    }
+
+    /**
+     * Sets the vertex tangents that replace the direction of the flat dpdu.
+     *
+     * @param {[Vector3D; 3]} tangents Tangents of the a, b and c vertices.
+     */
+    pub fn set_tangents(&mut self, tangents: [Vector3D; 3]) {
+        self.tangents = Some([
+            vec_normalize(&tangents[0]),
+            vec_normalize(&tangents[1]),
+            vec_normalize(&tangents[2])
+        ]);
+    }
+
+    /**
+     * Change of the position along the texture coordinates, constant over the triangle.
+     *
+     * @return {Option<(Vector3D, Vector3D)>} dpdu and dpdv, None without uvs or with degenerate ones.
+     */
+    pub fn get_uv_derivatives(&self) -> Option<(Vector3D, Vector3D)> {
+        let uvs = self.uvs?;
+
+        let (du02, dv02) = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+        let (du12, dv12) = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+
+        let determinant = du02 * dv12 - dv02 * du12;
+        if determinant.abs() < 1e-12 {
+            return None;
         }
+
+        let dp02 = vec_sub(&self.a, &self.c);
+        let dp12 = vec_sub(&self.b, &self.c);
+
+        let dpdu = vec_sub(&vec_multiplication(&dp02, dv12), &vec_multiplication(&dp12, dv02));
+        let dpdv = vec_sub(&vec_multiplication(&dp12, du02), &vec_multiplication(&dp02, du12));
+
+        return Some((vec_multiplication(&dpdu, 1.0 / determinant), vec_multiplication(&dpdv, 1.0 / determinant)));
     }
 }
 
 impl Intersect for Triangle {
-    fn intersects(&self, line: &Line) -> Option<f32> {
-        if let Some(t) = self.inner_plane.intersects(line) {
-            let point: Vector3D = line.get_point(t);
-            let tri_normal: Vector3D = self.get_normal(&point);
-
-            // Calculates the cross product of the edge and the normal,
-            //   if it's negative, the point is outside.
-            let ab: Vector3D = vec_sub(&self.b, &self.a);
-            let pa: Vector3D = vec_sub(&point, &self.a);
-
-            let diff = vec_dot(&tri_normal, &vec_cross(&pa, &ab));
-            if diff <= 0.0 {
-                return None;
-            }
+    /**
+     * Moller-Trumbore intersection, works from both sides of the triangle.
+     *
+     * @param {Line} line
+     *
+     * @return {Option<Hit>}
+     */
+    fn intersects(&self, line: &Line) -> Option<Hit> {
+        let ab: Vector3D = vec_sub(&self.b, &self.a);
+        let ac: Vector3D = vec_sub(&self.c, &self.a);
 
-            let bc: Vector3D = vec_sub(&self.c, &self.b);
-            let pb: Vector3D = vec_sub(&point, &self.b);
+        let p = vec_cross(&line.direction, &ac);
+        let det = vec_dot(&ab, &p);
 
-            let diff = vec_dot(&tri_normal, &vec_cross(&pb, &bc));
-            if diff <= 0.0 {
-                return None;
-            }
+        // The line is parallel to the triangle.
+        if det.abs() < 1e-8 {
+            return None;
+        }
 
-            let ca: Vector3D = vec_sub(&self.a, &self.c);
-            let pc: Vector3D = vec_sub(&point, &self.c);
+        let inverse_det = 1.0 / det;
+        let ao: Vector3D = vec_sub(&line.origin, &self.a);
 
-            let diff = vec_dot(&tri_normal, &vec_cross(&pc, &ca));
-            if diff <= 0.0 {
-                return None;
+        let u = vec_dot(&ao, &p) * inverse_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = vec_cross(&ao, &ab);
+        let v = vec_dot(&line.direction, &q) * inverse_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = vec_dot(&ac, &q) * inverse_det;
+        if t < MARGIN {
+            return None;
+        }
+
+        let w = 1.0 - u - v;
+
+        let mut hit = Hit::new(line, t, self.inner_plane.normal, (u, v), self.material_index);
+        hit.barycentric = Vector3D::new(w, u, v);
+
+        if let Some(normals) = self.normals {
+            let smooth = vec_sum(
+                &vec_sum(
+                    &vec_multiplication(&normals[0], w),
+                    &vec_multiplication(&normals[1], u)
+                ),
+                &vec_multiplication(&normals[2], v)
+            );
+
+            // Opposite vertex normals can cancel out, the flat normal is kept then.
+            let length = vec_get_length(&smooth);
+            if length > 0.0 {
+                hit.shading_normal = vec_multiplication(&smooth, 1.0 / length);
             }
+        }
+
+        if let Some(uvs) = self.uvs {
+            hit.uv = (
+                uvs[0].0 * w + uvs[1].0 * u + uvs[2].0 * v,
+                uvs[0].1 * w + uvs[1].1 * u + uvs[2].1 * v
+            );
+        }
 
-            return Some(t);
+        if let Some((dpdu, dpdv)) = self.get_uv_derivatives() {
+            hit.dpdu = dpdu;
+            hit.dpdv = dpdv;
+
+            if let Some(tangents) = self.tangents {
+                let smooth = vec_sum(
+                    &vec_sum(
+                        &vec_multiplication(&tangents[0], w),
+                        &vec_multiplication(&tangents[1], u)
+                    ),
+                    &vec_multiplication(&tangents[2], v)
+                );
+
+                if vec_get_length(&smooth) > 0.0 {
+                    hit.dpdu = vec_multiplication(&vec_normalize(&smooth), vec_get_length(&dpdu));
+                }
+            }
         }
-        return None;
+
+        return Some(hit);
     }
+}
 
-    fn get_normal(&self, _: &Vector3D) -> Vector3D {
-        return self.inner_plane.normal;
+// ================================================
+// Primitive implementation
+// ================================================
+
+/**
+ * Any shape that can be stored in the world.
+ */
+#[derive(Clone, Copy)]
+pub enum Primitive {
+    Plane(Plane),
+    Sphere(Sphere),
+    Triangle(Triangle)
+}
+
+impl Primitive {
+    /**
+     * Gets the bounding box of the primitive.
+     *
+     * @return {Option<Aabb>} None for unbounded primitives like planes.
+     */
+    pub fn get_bounds(&self) -> Option<Aabb> {
+        return match self {
+            Primitive::Plane(_) => None,
+            Primitive::Sphere(sphere) => {
+                let r = Vector3D::new(sphere.radius, sphere.radius, sphere.radius);
+
+                Some(Aabb::new(vec_sub(&sphere.origin, &r), vec_sum(&sphere.origin, &r)))
+            },
+            Primitive::Triangle(triangle) => {
+                let bounds = Aabb::new_empty()
+                    .grow(&triangle.a)
+                    .grow(&triangle.b)
+                    .grow(&triangle.c);
+
+                Some(bounds)
+            }
+        };
     }
+}
 
-    fn get_material_index(&self) -> usize {
-        return self.material_index;
+impl Intersect for Primitive {
+    fn intersects(&self, line: &Line) -> Option<Hit> {
+        return match self {
+            Primitive::Plane(plane) => plane.intersects(line),
+            Primitive::Sphere(sphere) => sphere.intersects(line),
+            Primitive::Triangle(triangle) => triangle.intersects(line)
+        };
     }
 }
 
 // ================================================
-// Triangle implementation
+// Mesh implementation
 // ================================================
 
 pub struct Mesh {
@@ -0,0 +1,369 @@
+use rand::Rng;
+
+use crate::{trace, World, get_absorption, get_lights_radiance, get_unweighted_area_lights_radiance, get_visible_lights_radiance};
+use crate::bsdf::{MetallicRoughness, fresnel_schlick, fresnel_dielectric, reflect, refract};
+use crate::geometry::{Line};
+use crate::vector3d::{
+    Vector3D, Onb, sample_cosine_hemisphere,
+    vec_dot, vec_sum, vec_multiplication, vec_hadamard
+};
+
+/**
+ * Light transport algorithm, finds the radiance arriving along a camera line.
+ */
+pub trait Integrator {
+    /**
+     * @param {World} world
+     * @param {Line} line
+     * @param {u32} max_bounces
+     *
+     * @return {(Vector3D, u32)} Linear radiance and the number of surfaces hit.
+     */
+    fn radiance(&self, world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32);
+}
+
+// ================================================
+// Path tracer
+// ================================================
+
+/**
+ * Unbiased global illumination, see `trace`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32) {
+        return trace(world, line, max_bounces);
+    }
+}
+
+// ================================================
+// Whitted
+// ================================================
+
+// Branches adding less than this to the pixel are not followed.
+const WHITTED_MIN_WEIGHT: f32 = 0.01;
+
+/**
+ * Classic recursive ray tracer: direct light from the lights and the emissive objects,
+ * a flat ambient term from the environment, and perfect mirror reflections and refractions.
+ * Fast and noise free with point lights, but rough reflections and indirect light are missing.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Whitted;
+
+impl Whitted {
+    /**
+     * @param {World} world
+     * @param {Line} line
+     * @param {u32} bounces_left Surfaces the branch can still shade, this one included.
+     * @param {f32} weight Largest factor of this branch in the pixel.
+     * @param {bool} shows_lights False if the lights seen by a miss were already counted by the direct light.
+     * @param {u32} bounces Surfaces hit by all the branches.
+     *
+     * @return {Vector3D}
+     */
+    fn get_radiance(&self, world: &World, line: &Line, bounces_left: u32, weight: f32, shows_lights: bool, bounces: &mut u32) -> Vector3D {
+        let mut rng = rand::thread_rng();
+
+        let mut hit = match world.closest_opaque_hit(line, &mut rng) {
+            Some(hit) => hit,
+            None => {
+                let environment = world.environment.get_radiance(&line.direction);
+                if !shows_lights {
+                    return environment;
+                }
+
+                // The camera and the glass see the sun disk.
+                return vec_sum(&environment, &get_visible_lights_radiance(world, &line.origin, &line.direction, None));
+            }
+        };
+
+        let material = world.get_surface_material(&hit);
+
+        let mut result_color = material.emision_color;
+
+        if bounces_left == 0 {
+            return result_color;
+        }
+
+        *bounces += 1;
+
+        let outgoing = vec_multiplication(&line.direction, -1.0);
+
+        hit.shading_normal = world.get_shading_normal(&hit, &material, &outgoing);
+        let normal = hit.get_facing_normal();
+        let cos_theta_o = vec_dot(&normal, &outgoing).max(0.0);
+
+        // --- Smooth dielectric ---
+        if material.transmission > 0.0 {
+            let eta = if hit.front_face { material.ior } else { 1.0 / material.ior };
+            let reflectance = fresnel_dielectric(cos_theta_o, eta);
+
+            let mut dielectric_color = Vector3D::new_as_zero();
+
+            let reflection_weight = weight * material.transmission * reflectance;
+            if reflection_weight >= WHITTED_MIN_WEIGHT {
+                let reflection = Line::new(hit.position, reflect(&normal, &outgoing));
+                let reflected = self.get_radiance(world, &reflection, bounces_left - 1, reflection_weight, true, bounces);
+
+                dielectric_color = vec_sum(&dielectric_color, &vec_multiplication(&reflected, reflectance));
+            }
+
+            let refraction_weight = weight * material.transmission * (1.0 - reflectance);
+            if refraction_weight >= WHITTED_MIN_WEIGHT {
+                if let Some(direction) = refract(&normal, &outgoing, eta) {
+                    let refraction = Line::new(hit.position, direction);
+                    let refracted = self.get_radiance(world, &refraction, bounces_left - 1, refraction_weight, true, bounces);
+
+                    dielectric_color = vec_sum(&dielectric_color, &vec_multiplication(&refracted, 1.0 - reflectance));
+                }
+            }
+
+            result_color = vec_sum(&result_color, &vec_multiplication(&dielectric_color, material.transmission));
+        }
+
+        // --- Opaque surface ---
+        let opaque_weight = 1.0 - material.transmission;
+        if opaque_weight > 0.0 {
+            let bsdf = MetallicRoughness::from_material(&material);
+
+            // No BSDF samples are taken, so the lights with a size get their whole light here.
+            let direct = vec_sum(
+                &get_lights_radiance(world, &bsdf, &hit.position, &normal, &outgoing, false, &mut rng),
+                &get_unweighted_area_lights_radiance(world, &bsdf, &hit.position, &normal, &outgoing, &mut rng)
+            );
+            let ambient = vec_multiplication(&vec_hadamard(&world.environment.get_average_radiance(), &bsdf.base_color), 1.0 - bsdf.metallic);
+
+            let mut opaque_color = vec_sum(&direct, &ambient);
+
+            // The mirror fades out as the surface gets rough.
+            let mirror = vec_multiplication(&fresnel_schlick(&bsdf.get_f0(), cos_theta_o), 1.0 - bsdf.roughness);
+            let mirror_weight = weight * opaque_weight * mirror.x.max(mirror.y).max(mirror.z);
+            if mirror_weight >= WHITTED_MIN_WEIGHT {
+                // The sun disk in the mirror is already in the direct light.
+                let reflection = Line::new(hit.position, reflect(&normal, &outgoing));
+                let reflected = self.get_radiance(world, &reflection, bounces_left - 1, mirror_weight, false, bounces);
+
+                opaque_color = vec_sum(&opaque_color, &vec_hadamard(&reflected, &mirror));
+            }
+
+            result_color = vec_sum(&result_color, &vec_multiplication(&opaque_color, opaque_weight));
+        }
+
+        // Reaching the inside of a transparent material means the line went through it.
+        if !hit.front_face && material.transmission > 0.0 {
+            result_color = vec_hadamard(&result_color, &get_absorption(&material.absorption_color, hit.t));
+        }
+
+        return result_color;
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(&self, world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32) {
+        let mut bounces: u32 = 0;
+        let color = self.get_radiance(world, line, max_bounces, 1.0, true, &mut bounces);
+
+        return (color, bounces);
+    }
+}
+
+// ================================================
+// Ambient occlusion
+// ================================================
+
+/**
+ * Fraction of the hemisphere above the first hit that is open within a distance.
+ * White is fully open, lines that miss everything are white.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    // Occluders further than this don't count.
+    pub distance: f32,
+    pub samples: u32
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32, samples: u32) -> AmbientOcclusion {
+        return AmbientOcclusion {
+            distance: distance,
+            samples: samples
+        };
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, world: &World, line: &Line, _max_bounces: u32) -> (Vector3D, u32) {
+        let mut rng = rand::thread_rng();
+
+        let hit = match world.closest_opaque_hit(line, &mut rng) {
+            Some(hit) => hit,
+            None => { return (Vector3D::new_as_one(), 0); }
+        };
+
+        let samples = self.samples.max(1);
+        let basis = Onb::new_from_w(&hit.get_facing_normal());
+
+        // Cosine weighted, the occluders near the normal count the most.
+        let mut open: u32 = 0;
+        for _ in 0..samples {
+            let direction = basis.to_world(&sample_cosine_hemisphere(rng.gen::<f32>(), rng.gen::<f32>()));
+
+            if !world.any_opaque_hit(&Line::new(hit.position, direction), self.distance, &mut rng) {
+                open += 1;
+            }
+        }
+
+        let visibility = open as f32 / samples as f32;
+
+        return (Vector3D::new(visibility, visibility, visibility), 1);
+    }
+}
+
+// ================================================
+// Debug views
+// ================================================
+
+/**
+ * False color views of the first hit, for checking scenes. Misses are black.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugMode {
+    // Shading normal mapped from [-1, 1] to [0, 1].
+    Normals,
+    // 1 / (1 + distance), white is near.
+    Depth,
+    // Different color for every material.
+    MaterialId,
+    // Weights of the triangle vertices as red, green and blue, black on other primitives.
+    Barycentrics,
+    // Surfaces hit by a path traced sample, from blue (none) to red (max bounces).
+    Bounces
+}
+
+/**
+ * Bright color for the golden ratio hue of an index, neighbours are far apart.
+ *
+ * @param {usize} index
+ *
+ * @return {Vector3D}
+ */
+fn get_index_color(index: usize) -> Vector3D {
+    let hue = (index as f32 * 0.618034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x)
+    };
+
+    return Vector3D::new(0.1 + 0.9 * r, 0.1 + 0.9 * g, 0.1 + 0.9 * b);
+}
+
+/**
+ * Blue, green and red ramp.
+ *
+ * @param {f32} value In [0, 1].
+ *
+ * @return {Vector3D}
+ */
+fn get_heatmap_color(value: f32) -> Vector3D {
+    let value = value.clamp(0.0, 1.0);
+
+    if value < 0.5 {
+        let t = value * 2.0;
+        return Vector3D::new(0.0, t, 1.0 - t);
+    }
+
+    let t = (value - 0.5) * 2.0;
+    return Vector3D::new(t, 1.0 - t, 0.0);
+}
+
+impl Integrator for DebugMode {
+    fn radiance(&self, world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32) {
+        if *self == DebugMode::Bounces {
+            let (_, bounces) = trace(world, line, max_bounces);
+
+            return (get_heatmap_color(bounces as f32 / max_bounces.max(1) as f32), bounces);
+        }
+
+        let hit = match world.closest_opaque_hit(line, &mut rand::thread_rng()) {
+            Some(hit) => hit,
+            None => { return (Vector3D::new_as_zero(), 0); }
+        };
+
+        let color = match self {
+            DebugMode::Normals => {
+                // With the normal and bump maps, to check them.
+                let outgoing = vec_multiplication(&line.direction, -1.0);
+                let normal = world.get_shading_normal(&hit, &world.get_surface_material(&hit), &outgoing);
+
+                vec_sum(&vec_multiplication(&normal, 0.5), &Vector3D::new(0.5, 0.5, 0.5))
+            },
+            DebugMode::Depth => {
+                let value = 1.0 / (1.0 + hit.t);
+                Vector3D::new(value, value, value)
+            },
+            DebugMode::MaterialId => get_index_color(hit.material_index),
+            DebugMode::Barycentrics => hit.barycentric,
+            DebugMode::Bounces => Vector3D::new_as_zero()
+        };
+
+        return (color, 1);
+    }
+}
+
+// ================================================
+// Integrator selection
+// ================================================
+
+/**
+ * The integrator chosen in the render settings.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegratorKind {
+    Path(PathTracer),
+    Whitted(Whitted),
+    AmbientOcclusion(AmbientOcclusion),
+    Debug(DebugMode)
+}
+
+impl IntegratorKind {
+    /**
+     * Finds the integrator by the name used in scene files and the command line.
+     *
+     * @param {&str} name
+     *
+     * @return {Option<IntegratorKind>}
+     */
+    pub fn from_name(name: &str) -> Option<IntegratorKind> {
+        return match name.to_lowercase().as_str() {
+            "path" => Some(IntegratorKind::Path(PathTracer)),
+            "whitted" => Some(IntegratorKind::Whitted(Whitted)),
+            "ao" | "ambient_occlusion" => Some(IntegratorKind::AmbientOcclusion(AmbientOcclusion::new(1.0, 4))),
+            "normals" => Some(IntegratorKind::Debug(DebugMode::Normals)),
+            "depth" => Some(IntegratorKind::Debug(DebugMode::Depth)),
+            "material_id" | "materials" => Some(IntegratorKind::Debug(DebugMode::MaterialId)),
+            "barycentrics" => Some(IntegratorKind::Debug(DebugMode::Barycentrics)),
+            "bounces" | "heatmap" => Some(IntegratorKind::Debug(DebugMode::Bounces)),
+            _ => None
+        };
+    }
+}
+
+impl Integrator for IntegratorKind {
+    fn radiance(&self, world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32) {
+        return match self {
+            IntegratorKind::Path(integrator) => integrator.radiance(world, line, max_bounces),
+            IntegratorKind::Whitted(integrator) => integrator.radiance(world, line, max_bounces),
+            IntegratorKind::AmbientOcclusion(integrator) => integrator.radiance(world, line, max_bounces),
+            IntegratorKind::Debug(integrator) => integrator.radiance(world, line, max_bounces)
+        };
+    }
+}
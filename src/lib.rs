@@ -3,38 +3,50 @@ pub mod color;
 pub mod geometry;
 pub mod camera;
 pub mod loaders;
+pub mod bvh;
+pub mod renderer;
+pub mod output;
+pub mod bsdf;
+pub mod integrator;
+pub mod lights;
+pub mod distribution;
+pub mod environment;
+pub mod sky;
+pub mod texture;
 
 use std::f32;
 
+use rand::Rng;
+
 use vector3d::{
-    Vector3D, vec_get_length,
-    vec_dot, vec_normalize,
-    vec_sum, vec_sub, vec_multiplication, vec_hadamard
+    Vector3D,
+    vec_dot, vec_normalize, vec_get_length,
+    vec_sum, vec_sub, vec_multiplication, vec_division, vec_hadamard
 };
 
 use geometry::{
-    Line, Sphere, Plane, Intersect, Triangle
+    Line, Hit, Intersect, Primitive, Mesh
 };
 
-use color::{Material};
+use color::{Material, get_luminance};
 
-#[derive(Clone, Copy)]
-pub struct PointLight {
-    pub position: Vector3D,
-    pub color: Vector3D,
-    pub range: f32
-}
+use bvh::{Bvh};
 
-impl PointLight {
-    pub fn new (pos: Vector3D, color: Vector3D, range: f32) -> PointLight {
-        return PointLight {
-            position: pos,
-            color: color,
-            range: range
-        };
-    }
-}
+use bsdf::{Bsdf, MaterialBsdf};
+
+pub use renderer::{RenderSettings};
+pub use lights::{PointLight, SceneLight};
 
+use lights::{Light, LightSample, AreaLight};
+
+use environment::{Environment};
+
+use distribution::{Distribution1D};
+
+use texture::{Texture, SceneTexture};
+
+// Step in texture coordinates of the bump map differences.
+const BUMP_DELTA: f32 = 0.0005;
 
 /**
  * World struct
@@ -43,166 +55,729 @@ impl PointLight {
 pub struct World {
     pub materials: Vec<Material>,
 
-    pub planes:  Vec<Plane>,
-    pub shperes: Vec<Sphere>,
+    // Used by the materials through their indices.
+    pub textures: Vec<SceneTexture>,
+
+    // Changed through `add_object` and `get_objects_mut`, so the BVH knows when it's stale.
+    objects: Vec<Primitive>,
+
+    pub lights: Vec<SceneLight>,
 
-    pub objects: Vec<Triangle>,
+    // Light from the directions that miss every object.
+    pub environment: Environment,
 
-    pub lights: Vec<PointLight>
+    // Emissive spheres and triangles, found by `build`.
+    pub area_lights: Vec<AreaLight>,
+
+    // Chooses the area lights by their power.
+    area_light_distribution: Distribution1D,
+    // Area light of every object, if it emits light.
+    area_light_indices: Vec<Option<usize>>,
+
+    bvh: Bvh,
+    // False from the first change to the objects until the next build.
+    is_bvh_valid: bool
 }
 
 impl World {
     pub fn new() -> World{
         return World {
             materials: Vec::new(),
-            planes: Vec::new(),
-            shperes: Vec::new(),
+
+            textures: Vec::new(),
 
             objects: Vec::new(),
 
-            lights: Vec::new()
+            lights: Vec::new(),
+
+            environment: Environment::Constant(Vector3D::new_as_zero()),
+
+            area_lights: Vec::new(),
+
+            area_light_distribution: Distribution1D::new(&[]),
+            area_light_indices: Vec::new(),
+
+            bvh: Bvh::new(),
+            is_bvh_valid: false
+        }
+    }
+
+    /**
+     * Builds the acceleration structures and finds the area lights, call it once the scene is set up.
+     */
+    pub fn build(&mut self) {
+        self.bvh = Bvh::build(&self.objects);
+        self.is_bvh_valid = true;
+
+        self.area_lights.clear();
+        self.area_light_indices = vec![None; self.objects.len()];
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let material_index = match object {
+                Primitive::Plane(plane) => plane.material_index,
+                Primitive::Sphere(sphere) => sphere.material_index,
+                Primitive::Triangle(triangle) => triangle.material_index
+            };
+
+            let emission = match self.materials.get(material_index) {
+                // Textured emission changes over the surface, it's only found by the BSDF samples.
+                Some(material) if material.emission_texture.is_none() => material.emision_color,
+                _ => { continue; }
+            };
+
+            if emission.x <= 0.0 && emission.y <= 0.0 && emission.z <= 0.0 {
+                continue;
+            }
+
+            if let Some(area_light) = AreaLight::new(*object, index, emission) {
+                self.area_light_indices[index] = Some(self.area_lights.len());
+                self.area_lights.push(area_light);
+            }
+        }
+
+        let powers: Vec<f32> = self.area_lights.iter().map(|light| light.get_power()).collect();
+        self.area_light_distribution = Distribution1D::new(&powers);
+    }
+
+    pub fn get_objects(&self) -> &[Primitive] {
+        return &self.objects;
+    }
+
+    /**
+     * Gives access to change the objects, the BVH isn't used again until the next build.
+     *
+     * @return {&mut Vec<Primitive>}
+     */
+    pub fn get_objects_mut(&mut self) -> &mut Vec<Primitive> {
+        self.is_bvh_valid = false;
+
+        return &mut self.objects;
+    }
+
+    /**
+     * Adds an object, call `build` again before rendering.
+     *
+     * @param {Primitive} object
+     */
+    pub fn add_object(&mut self, object: Primitive) {
+        self.objects.push(object);
+        self.is_bvh_valid = false;
+    }
+
+    /**
+     * Material of a hit with its textures applied.
+     *
+     * @param {Hit} hit
+     *
+     * @return {Material}
+     */
+    pub fn get_surface_material(&self, hit: &Hit) -> Material {
+        let mut material = self.materials[hit.material_index];
+
+        let evaluate = |texture: Option<usize>| {
+            return texture
+                .and_then(|index| self.textures.get(index))
+                .map(|texture| texture.evaluate(hit.uv, &hit.position));
+        };
+
+        if let Some(color) = evaluate(material.base_color_texture) {
+            material.base_color = vec_hadamard(&material.base_color, &color);
+        }
+
+        if let Some(color) = evaluate(material.emission_texture) {
+            material.emision_color = vec_hadamard(&material.emision_color, &color);
+        }
+
+        if let Some(color) = evaluate(material.roughness_texture) {
+            material.roughness *= get_luminance(&color);
+        }
+
+        if let Some(color) = evaluate(material.metalic_texture) {
+            material.metalic *= get_luminance(&color);
+        }
+
+        return material;
+    }
+
+    /**
+     * Shading normal of a hit bent by the normal and bump maps of its material,
+     * on the outside of the surface like `hit.shading_normal`.
+     * It's kept as it is when the maps would hide the outgoing direction.
+     *
+     * @param {Hit} hit
+     * @param {Material} material Usually from `get_surface_material`.
+     * @param {Vector3D} outgoing Towards where the line came from.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_shading_normal(&self, hit: &Hit, material: &Material, outgoing: &Vector3D) -> Vector3D {
+        let normal_texture = material.normal_texture.and_then(|index| self.textures.get(index));
+        let bump_texture = material.bump_texture.and_then(|index| self.textures.get(index));
+
+        if normal_texture.is_none() && bump_texture.is_none() {
+            return hit.shading_normal;
+        }
+
+        let mut frame = hit.get_tangent_frame();
+
+        if let Some(texture) = normal_texture {
+            // Colors in [0, 1] store the components in [-1, 1].
+            let color = texture.evaluate(hit.uv, &hit.position);
+            let local = Vector3D::new(
+                (2.0 * color.x - 1.0) * material.normal_scale,
+                (2.0 * color.y - 1.0) * material.normal_scale,
+                2.0 * color.z - 1.0
+            );
+
+            let mapped = frame.to_world(&local);
+            if vec_get_length(&mapped) > 0.0 {
+                frame.w = vec_normalize(&mapped);
+            }
+        }
+
+        let u_length = vec_get_length(&hit.dpdu);
+        let v_length = vec_get_length(&hit.dpdv);
+
+        if let (Some(texture), true) = (bump_texture, u_length > 0.0 && v_length > 0.0) {
+            let get_height = |du: f32, dv: f32| {
+                let position = vec_sum(&hit.position, &vec_sum(&vec_multiplication(&hit.dpdu, du), &vec_multiplication(&hit.dpdv, dv)));
+
+                return get_luminance(&texture.evaluate((hit.uv.0 + du, hit.uv.1 + dv), &position)) * material.bump_scale;
+            };
+
+            let height = get_height(0.0, 0.0);
+
+            // Slopes in height per world unit along the tangent and the bitangent.
+            let u_slope = (get_height(BUMP_DELTA, 0.0) - height) / (BUMP_DELTA * u_length);
+            let v_slope = (get_height(0.0, BUMP_DELTA) - height) / (BUMP_DELTA * v_length);
+
+            let bumped = vec_sub(
+                &frame.w,
+                &vec_sum(&vec_multiplication(&frame.u, u_slope), &vec_multiplication(&frame.v, v_slope))
+            );
+
+            frame.w = vec_normalize(&bumped);
+        }
+
+        // Facing the same side as the hit, the outgoing direction must stay above it.
+        let side = if hit.front_face { 1.0 } else { -1.0 };
+        if vec_dot(&frame.w, outgoing) * side <= 0.0 {
+            return hit.shading_normal;
+        }
+
+        return frame.w;
+    }
+
+    /**
+     * Chooses an area light, the brighter ones more often.
+     *
+     * @param {f32} u Uniform number in [0, 1).
+     *
+     * @return {Option<(&AreaLight, f32)>} The light and the chance of choosing it.
+     */
+    pub fn sample_area_light(&self, u: f32) -> Option<(&AreaLight, f32)> {
+        let (index, probability) = self.area_light_distribution.sample(u)?;
+
+        return Some((&self.area_lights[index], probability));
+    }
+
+    /**
+     * Finds the area light of an object.
+     *
+     * @param {usize} primitive_index
+     *
+     * @return {Option<(&AreaLight, f32)>} The light and the chance of `sample_area_light` choosing it.
+     */
+    pub fn get_area_light(&self, primitive_index: usize) -> Option<(&AreaLight, f32)> {
+        let index = (*self.area_light_indices.get(primitive_index)?)?;
+
+        return Some((&self.area_lights[index], self.area_light_distribution.get_probability(index)));
+    }
+
+    /**
+     * Finds the closest object hit by the line.
+     * Falls back to a linear scan if the objects changed after the last build.
+     *
+     * @param {Line} line
+     *
+     * @return {Option<Hit>} The closest hit, with the object index as primitive index.
+     */
+    pub fn closest_hit(&self, line: &Line) -> Option<Hit> {
+        if self.is_bvh_valid {
+            return self.bvh.closest_hit(&self.objects, line);
+        }
+
+        let mut closest: Option<Hit> = None;
+        let mut hit_distance: f32 = f32::MAX;
+
+        for (index, obj) in self.objects.iter().enumerate() {
+            if let Some(mut hit) = obj.intersects(line) {
+                if hit.t < hit_distance {
+                    hit_distance = hit.t;
+                    hit.primitive_index = index;
+                    closest = Some(hit);
+                }
+            }
+        }
+
+        return closest;
+    }
+
+    /**
+     * Checks if the line hits any object before the given distance.
+     *
+     * @param {Line} line
+     * @param {f32} max_t
+     *
+     * @return {bool}
+     */
+    pub fn any_hit(&self, line: &Line, max_t: f32) -> bool {
+        if self.is_bvh_valid {
+            return self.bvh.any_hit(&self.objects, line, max_t);
+        }
+
+        for obj in self.objects.iter() {
+            if let Some(hit) = obj.intersects(line) {
+                if hit.t < max_t {
+                    return true;
+                }
+            }
+        }
+
+        return false;
+    }
+
+    /**
+     * Chooses at random if a line goes straight through the surface of a hit,
+     * as often as the opacity of its material lets it.
+     *
+     * @param {Hit} hit
+     * @param {R} rng
+     *
+     * @return {bool}
+     */
+    fn is_cut_out<R: Rng + ?Sized>(&self, hit: &Hit, rng: &mut R) -> bool {
+        let opacity = self.materials[hit.material_index].opacity;
+
+        return opacity < 1.0 && rng.gen::<f32>() >= opacity;
+    }
+
+    /**
+     * Like `closest_hit`, but the line goes through the surfaces that are cut out
+     * by the opacity of their materials.
+     *
+     * @param {Line} line
+     * @param {R} rng
+     *
+     * @return {Option<Hit>} The hit, its distance counted from the line origin.
+     */
+    pub fn closest_opaque_hit<R: Rng + ?Sized>(&self, line: &Line, rng: &mut R) -> Option<Hit> {
+        let mut current_line = Line::from(line);
+        let mut distance = 0.0;
+
+        loop {
+            let mut hit = self.closest_hit(&current_line)?;
+
+            if !self.is_cut_out(&hit, rng) {
+                hit.t += distance;
+                return Some(hit);
+            }
+
+            distance += hit.t;
+            current_line = Line::new(hit.position, line.direction);
         }
     }
+
+    /**
+     * Like `any_hit`, but the line goes through the surfaces that are cut out
+     * by the opacity of their materials.
+     *
+     * @param {Line} line
+     * @param {f32} max_t
+     * @param {R} rng
+     *
+     * @return {bool}
+     */
+    pub fn any_opaque_hit<R: Rng + ?Sized>(&self, line: &Line, max_t: f32, rng: &mut R) -> bool {
+        if self.materials.iter().all(|material| material.opacity >= 1.0) {
+            return self.any_hit(line, max_t);
+        }
+
+        let mut current_line = Line::from(line);
+        let mut remaining = max_t;
+
+        while let Some(hit) = self.closest_hit(&current_line) {
+            if hit.t >= remaining {
+                return false;
+            }
+
+            if !self.is_cut_out(&hit, rng) {
+                return true;
+            }
+
+            remaining -= hit.t;
+            current_line = Line::new(hit.position, line.direction);
+        }
+
+        return false;
+    }
+
+    /**
+     * Adds every triangle of the mesh to the world objects.
+     *
+     * @param {Mesh} mesh
+     */
+    pub fn add_mesh(&mut self, mesh: Mesh) {
+        for triangle in mesh.triangles {
+            self.add_object(Primitive::Triangle(triangle));
+        }
+    }
+}
+
+/**
+ * Beer-Lambert absorption along a distance inside a material.
+ *
+ * @param {Vector3D} absorption_color Color after one unit of distance.
+ * @param {f32} distance
+ *
+ * @return {Vector3D}
+ */
+fn get_absorption(absorption_color: &Vector3D, distance: f32) -> Vector3D {
+    return Vector3D::new(
+        absorption_color.x.max(0.0).powf(distance),
+        absorption_color.y.max(0.0).powf(distance),
+        absorption_color.z.max(0.0).powf(distance)
+    );
+}
+
+/**
+ * Light of a sample of an area light or the environment reflected by a surface point
+ * towards the outgoing direction, weighted against the BSDF samples that can hit the same light.
+ *
+ * @param {World} world
+ * @param {B} bsdf
+ * @param {LightSample} sample
+ * @param {f32} probability Chance of choosing the light.
+ * @param {Vector3D} position
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ *
+ * @return {Vector3D}
+ */
+fn get_weighted_light_radiance<B: Bsdf>(world: &World, bsdf: &B, sample: &LightSample, probability: f32, position: &Vector3D, normal: &Vector3D, outgoing: &Vector3D) -> Vector3D {
+    let light_pdf = probability * sample.pdf;
+    let cos_theta = vec_dot(normal, &sample.direction);
+    if light_pdf <= 0.0 || cos_theta <= 0.0 || !sample.is_visible(world, position) {
+        return Vector3D::new_as_zero();
+    }
+
+    let weight = power_heuristic(light_pdf, bsdf.pdf(normal, outgoing, &sample.direction));
+
+    let radiance = vec_multiplication(&sample.radiance, cos_theta * weight / light_pdf);
+
+    return vec_hadamard(&bsdf.evaluate(normal, outgoing, &sample.direction), &radiance);
+}
+
+/**
+ * Light of one area light reflected by a surface point towards the outgoing direction.
+ *
+ * @param {World} world
+ * @param {B} bsdf
+ * @param {Vector3D} position
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ * @param {R} rng
+ *
+ * @return {Vector3D}
+ */
+fn get_area_lights_radiance<B: Bsdf, R: Rng + ?Sized>(world: &World, bsdf: &B, position: &Vector3D, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Vector3D {
+    let (light, probability) = match world.sample_area_light(rng.gen::<f32>()) {
+        Some(choice) => choice,
+        None => { return Vector3D::new_as_zero(); }
+    };
+
+    return match light.sample(position, rng) {
+        Some(sample) => get_weighted_light_radiance(world, bsdf, &sample, probability, position, normal, outgoing),
+        None => Vector3D::new_as_zero()
+    };
+}
+
+/**
+ * Light of one area light reflected by a surface point towards the outgoing direction,
+ * for integrators that never hit the lights with BSDF samples so there is nothing to weight against.
+ *
+ * @param {World} world
+ * @param {B} bsdf
+ * @param {Vector3D} position
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ * @param {R} rng
+ *
+ * @return {Vector3D}
+ */
+fn get_unweighted_area_lights_radiance<B: Bsdf, R: Rng + ?Sized>(world: &World, bsdf: &B, position: &Vector3D, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Vector3D {
+    let (light, probability) = match world.sample_area_light(rng.gen::<f32>()) {
+        Some(choice) => choice,
+        None => { return Vector3D::new_as_zero(); }
+    };
+
+    let sample = match light.sample(position, rng) {
+        Some(sample) => sample,
+        None => { return Vector3D::new_as_zero(); }
+    };
+
+    let light_pdf = probability * sample.pdf;
+    let cos_theta = vec_dot(normal, &sample.direction);
+    if light_pdf <= 0.0 || cos_theta <= 0.0 || !sample.is_visible(world, position) {
+        return Vector3D::new_as_zero();
+    }
+
+    let radiance = vec_multiplication(&sample.radiance, cos_theta / light_pdf);
+
+    return vec_hadamard(&bsdf.evaluate(normal, outgoing, &sample.direction), &radiance);
+}
+
+/**
+ * Light of the environment reflected by a surface point towards the outgoing direction.
+ *
+ * @param {World} world
+ * @param {B} bsdf
+ * @param {Vector3D} position
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ * @param {R} rng
+ *
+ * @return {Vector3D}
+ */
+fn get_environment_radiance<B: Bsdf, R: Rng + ?Sized>(world: &World, bsdf: &B, position: &Vector3D, normal: &Vector3D, outgoing: &Vector3D, rng: &mut R) -> Vector3D {
+    return match world.environment.sample(position, rng) {
+        Some(sample) => get_weighted_light_radiance(world, bsdf, &sample, 1.0, position, normal, outgoing),
+        None => Vector3D::new_as_zero()
+    };
+}
+
+// Bounces before a path can be ended by russian roulette.
+const ROULETTE_MIN_BOUNCES: u32 = 3;
+
+/**
+ * Power heuristic (beta = 2) weight of a sample taken with one strategy,
+ * when another strategy could have taken it too.
+ *
+ * @param {f32} pdf Density of the strategy that took the sample.
+ * @param {f32} other_pdf Density of the other strategy for the same sample.
+ *
+ * @return {f32}
+ */
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+
+    return a / (a + b);
+}
+
+/**
+ * Light of the world lights reflected by a surface point towards the outgoing direction.
+ *
+ * @param {World} world
+ * @param {B} bsdf
+ * @param {Vector3D} position
+ * @param {Vector3D} normal On the side of the outgoing direction.
+ * @param {Vector3D} outgoing
+ * @param {bool} is_weighted False for integrators that never hit the lights with BSDF samples.
+ * @param {R} rng
+ *
+ * @return {Vector3D}
+ */
+fn get_lights_radiance<B: Bsdf, R: Rng + ?Sized>(world: &World, bsdf: &B, position: &Vector3D, normal: &Vector3D, outgoing: &Vector3D, is_weighted: bool, rng: &mut R) -> Vector3D {
+    let mut result_color = Vector3D::new_as_zero();
+
+    for light in world.lights.iter() {
+        let sample = match light.sample(position, rng) {
+            Some(sample) => sample,
+            None => { continue; }
+        };
+
+        // Surfaces facing away get nothing.
+        let cos_theta = vec_dot(normal, &sample.direction);
+        if cos_theta <= 0.0 {
+            continue;
+        }
+
+        // Shadow calculation
+        if !sample.is_visible(world, position) {
+            continue;
+        }
+
+        // Lights with a size can be hit by the BSDF samples too.
+        let weight = if sample.is_delta || !is_weighted {
+            1.0
+        } else {
+            power_heuristic(sample.pdf, bsdf.pdf(normal, outgoing, &sample.direction))
+        };
+
+        let radiance = vec_multiplication(&sample.radiance, cos_theta * weight / sample.pdf);
+
+        let reflected = vec_hadamard(&bsdf.evaluate(normal, outgoing, &sample.direction), &radiance);
+        result_color = vec_sum(&result_color, &reflected);
+    }
+
+    return result_color;
+}
+
+/**
+ * Light of the world lights seen along a line that misses every object,
+ * weighted against the light samples of the point the line comes from.
+ *
+ * @param {World} world
+ * @param {Vector3D} position Where the line starts.
+ * @param {Vector3D} direction
+ * @param {Option<f32>} bsdf_pdf Density of the BSDF sample that chose the line, None if the lights weren't sampled.
+ *
+ * @return {Vector3D}
+ */
+fn get_visible_lights_radiance(world: &World, position: &Vector3D, direction: &Vector3D, bsdf_pdf: Option<f32>) -> Vector3D {
+    let mut result_color = Vector3D::new_as_zero();
+
+    for light in world.lights.iter() {
+        let radiance = light.get_radiance(direction);
+
+        let weight = match bsdf_pdf {
+            Some(pdf) => power_heuristic(pdf, light.pdf(position, direction)),
+            None => 1.0
+        };
+
+        result_color = vec_sum(&result_color, &vec_multiplication(&radiance, weight));
+    }
+
+    return result_color;
 }
 
 /**
  * Trace party
+ *
+ * Unbiased path tracer. At every hit the lights, one area light and the environment are sampled
+ * explicitly with shadow rays, then the next direction is sampled from the material BSDF.
+ * Area lights and the environment are found by both strategies, they are combined with
+ * multiple importance sampling.
+ * Paths are ended by russian roulette after a few bounces.
+ *
+ * @return {(Vector3D, u32)} Linear radiance and the number of surfaces hit.
  */
 pub fn trace(world: &World, line: &Line, max_bounces: u32) -> (Vector3D, u32) {
-    let mut hit_distance: f32 = f32::MAX;
-    let mut final_material: usize = 0;
+    let mut rng = rand::thread_rng();
 
     // Color calculation
     let mut result_color: Vector3D = Vector3D::new_as_zero();
-    let mut attenuation: Vector3D = Vector3D::new_as_one();
+    let mut throughput: Vector3D = Vector3D::new_as_one();
 
     // Bounces
     let mut current_line: Line = Line::from(line);
-    let mut next_origin: Vector3D = Vector3D::new_as_zero();
-    let mut next_normal: Vector3D = Vector3D::new_as_zero();
+
+    // Density of the BSDF sample that started the current line,
+    // None for the camera line and delta bounces that lights can't sample.
+    let mut previous_pdf: Option<f32> = None;
+    let mut previous_position: Vector3D = line.origin;
 
     let mut bounces_performed: u32 = 0;
-    let mut i: u32 = 0;
-    while i < max_bounces {
-        i += 1;
-
-        for obj in world.objects.iter() {
-            if let Some(t) = obj.intersects(&current_line) {
-                if t < hit_distance {
-                    hit_distance = t;
-                    final_material = obj.get_material_index();
-
-                    next_origin = current_line.get_point(t);
-                    next_normal = obj.get_normal(&next_origin);
-                }
+    loop {
+        let mut hit = match world.closest_opaque_hit(&current_line, &mut rng) {
+            Some(hit) => hit,
+            None => {
+                // Environment contribution, weighted against the environment samples of the previous hit.
+                let weight = match previous_pdf {
+                    Some(pdf) => power_heuristic(pdf, world.environment.pdf(&previous_position, &current_line.direction)),
+                    None => 1.0
+                };
+
+                let radiance = world.environment.get_radiance(&current_line.direction);
+
+                result_color = vec_sum(&result_color, &vec_multiplication(&vec_hadamard(&throughput, &radiance), weight));
+
+                // Lights with a size in the sky, like the sun disk.
+                let radiance = get_visible_lights_radiance(world, &previous_position, &current_line.direction, previous_pdf);
+                result_color = vec_sum(&result_color, &vec_hadamard(&throughput, &radiance));
+                break;
             }
-        }
+        };
 
-        if final_material != 0 {
-            let material = world.materials.get(final_material).unwrap();
+        let material = world.get_surface_material(&hit);
 
-            // Reflection
-            let reflection_coeficient: f32 = 2.0 * vec_dot(&line.direction, &next_normal);
-            let reflection_correction = vec_multiplication(&next_normal, reflection_coeficient);
-            let pure_reflection = vec_sub(&line.direction, &reflection_correction);
+        // Reaching the inside of a transparent material means the path went through it.
+        if !hit.front_face && material.transmission > 0.0 {
+            throughput = vec_hadamard(&throughput, &get_absorption(&material.absorption_color, hit.t));
+        }
 
-            let random_reflection = Vector3D::new_random(material.roughness);
+        // Emission contribution, weighted against the area light samples of the previous hit.
+        let emission_weight = match (previous_pdf, world.get_area_light(hit.primitive_index)) {
+            (Some(pdf), Some((light, probability))) => {
+                power_heuristic(pdf, probability * light.pdf(&previous_position, &current_line.direction))
+            },
+            _ => 1.0
+        };
 
-            let reflection = vec_normalize(
-                &vec_sum(
-                    &pure_reflection,
-                    &random_reflection
-                )
-            );
+        result_color = vec_sum(&result_color, &vec_multiplication(&vec_hadamard(&throughput, &material.emision_color), emission_weight));
 
-            // --------------------------------------
-            // Light stuff
-            // --------------------------------------
+        if bounces_performed >= max_bounces {
+            break;
+        }
 
-            let mut light_contribs: Vec<(f32, Vector3D)> = Vec::with_capacity(world.lights.len());
+        bounces_performed += 1;
 
-            for light in world.lights.iter() {
-                let point_to_light = vec_normalize(&vec_sub(&light.position, &next_origin));
+        let bsdf = MaterialBsdf::from_material(&material, hit.front_face, &mut rng);
 
-                let mut coeficient: f32= 1.0;
-                coeficient += vec_dot(
-                    &next_normal,
-                    &point_to_light
-                );
+        let position = hit.position;
+        let outgoing = vec_multiplication(&current_line.direction, -1.0);
 
-                if coeficient < 0.1 {
-                    coeficient = 0.1;
-                }
+        hit.shading_normal = world.get_shading_normal(&hit, &material, &outgoing);
+        let normal = hit.get_facing_normal();
 
-                let surface_to_light : Line = Line::new(next_origin, point_to_light);
-                let point_to_light_distance = vec_get_length(&vec_sub(&light.position, &next_origin));
-                // Shadow calculation coeficient
-                for obj in world.objects.iter() {
-                    if let Some(t) = obj.intersects(&surface_to_light) {
-                        if t < point_to_light_distance {
-                            coeficient *= 0.25;
-                            break;
-                        }
-                    }
-                }
+        // --------------------------------------
+        // Light stuff
+        // --------------------------------------
 
-                let mut percent: f32 = point_to_light_distance / light.range;
+        // Delta BSDFs never reflect a light sample towards the path.
+        if !bsdf.is_delta() {
+            // Delta lights can't be hit by the BSDF samples, no weighting needed.
+            let reflected = get_lights_radiance(world, &bsdf, &position, &normal, &outgoing, true, &mut rng);
+            result_color = vec_sum(&result_color, &vec_hadamard(&throughput, &reflected));
 
-                if percent > 1.0 { percent = 1.0; }
+            let reflected = get_area_lights_radiance(world, &bsdf, &position, &normal, &outgoing, &mut rng);
+            result_color = vec_sum(&result_color, &vec_hadamard(&throughput, &reflected));
 
-                let light_power = 1.0 - percent;
-
-                light_contribs.push( (coeficient, vec_multiplication(&light.color, light_power)) );
-            }
-
-            // --------------------------------------
-            // Color modification
-            // --------------------------------------
+            let reflected = get_environment_radiance(world, &bsdf, &position, &normal, &outgoing, &mut rng);
+            result_color = vec_sum(&result_color, &vec_hadamard(&throughput, &reflected));
+        }
 
-            // Emission contribution
-            result_color = vec_sum(&result_color, &vec_hadamard(&attenuation, &material.emision_color));
+        // --------------------------------------
+        // Next bounce
+        // --------------------------------------
 
-            // Light absortion
-            attenuation = vec_hadamard(
-                &attenuation,
-                &vec_multiplication(
-                    // More metalic, less attenuation by base color.
-                    &material.base_color, 1.0 - (0.5 + (material.metalic * 0.5))
-                )
-            );
+        let sample = match bsdf.sample(&normal, &outgoing, &mut rng) {
+            Some(sample) => sample,
+            None => { break; }
+        };
 
-            // Lights contribution
-            let mut coeficient_sum: f32 = 1.0;
-            for contrib in light_contribs {
-                let diffuse_light = vec_hadamard(&contrib.1, &attenuation);
-                result_color = vec_sum(
-                    &result_color,
-                    &vec_multiplication(&diffuse_light, contrib.0)
-                );
+        throughput = vec_hadamard(&throughput, &sample.weight);
+        previous_pdf = if sample.is_delta { None } else { Some(sample.pdf) };
 
-                coeficient_sum *= contrib.0;
+        // Russian roulette, dim paths are ended and the survivors carry their energy.
+        if bounces_performed >= ROULETTE_MIN_BOUNCES {
+            let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+            if rng.gen::<f32>() >= survival {
+                break;
             }
-            attenuation = vec_multiplication(&attenuation, coeficient_sum);
 
-            final_material = 0;
-
-            // Create the next line
-            current_line = Line::new(next_origin, reflection);
-            bounces_performed += 1;
-        } else {
-            // Avoid keep raycasting if it doesn't hit anything.
-            i = max_bounces;
-
-            // FIXME: Assume we hit the sky
-            let sky_material = world.materials.get(0).unwrap();
-            result_color = vec_sum(&result_color, &vec_hadamard(&attenuation, &sky_material.emision_color));
+            throughput = vec_division(&throughput, survival);
         }
-    }
 
-    // Clamp the colour length
-    if vec_get_length(&result_color) > 1.73 {
-        result_color = vec_multiplication(&vec_normalize(&result_color), 1.73);
+        // Create the next line
+        previous_position = position;
+        current_line = Line::new(position, sample.direction);
     }
 
-    return (result_color, bounces_performed); // world.materials.get(final_material).unwrap().base_color;
-}
\ No newline at end of file
+    return (result_color, bounces_performed);
+}
@@ -0,0 +1,702 @@
+use std::f32;
+
+use rand::Rng;
+
+use crate::{World};
+use crate::color::{get_luminance};
+use crate::geometry::{Line, Intersect, Primitive};
+use crate::vector3d::{
+    Vector3D, Onb, vec_get_length,
+    vec_dot, vec_cross, vec_normalize,
+    vec_sum, vec_sub, vec_multiplication
+};
+
+// Gap left before the light surface so the shadow ray doesn't hit the light itself.
+const SHADOW_EPSILON: f32 = 1e-3;
+
+/**
+ * Direction towards a light chosen from a surface point.
+ */
+#[derive(Clone, Copy)]
+pub struct LightSample {
+    // From the surface point to the light.
+    pub direction: Vector3D,
+
+    // Distance to the light, infinite for directional lights.
+    pub distance: f32,
+
+    // Incident radiance, for delta lights the irradiance on a surface facing the light.
+    pub radiance: Vector3D,
+
+    // Solid angle density of the direction, 1 for delta lights.
+    pub pdf: f32,
+
+    pub is_delta: bool
+}
+
+impl LightSample {
+    /**
+     * Traces the shadow ray from the surface point to the light.
+     *
+     * @param {World} world
+     * @param {Vector3D} position
+     *
+     * @return {bool} Nothing blocks the light.
+     */
+    pub fn is_visible(&self, world: &World, position: &Vector3D) -> bool {
+        let surface_to_light: Line = Line::new(*position, self.direction);
+
+        return !world.any_opaque_hit(&surface_to_light, self.distance, &mut rand::thread_rng());
+    }
+}
+
+/**
+ * Light source that can be sampled from a surface point for direct lighting.
+ */
+pub trait Light {
+    /**
+     * Chooses a direction towards the light, None if the light doesn't reach the point.
+     */
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, rng: &mut R) -> Option<LightSample>;
+
+    /**
+     * Solid angle density of `sample` choosing the direction, 0 for delta lights.
+     */
+    fn pdf(&self, position: &Vector3D, direction: &Vector3D) -> f32;
+
+    /**
+     * Comes from a single point or direction, BSDF samples never hit it.
+     */
+    fn is_delta(&self) -> bool {
+        return true;
+    }
+}
+
+/**
+ * Inverse square falloff with the optional smooth window, (1 - (d / range)^4)^2.
+ *
+ * @param {f32} intensity Watts per steradian.
+ * @param {f32} distance
+ * @param {Option<f32>} range
+ *
+ * @return {f32} Irradiance on a surface facing the light.
+ */
+fn get_distance_attenuation(intensity: f32, distance: f32, range: Option<f32>) -> f32 {
+    let distance2 = (distance * distance).max(1e-8);
+    let mut irradiance = intensity / distance2;
+
+    if let Some(range) = range {
+        let ratio = distance / range;
+        let window = (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0);
+
+        irradiance *= window * window;
+    }
+
+    return irradiance;
+}
+
+/**
+ * Density of the directions sampled uniformly inside a cone.
+ *
+ * @param {f32} cos_theta_max Cosine of the half angle of the cone.
+ *
+ * @return {f32}
+ */
+fn get_cone_pdf(cos_theta_max: f32) -> f32 {
+    return 1.0 / (2.0 * f32::consts::PI * (1.0 - cos_theta_max).max(1e-7));
+}
+
+/**
+ * Direction chosen uniformly inside a cone.
+ *
+ * @param {Vector3D} axis Normalized.
+ * @param {f32} cos_theta_max Cosine of the half angle of the cone.
+ * @param {f32} u1
+ * @param {f32} u2
+ *
+ * @return {Vector3D}
+ */
+fn sample_cone(axis: &Vector3D, cos_theta_max: f32, u1: f32, u2: f32) -> Vector3D {
+    let cos_theta = 1.0 - u1 + u1 * cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * f32::consts::PI * u2;
+
+    let local = Vector3D::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+    return Onb::new_from_w(axis).to_world(&local);
+}
+
+// ================================================
+// Point light
+// ================================================
+
+/**
+ * Light emitted from a single point, the same in every direction.
+ * The irradiance falls with the inverse square of the distance.
+ */
+#[derive(Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3D,
+    pub color: Vector3D,
+
+    // Radiant intensity in watts per steradian, scales the color.
+    pub intensity: f32,
+
+    // Distance where the light has faded out, None lights the whole scene.
+    // Smaller ranges skip the shadow rays of far away points.
+    pub range: Option<f32>
+}
+
+impl PointLight {
+    pub fn new (pos: Vector3D, color: Vector3D, intensity: f32) -> PointLight {
+        return PointLight {
+            position: pos,
+            color: color,
+            intensity: intensity,
+            range: None
+        };
+    }
+
+    /**
+     * Light with the given radiant power spread evenly over the sphere.
+     *
+     * @param {Vector3D} pos
+     * @param {Vector3D} color
+     * @param {f32} power Watts.
+     *
+     * @return {PointLight}
+     */
+    pub fn new_with_power(pos: Vector3D, color: Vector3D, power: f32) -> PointLight {
+        return PointLight::new(pos, color, power / (4.0 * f32::consts::PI));
+    }
+
+    /**
+     * Sets the distance where the light smoothly fades out.
+     *
+     * @param {f32} range
+     *
+     * @return {PointLight}
+     */
+    pub fn with_range(mut self, range: f32) -> PointLight {
+        self.range = Some(range);
+
+        return self;
+    }
+
+    /**
+     * Radiant power in watts.
+     *
+     * @return {f32}
+     */
+    pub fn get_power(&self) -> f32 {
+        return self.intensity * 4.0 * f32::consts::PI;
+    }
+
+    /**
+     * Irradiance on a surface facing the light, without the color.
+     *
+     * @param {f32} distance
+     *
+     * @return {f32}
+     */
+    pub fn get_irradiance(&self, distance: f32) -> f32 {
+        return get_distance_attenuation(self.intensity, distance, self.range);
+    }
+}
+
+impl Light for PointLight {
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, _rng: &mut R) -> Option<LightSample> {
+        let point_to_light = vec_sub(&self.position, position);
+        let distance = vec_get_length(&point_to_light);
+
+        // The range skips the shadow ray.
+        let irradiance = self.get_irradiance(distance);
+        if irradiance <= 0.0 {
+            return None;
+        }
+
+        return Some(LightSample {
+            direction: vec_normalize(&point_to_light),
+            distance: distance,
+            radiance: vec_multiplication(&self.color, irradiance),
+            pdf: 1.0,
+            is_delta: true
+        });
+    }
+
+    fn pdf(&self, _position: &Vector3D, _direction: &Vector3D) -> f32 {
+        return 0.0;
+    }
+}
+
+// ================================================
+// Directional light
+// ================================================
+
+/**
+ * Light from a far away source like the sun, every point receives it from the same direction.
+ * With an angular size it's a disk in the sky that casts soft shadows, and the lines
+ * that miss every object see it.
+ */
+#[derive(Clone, Copy)]
+pub struct DirectionalLight {
+    // Direction the light travels in.
+    pub direction: Vector3D,
+    pub color: Vector3D,
+
+    // Watts per square meter on a surface facing the light, scales the color.
+    pub irradiance: f32,
+
+    // Half of the angle the source covers, in radians. 0 is a single direction.
+    pub angular_radius: f32
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3D, color: Vector3D, irradiance: f32) -> DirectionalLight {
+        return DirectionalLight {
+            direction: vec_normalize(&direction),
+            color: color,
+            irradiance: irradiance,
+            angular_radius: 0.0
+        };
+    }
+
+    /**
+     * Sets the angle the source covers in the sky, the sun is about 0.53 degrees.
+     *
+     * @param {f32} angular_diameter Radians.
+     *
+     * @return {DirectionalLight}
+     */
+    pub fn with_angular_diameter(mut self, angular_diameter: f32) -> DirectionalLight {
+        self.angular_radius = angular_diameter / 2.0;
+
+        return self;
+    }
+
+    /**
+     * Radiance of the disk, the irradiance spread evenly over its solid angle.
+     * Zero outside the disk and for a single direction.
+     *
+     * @param {Vector3D} direction Towards the sky.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        let pdf = self.pdf(&Vector3D::new_as_zero(), direction);
+        if pdf <= 0.0 || self.irradiance <= 0.0 {
+            return Vector3D::new_as_zero();
+        }
+
+        return vec_multiplication(&self.color, self.irradiance * pdf);
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample<R: Rng + ?Sized>(&self, _position: &Vector3D, rng: &mut R) -> Option<LightSample> {
+        if self.irradiance <= 0.0 {
+            return None;
+        }
+
+        let to_light = vec_multiplication(&self.direction, -1.0);
+        let irradiance = vec_multiplication(&self.color, self.irradiance);
+
+        if self.angular_radius <= 0.0 {
+            return Some(LightSample {
+                direction: to_light,
+                distance: f32::INFINITY,
+                radiance: irradiance,
+                pdf: 1.0,
+                is_delta: true
+            });
+        }
+
+        // The irradiance spread evenly over the disk.
+        let cos_theta_max = self.angular_radius.cos();
+        let pdf = get_cone_pdf(cos_theta_max);
+
+        return Some(LightSample {
+            direction: sample_cone(&to_light, cos_theta_max, rng.gen::<f32>(), rng.gen::<f32>()),
+            distance: f32::INFINITY,
+            radiance: vec_multiplication(&irradiance, pdf),
+            pdf: pdf,
+            is_delta: false
+        });
+    }
+
+    fn pdf(&self, _position: &Vector3D, direction: &Vector3D) -> f32 {
+        if self.angular_radius <= 0.0 {
+            return 0.0;
+        }
+
+        let cos_theta_max = self.angular_radius.cos();
+        if -vec_dot(&self.direction, direction) < cos_theta_max {
+            return 0.0;
+        }
+
+        return get_cone_pdf(cos_theta_max);
+    }
+
+    fn is_delta(&self) -> bool {
+        return self.angular_radius <= 0.0;
+    }
+}
+
+// ================================================
+// Spot light
+// ================================================
+
+/**
+ * Point light limited to a cone. It's at full intensity inside the inner angle
+ * and fades out towards the outer angle.
+ */
+#[derive(Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vector3D,
+    // Axis of the cone, from the light outwards.
+    pub direction: Vector3D,
+    pub color: Vector3D,
+
+    // Radiant intensity along the axis in watts per steradian, scales the color.
+    pub intensity: f32,
+
+    // Half angles of the cone, in radians.
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+
+    // Shape of the fade between the cones, 1 is linear in the cosine.
+    pub falloff: f32,
+
+    // Distance where the light has faded out, None lights the whole scene.
+    pub range: Option<f32>
+}
+
+impl SpotLight {
+    pub fn new(position: Vector3D, direction: Vector3D, color: Vector3D, intensity: f32, inner_angle: f32, outer_angle: f32) -> SpotLight {
+        return SpotLight {
+            position: position,
+            direction: vec_normalize(&direction),
+            color: color,
+
+            intensity: intensity,
+
+            inner_angle: inner_angle.min(outer_angle),
+            outer_angle: outer_angle,
+
+            falloff: 1.0,
+
+            range: None
+        };
+    }
+
+    /**
+     * Sets the exponent of the fade between the cones.
+     *
+     * @param {f32} falloff
+     *
+     * @return {SpotLight}
+     */
+    pub fn with_falloff(mut self, falloff: f32) -> SpotLight {
+        self.falloff = falloff;
+
+        return self;
+    }
+
+    /**
+     * Sets the distance where the light smoothly fades out.
+     *
+     * @param {f32} range
+     *
+     * @return {SpotLight}
+     */
+    pub fn with_range(mut self, range: f32) -> SpotLight {
+        self.range = Some(range);
+
+        return self;
+    }
+
+    /**
+     * Fraction of the intensity sent in a direction.
+     *
+     * @param {Vector3D} direction From the light outwards.
+     *
+     * @return {f32} 1 inside the inner cone, 0 outside the outer one.
+     */
+    pub fn get_cone_attenuation(&self, direction: &Vector3D) -> f32 {
+        let cos_theta = vec_dot(&self.direction, direction);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+
+        if cos_theta >= cos_inner {
+            return 1.0;
+        }
+
+        if cos_theta <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_theta - cos_outer) / (cos_inner - cos_outer);
+
+        return t.powf(self.falloff);
+    }
+}
+
+impl Light for SpotLight {
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, _rng: &mut R) -> Option<LightSample> {
+        let point_to_light = vec_sub(&self.position, position);
+        let distance = vec_get_length(&point_to_light);
+        let direction = vec_normalize(&point_to_light);
+
+        let irradiance = get_distance_attenuation(self.intensity, distance, self.range)
+            * self.get_cone_attenuation(&vec_multiplication(&direction, -1.0));
+        if irradiance <= 0.0 {
+            return None;
+        }
+
+        return Some(LightSample {
+            direction: direction,
+            distance: distance,
+            radiance: vec_multiplication(&self.color, irradiance),
+            pdf: 1.0,
+            is_delta: true
+        });
+    }
+
+    fn pdf(&self, _position: &Vector3D, _direction: &Vector3D) -> f32 {
+        return 0.0;
+    }
+}
+
+// ================================================
+// Area light
+// ================================================
+
+/**
+ * Emissive sphere or triangle of the world, it emits the same radiance
+ * from every point and to both sides.
+ */
+#[derive(Clone, Copy)]
+pub struct AreaLight {
+    pub primitive: Primitive,
+    // Position of the primitive in the world objects.
+    pub primitive_index: usize,
+
+    pub emission: Vector3D
+}
+
+impl AreaLight {
+    /**
+     * Planes are infinite and can't be area lights.
+     *
+     * @param {Primitive} primitive
+     * @param {usize} primitive_index
+     * @param {Vector3D} emission
+     *
+     * @return {Option<AreaLight>}
+     */
+    pub fn new(primitive: Primitive, primitive_index: usize, emission: Vector3D) -> Option<AreaLight> {
+        if let Primitive::Plane(_) = primitive {
+            return None;
+        }
+
+        return Some(AreaLight {
+            primitive: primitive,
+            primitive_index: primitive_index,
+            emission: emission
+        });
+    }
+
+    pub fn get_area(&self) -> f32 {
+        return match self.primitive {
+            Primitive::Sphere(sphere) => 4.0 * f32::consts::PI * sphere.radius * sphere.radius,
+            Primitive::Triangle(triangle) => {
+                vec_get_length(&vec_cross(&vec_sub(&triangle.b, &triangle.a), &vec_sub(&triangle.c, &triangle.a))) / 2.0
+            },
+            Primitive::Plane(_) => 0.0
+        };
+    }
+
+    /**
+     * Approximate emitted power, lights are chosen by it.
+     *
+     * @return {f32}
+     */
+    pub fn get_power(&self) -> f32 {
+        return get_luminance(&self.emission) * self.get_area() * f32::consts::PI;
+    }
+
+    /**
+     * Converts the uniform area density of a point on the light to solid angle.
+     *
+     * @param {Vector3D} position Surface point that receives the light.
+     * @param {Vector3D} light_point
+     * @param {Vector3D} light_normal
+     *
+     * @return {Option<LightSample>}
+     */
+    fn get_area_sample(&self, position: &Vector3D, light_point: &Vector3D, light_normal: &Vector3D) -> Option<LightSample> {
+        let point_to_light = vec_sub(light_point, position);
+        let distance = vec_get_length(&point_to_light);
+        if distance <= SHADOW_EPSILON {
+            return None;
+        }
+
+        let direction = vec_multiplication(&point_to_light, 1.0 / distance);
+        let cos_theta_light = vec_dot(light_normal, &direction).abs();
+        if cos_theta_light <= 1e-6 {
+            return None;
+        }
+
+        return Some(LightSample {
+            direction: direction,
+            distance: distance - SHADOW_EPSILON,
+            radiance: self.emission,
+            pdf: distance * distance / (cos_theta_light * self.get_area()),
+            is_delta: false
+        });
+    }
+}
+
+impl Light for AreaLight {
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, rng: &mut R) -> Option<LightSample> {
+        let u1 = rng.gen::<f32>();
+        let u2 = rng.gen::<f32>();
+
+        return match self.primitive {
+            Primitive::Triangle(triangle) => {
+                // Uniform barycentric coordinates.
+                let root = u1.sqrt();
+                let weight_a = 1.0 - root;
+                let weight_b = u2 * root;
+
+                let light_point = vec_sum(
+                    &vec_sum(&vec_multiplication(&triangle.a, weight_a), &vec_multiplication(&triangle.b, weight_b)),
+                    &vec_multiplication(&triangle.c, 1.0 - weight_a - weight_b)
+                );
+
+                self.get_area_sample(position, &light_point, &triangle.inner_plane.normal)
+            },
+            Primitive::Sphere(sphere) => {
+                let point_to_center = vec_sub(&sphere.origin, position);
+                let center_distance2 = vec_dot(&point_to_center, &point_to_center);
+                let radius2 = sphere.radius * sphere.radius;
+
+                // From the inside the whole sphere is seen, sample its area.
+                if center_distance2 <= radius2 {
+                    let z = 1.0 - 2.0 * u1;
+                    let ring = (1.0 - z * z).max(0.0).sqrt();
+                    let phi = 2.0 * f32::consts::PI * u2;
+                    let light_normal = Vector3D::new(ring * phi.cos(), ring * phi.sin(), z);
+
+                    return self.get_area_sample(position, &vec_sum(&sphere.origin, &vec_multiplication(&light_normal, sphere.radius)), &light_normal);
+                }
+
+                // From the outside, sample the cone of directions it covers.
+                let center_distance = center_distance2.sqrt();
+                let sin2_theta_max = radius2 / center_distance2;
+                let cos_theta_max = (1.0 - sin2_theta_max).max(0.0).sqrt();
+
+                let direction = sample_cone(&vec_multiplication(&point_to_center, 1.0 / center_distance), cos_theta_max, u1, u2);
+                let cos_theta = vec_dot(&direction, &point_to_center) / center_distance;
+                let sin2_theta = (1.0 - cos_theta * cos_theta).max(0.0);
+
+                // Closest intersection along the direction, the tangent point at the edge.
+                let distance = center_distance * cos_theta - (radius2 - center_distance2 * sin2_theta).max(0.0).sqrt();
+
+                Some(LightSample {
+                    direction: direction,
+                    distance: distance - SHADOW_EPSILON,
+                    radiance: self.emission,
+                    pdf: get_cone_pdf(cos_theta_max),
+                    is_delta: false
+                })
+            },
+            Primitive::Plane(_) => None
+        };
+    }
+
+    fn pdf(&self, position: &Vector3D, direction: &Vector3D) -> f32 {
+        let hit = match self.primitive.intersects(&Line::new(*position, *direction)) {
+            Some(hit) => hit,
+            None => { return 0.0; }
+        };
+
+        if let Primitive::Sphere(sphere) = self.primitive {
+            let point_to_center = vec_sub(&sphere.origin, position);
+            let center_distance2 = vec_dot(&point_to_center, &point_to_center);
+            let radius2 = sphere.radius * sphere.radius;
+
+            if center_distance2 > radius2 {
+                return get_cone_pdf((1.0 - radius2 / center_distance2).max(0.0).sqrt());
+            }
+        }
+
+        let cos_theta_light = vec_dot(&hit.normal, direction).abs();
+        if cos_theta_light <= 1e-6 {
+            return 0.0;
+        }
+
+        return hit.t * hit.t / (cos_theta_light * self.get_area());
+    }
+
+    fn is_delta(&self) -> bool {
+        return false;
+    }
+}
+
+// ================================================
+// Scene light
+// ================================================
+
+/**
+ * Any of the lights of the world.
+ */
+#[derive(Clone, Copy)]
+pub enum SceneLight {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight)
+}
+
+impl SceneLight {
+    /**
+     * Radiance seen along a line that misses every object, only the lights
+     * with a size in the sky can be seen.
+     *
+     * @param {Vector3D} direction
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        return match self {
+            SceneLight::Directional(light) => light.get_radiance(direction),
+            _ => Vector3D::new_as_zero()
+        };
+    }
+}
+
+impl Light for SceneLight {
+    fn sample<R: Rng + ?Sized>(&self, position: &Vector3D, rng: &mut R) -> Option<LightSample> {
+        return match self {
+            SceneLight::Point(light) => light.sample(position, rng),
+            SceneLight::Directional(light) => light.sample(position, rng),
+            SceneLight::Spot(light) => light.sample(position, rng)
+        };
+    }
+
+    fn pdf(&self, position: &Vector3D, direction: &Vector3D) -> f32 {
+        return match self {
+            SceneLight::Point(light) => light.pdf(position, direction),
+            SceneLight::Directional(light) => light.pdf(position, direction),
+            SceneLight::Spot(light) => light.pdf(position, direction)
+        };
+    }
+
+    fn is_delta(&self) -> bool {
+        return match self {
+            SceneLight::Point(light) => light.is_delta(),
+            SceneLight::Directional(light) => light.is_delta(),
+            SceneLight::Spot(light) => light.is_delta()
+        };
+    }
+}
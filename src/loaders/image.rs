@@ -0,0 +1,369 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use exr::prelude::{read, ReadChannels, ReadLayers};
+
+use crate::color::{srgb_color_to_linear};
+use crate::renderer::{Film};
+use crate::vector3d::{Vector3D};
+
+use super::{LoadError, LoadErrorReason};
+
+/**
+ * Decodes a color stored with a shared exponent, as in Radiance files.
+ *
+ * @param {[u8; 4]} rgbe Red, green and blue mantissas and the exponent.
+ *
+ * @return {Vector3D}
+ */
+pub fn rgbe_to_color(rgbe: &[u8; 4]) -> Vector3D {
+    if rgbe[3] == 0 {
+        return Vector3D::new_as_zero();
+    }
+
+    // The mantissas are in [0, 256), scaled by 2^(exponent - 128).
+    let scale = 2.0f32.powi(rgbe[3] as i32 - 136);
+
+    return Vector3D::new(rgbe[0] as f32 * scale, rgbe[1] as f32 * scale, rgbe[2] as f32 * scale);
+}
+
+fn new_bad_image(token: &str) -> LoadError {
+    return LoadError::new(0, token, LoadErrorReason::BadImage);
+}
+
+/**
+ * Reads the text line that starts at the position and moves past it.
+ *
+ * @param {&[u8]} data
+ * @param {usize} position
+ *
+ * @return {Option<String>} The line without the line break, None at the end of the data.
+ */
+fn read_line(data: &[u8], position: &mut usize) -> Option<String> {
+    let rest = data.get(*position..)?;
+    let length = rest.iter().position(|byte| *byte == b'\n')?;
+
+    *position += length + 1;
+
+    return Some(String::from_utf8_lossy(&rest[..length]).trim_end_matches('\r').to_string());
+}
+
+/**
+ * Reads one scanline of RGBE pixels, flat or with the per channel run length encoding.
+ *
+ * @param {&[u8]} data
+ * @param {usize} position
+ * @param {&mut [[u8; 4]]} scanline
+ *
+ * @return {Option<()>} None if the data is truncated or malformed.
+ */
+fn read_scanline(data: &[u8], position: &mut usize, scanline: &mut [[u8; 4]]) -> Option<()> {
+    let width = scanline.len();
+    let header = data.get(*position..*position + 4)?;
+
+    // Encoded scanlines start with 2, 2 and the width, anything else is flat.
+    let is_encoded = (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0;
+
+    if !is_encoded {
+        for pixel in scanline.iter_mut() {
+            pixel.copy_from_slice(data.get(*position..*position + 4)?);
+            *position += 4;
+        }
+
+        return Some(());
+    }
+
+    if ((header[2] as usize) << 8 | header[3] as usize) != width {
+        return None;
+    }
+
+    *position += 4;
+
+    for channel in 0..4 {
+        let mut x = 0;
+
+        while x < width {
+            let count = *data.get(*position)? as usize;
+            *position += 1;
+
+            if count > 128 {
+                // Run of the same value.
+                let run = count - 128;
+                let value = *data.get(*position)?;
+                *position += 1;
+
+                if x + run > width {
+                    return None;
+                }
+
+                for pixel in scanline[x..x + run].iter_mut() {
+                    pixel[channel] = value;
+                }
+
+                x += run;
+            } else {
+                // Literal values.
+                if count == 0 || x + count > width {
+                    return None;
+                }
+
+                let values = data.get(*position..*position + count)?;
+                *position += count;
+
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values.iter()) {
+                    pixel[channel] = *value;
+                }
+
+                x += count;
+            }
+        }
+    }
+
+    return Some(());
+}
+
+/**
+ * Parses a Radiance HDR image with the standard -Y height +X width orientation.
+ *
+ * @param {&[u8]} data
+ *
+ * @return {Result<Film, LoadError>}
+ */
+pub fn parse_hdr(data: &[u8]) -> Result<Film, LoadError> {
+    let mut position: usize = 0;
+
+    // --- Header ---
+    match read_line(data, &mut position) {
+        Some(ref line) if line.starts_with("#?") => {},
+        _ => { return Err(new_bad_image("header")); }
+    }
+
+    loop {
+        let line = match read_line(data, &mut position) {
+            Some(line) => line,
+            None => { return Err(new_bad_image("header")); }
+        };
+
+        if line.is_empty() {
+            break;
+        }
+
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(new_bad_image(&line));
+        }
+    }
+
+    let resolution = match read_line(data, &mut position) {
+        Some(line) => line,
+        None => { return Err(new_bad_image("resolution")); }
+    };
+
+    let components: Vec<&str> = resolution.split_whitespace().collect();
+    let size = match components.as_slice() {
+        ["-Y", height, "+X", width] => match (height.parse::<usize>(), width.parse::<usize>()) {
+            (Ok(height), Ok(width)) if height > 0 && width > 0 => Some((width, height)),
+            _ => None
+        },
+        _ => None
+    };
+
+    let (width, height) = match size {
+        Some(size) => size,
+        None => { return Err(new_bad_image(&resolution)); }
+    };
+
+    // --- Pixels ---
+    let mut film = Film::new(width, height);
+    let mut scanline: Vec<[u8; 4]> = vec![[0; 4]; width];
+
+    for y in 0..height {
+        if read_scanline(data, &mut position, &mut scanline).is_none() {
+            return Err(new_bad_image(&format!("scanline {}", y + 1)));
+        }
+
+        for (x, rgbe) in scanline.iter().enumerate() {
+            film.set_pixel(x, y, rgbe_to_color(rgbe));
+        }
+    }
+
+    return Ok(film);
+}
+
+/**
+ * Parses the red, green and blue channels of the first layer of an OpenEXR image.
+ *
+ * @param {&[u8]} data
+ *
+ * @return {Result<Film, LoadError>}
+ */
+pub fn parse_exr(data: &[u8]) -> Result<Film, LoadError> {
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgb_channels(
+            |resolution, _| Film::new(resolution.width(), resolution.height()),
+            |film: &mut Film, position, (r, g, b): (f32, f32, f32)| {
+                film.set_pixel(position.x(), position.y(), Vector3D::new(r, g, b));
+            }
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(Cursor::new(data));
+
+    return match image {
+        Ok(image) => Ok(image.layer_data.channel_data.pixels),
+        Err(error) => Err(new_bad_image(&error.to_string()))
+    };
+}
+
+/**
+ * Creates a film from integer pixels.
+ *
+ * @param {usize} width
+ * @param {usize} height
+ * @param {bool} srgb Decodes the colors from sRGB.
+ * @param {F} get_color Color of a pixel in [0, 1] by its x and y.
+ *
+ * @return {Film}
+ */
+fn new_film<F: Fn(usize, usize) -> Vector3D>(width: usize, height: usize, srgb: bool, get_color: F) -> Film {
+    let mut film = Film::new(width, height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = get_color(x, y);
+
+            film.set_pixel(x, y, if srgb { srgb_color_to_linear(&color) } else { color });
+        }
+    }
+
+    return film;
+}
+
+/**
+ * Parses a PNG image of any color type and bit depth, the alpha is dropped.
+ *
+ * @param {&[u8]} data
+ * @param {bool} srgb Decodes the colors from sRGB, keep it off for images with data like roughness.
+ *
+ * @return {Result<Film, LoadError>}
+ */
+pub fn parse_png(data: &[u8], srgb: bool) -> Result<Film, LoadError> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+
+    // Palettes become RGB and the small bit depths 8 bits.
+    decoder.set_transformations(png::Transformations::EXPAND);
+
+    let mut reader = match decoder.read_info() {
+        Ok(reader) => reader,
+        Err(error) => { return Err(new_bad_image(&error.to_string())); }
+    };
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = match reader.next_frame(&mut buffer) {
+        Ok(info) => info,
+        Err(error) => { return Err(new_bad_image(&error.to_string())); }
+    };
+
+    let samples = info.color_type.samples();
+    let sixteen_bit = info.bit_depth == png::BitDepth::Sixteen;
+    let sample_size = if sixteen_bit { 2 } else { 1 };
+
+    let get_sample = |x: usize, y: usize, channel: usize| {
+        let offset = y * info.line_size + (x * samples + channel) * sample_size;
+
+        if sixteen_bit {
+            return u16::from_be_bytes([buffer[offset], buffer[offset + 1]]) as f32 / 65535.0;
+        }
+
+        return buffer[offset] as f32 / 255.0;
+    };
+
+    // Grey images, with or without alpha, have a single color sample.
+    let is_grey = samples < 3;
+
+    return Ok(new_film(info.width as usize, info.height as usize, srgb, |x, y| {
+        if is_grey {
+            let grey = get_sample(x, y, 0);
+            return Vector3D::new(grey, grey, grey);
+        }
+
+        return Vector3D::new(get_sample(x, y, 0), get_sample(x, y, 1), get_sample(x, y, 2));
+    }));
+}
+
+/**
+ * Parses a baseline or progressive JPEG image.
+ *
+ * @param {&[u8]} data
+ * @param {bool} srgb Decodes the colors from sRGB, keep it off for images with data like roughness.
+ *
+ * @return {Result<Film, LoadError>}
+ */
+pub fn parse_jpeg(data: &[u8], srgb: bool) -> Result<Film, LoadError> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(data));
+
+    let pixels = match decoder.decode() {
+        Ok(pixels) => pixels,
+        Err(error) => { return Err(new_bad_image(&error.to_string())); }
+    };
+
+    let info = match decoder.info() {
+        Some(info) => info,
+        None => { return Err(new_bad_image("header")); }
+    };
+
+    let width = info.width as usize;
+    let format = info.pixel_format;
+    let pixel_size = format.pixel_bytes();
+
+    return Ok(new_film(width, info.height as usize, srgb, |x, y| {
+        let pixel = &pixels[(y * width + x) * pixel_size..];
+        let get_value = |index: usize| pixel[index] as f32 / 255.0;
+
+        return match format {
+            jpeg_decoder::PixelFormat::L8 => Vector3D::new(get_value(0), get_value(0), get_value(0)),
+            jpeg_decoder::PixelFormat::L16 => {
+                let grey = u16::from_ne_bytes([pixel[0], pixel[1]]) as f32 / 65535.0;
+                Vector3D::new(grey, grey, grey)
+            },
+            jpeg_decoder::PixelFormat::RGB24 => Vector3D::new(get_value(0), get_value(1), get_value(2)),
+            jpeg_decoder::PixelFormat::CMYK32 => {
+                // The black ink darkens the other three.
+                let white = 1.0 - get_value(3);
+                Vector3D::new((1.0 - get_value(0)) * white, (1.0 - get_value(1)) * white, (1.0 - get_value(2)) * white)
+            }
+        };
+    }));
+}
+
+/**
+ * Loads an image, the format comes from the extension: .hdr, .exr, .png, .jpg or .jpeg.
+ * Float images are always linear.
+ *
+ * @param {&Path} path
+ * @param {bool} srgb Decodes the PNG and JPEG colors from sRGB.
+ *
+ * @return {Result<Film, LoadError>}
+ */
+pub fn load_image(path: &Path, srgb: bool) -> Result<Film, LoadError> {
+    let extension = path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let parse: fn(&[u8], bool) -> Result<Film, LoadError> = match extension.as_str() {
+        "hdr" => |data, _| parse_hdr(data),
+        "exr" => |data, _| parse_exr(data),
+        "png" => parse_png,
+        "jpg" | "jpeg" => parse_jpeg,
+        _ => { return Err(LoadError::new(0, &extension, LoadErrorReason::UnsupportedFormat).in_file(path)); }
+    };
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(_) => { return Err(LoadError::new_unreadable(path)); }
+    };
+
+    return parse(&data, srgb).map_err(|error| error.in_file(path));
+}
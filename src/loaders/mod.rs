@@ -0,0 +1,133 @@
+use std::fmt;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+pub mod obj;
+pub mod mtl;
+pub mod scene;
+pub mod image;
+
+pub use self::obj::{load_obj, load_obj_file};
+pub use self::mtl::{load_mtl, MtlMaterial};
+pub use self::scene::{load_scene, parse_scene, Scene, SceneError};
+pub use self::image::{load_image, parse_hdr, parse_exr, parse_png, parse_jpeg};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadErrorReason {
+    BadFloat,
+    BadIndex,
+    IndexOutOfRange,
+    MissingValue,
+    TooFewVertices,
+    UnreadableFile,
+    BadImage,
+    UnsupportedFormat
+}
+
+/**
+ * Error found while loading a file, with the place where it happened.
+ */
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    // None when loading from memory.
+    pub file: Option<PathBuf>,
+
+    // 1 based, like text editors. 0 if the error is about the whole file.
+    pub line: usize,
+    pub token: String,
+    pub reason: LoadErrorReason
+}
+
+impl LoadError {
+    pub fn new(line: usize, token: &str, reason: LoadErrorReason) -> LoadError {
+        return LoadError {
+            file: None,
+
+            line: line,
+            token: token.to_string(),
+            reason: reason
+        };
+    }
+
+    /**
+     * Creates the error for a file that can't be read.
+     *
+     * @param {&Path} path
+     *
+     * @return {LoadError}
+     */
+    pub fn new_unreadable(path: &Path) -> LoadError {
+        let mut error = LoadError::new(0, &path.to_string_lossy(), LoadErrorReason::UnreadableFile);
+        error.file = Some(path.to_path_buf());
+
+        return error;
+    }
+
+    /**
+     * Sets the file the error comes from, if it's not already known.
+     *
+     * @param {&Path} path
+     *
+     * @return {LoadError}
+     */
+    pub fn in_file(mut self, path: &Path) -> LoadError {
+        if self.file.is_none() {
+            self.file = Some(path.to_path_buf());
+        }
+
+        return self;
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self.reason {
+            LoadErrorReason::BadFloat => "is not a valid number",
+            LoadErrorReason::BadIndex => "is not a valid index",
+            LoadErrorReason::IndexOutOfRange => "is out of range",
+            LoadErrorReason::MissingValue => "needs more values",
+            LoadErrorReason::TooFewVertices => "has less than three vertices",
+            LoadErrorReason::UnreadableFile => "can't be read",
+            LoadErrorReason::BadImage => "is not valid",
+            LoadErrorReason::UnsupportedFormat => "is not a supported format"
+        };
+
+        if self.reason == LoadErrorReason::UnreadableFile {
+            return write!(f, "'{}' {}", self.token, description);
+        }
+
+        // Images have no lines.
+        if let (Some(file), 0) = (&self.file, self.line) {
+            return write!(f, "{}: '{}' {}", file.display(), self.token, description);
+        }
+
+        if let Some(file) = &self.file {
+            return write!(f, "{} line {}: '{}' {}", file.display(), self.line, self.token, description);
+        }
+
+        return write!(f, "line {}: '{}' {}", self.line, self.token, description);
+    }
+}
+
+impl Error for LoadError {}
+
+/**
+ * Parses the float at the given position of the line components.
+ *
+ * @param {&[&str]} components
+ * @param {usize} position
+ * @param {usize} line_number
+ *
+ * @return {Result<f32, LoadError>}
+ */
+fn parse_float(components: &[&str], position: usize, line_number: usize) -> Result<f32, LoadError> {
+    let token = match components.get(position) {
+        Some(token) => token,
+        None => { return Err(LoadError::new(line_number, components[0], LoadErrorReason::MissingValue)); }
+    };
+
+    return match token.parse::<f32>() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(LoadError::new(line_number, token, LoadErrorReason::BadFloat))
+    };
+}
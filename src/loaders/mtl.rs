@@ -0,0 +1,205 @@
+use crate::color::{Material};
+
+use crate::vector3d::{Vector3D};
+
+use super::{LoadError, LoadErrorReason, parse_float};
+
+/**
+ * Material as written in a MTL file.
+ */
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+
+    // Kd, Ke
+    pub diffuse: Vector3D,
+    pub emission: Vector3D,
+
+    // Ns, Pr, Pm
+    pub specular_exponent: Option<f32>,
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+
+    // d (or 1 - Tr), Ni
+    pub dissolve: f32,
+    pub optical_density: Option<f32>,
+
+    // Tf, the color let through by the refracting illumination models.
+    pub transmission_filter: Option<Vector3D>,
+    pub illumination_model: u32,
+
+    // map_Kd, norm and bump (or map_Bump), relative to the MTL file.
+    pub diffuse_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub bump_map: Option<String>,
+    // -bm option of the bump map.
+    pub bump_multiplier: f32
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> MtlMaterial {
+        return MtlMaterial {
+            name: name.to_string(),
+
+            diffuse: Vector3D::new(0.8, 0.8, 0.8),
+            emission: Vector3D::new_as_zero(),
+
+            specular_exponent: None,
+            roughness: None,
+            metallic: None,
+
+            dissolve: 1.0,
+            optical_density: None,
+
+            transmission_filter: None,
+            illumination_model: 2,
+
+            diffuse_map: None,
+            normal_map: None,
+            bump_map: None,
+            bump_multiplier: 1.0
+        };
+    }
+
+    /**
+     * Converts the MTL parameters in a renderer material.
+     *
+     * The PBR extension roughness (Pr) is preferred, otherwise it's derived
+     * from the Phong exponent with the Beckmann equivalence. The dissolve (d)
+     * is the coverage of the surface, the opacity, not glass. Only the transmission
+     * filter (Tf) of the refracting illumination models (4, 6, 7 and 9) makes a
+     * dielectric, with the optical density (Ni) as the index of refraction when it's given.
+     *
+     * @return {Material}
+     */
+    pub fn to_material(&self) -> Material {
+        let roughness = match (self.roughness, self.specular_exponent) {
+            (Some(roughness), _) => roughness,
+            (None, Some(exponent)) => (2.0 / (exponent.max(0.0) + 2.0)).sqrt(),
+            (None, None) => 0.5
+        };
+
+        let metalic = self.metallic.unwrap_or(0.0);
+
+        let mut material = Material::new(
+            self.diffuse,
+            self.emission,
+            roughness.clamp(0.0, 1.0),
+            metalic.clamp(0.0, 1.0)
+        );
+
+        // Exporters fade materials out with the dissolve, the hidden part is just not there.
+        material.opacity = self.dissolve.clamp(0.0, 1.0);
+
+        // Some exporters write a white filter on opaque materials, the illumination model tells them apart.
+        if let Some(filter) = self.transmission_filter {
+            if matches!(self.illumination_model, 4 | 6 | 7 | 9) {
+                material.transmission = ((filter.x + filter.y + filter.z) / 3.0).clamp(0.0, 1.0);
+            }
+        }
+
+        // Without Ni the glass keeps the default index, 1 would make it invisible.
+        if let Some(optical_density) = self.optical_density {
+            if optical_density > 0.0 {
+                material.ior = optical_density;
+            }
+        }
+
+        return material;
+    }
+}
+
+fn parse_color(components: &[&str], line_number: usize) -> Result<Vector3D, LoadError> {
+    let r: f32 = parse_float(components, 1, line_number)?;
+
+    // A single value is a grey.
+    if components.len() == 2 {
+        return Ok(Vector3D::new(r, r, r));
+    }
+
+    let g: f32 = parse_float(components, 2, line_number)?;
+    let b: f32 = parse_float(components, 3, line_number)?;
+
+    return Ok(Vector3D::new(r, g, b));
+}
+
+/**
+ * Reads the materials of a MTL file.
+ *
+ * @param {String} mtl_file
+ *
+ * @return {Result<Vec<MtlMaterial>, LoadError>}
+ */
+pub fn load_mtl(mtl_file: String) -> Result<Vec<MtlMaterial>, LoadError> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line_index, line) in mtl_file.lines().enumerate() {
+        let line_number = line_index + 1;
+
+        // Drop the comments
+        let content = line.split('#').next().unwrap();
+        let components: Vec<&str> = content.split_whitespace().collect();
+
+        if components.is_empty() {
+            continue;
+        }
+
+        if components[0] == "newmtl" {
+            if components.len() < 2 {
+                return Err(LoadError::new(line_number, components[0], LoadErrorReason::MissingValue));
+            }
+
+            materials.push(MtlMaterial::new(&components[1..].join(" ")));
+            continue;
+        }
+
+        // Every other statement belongs to a material.
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => { continue; }
+        };
+
+        match components[0] {
+            "Kd" => { material.diffuse = parse_color(&components, line_number)?; },
+            "Ke" => { material.emission = parse_color(&components, line_number)?; },
+            "Ns" => { material.specular_exponent = Some(parse_float(&components, 1, line_number)?); },
+            "Pr" => { material.roughness = Some(parse_float(&components, 1, line_number)?); },
+            "Pm" => { material.metallic = Some(parse_float(&components, 1, line_number)?); },
+            "d" => { material.dissolve = parse_float(&components, 1, line_number)?; },
+            "Tr" => { material.dissolve = 1.0 - parse_float(&components, 1, line_number)?; },
+            "Ni" => { material.optical_density = Some(parse_float(&components, 1, line_number)?); },
+            "Tf" => { material.transmission_filter = Some(parse_color(&components, line_number)?); },
+            "illum" => { material.illumination_model = parse_float(&components, 1, line_number)? as u32; },
+            "map_Kd" => {
+                // The options go before the file name: map_Kd -s 2 2 1 wood.png
+                if components.len() < 2 {
+                    return Err(LoadError::new(line_number, components[0], LoadErrorReason::MissingValue));
+                }
+
+                material.diffuse_map = Some(components[components.len() - 1].to_string());
+            },
+            "norm" => {
+                if components.len() < 2 {
+                    return Err(LoadError::new(line_number, components[0], LoadErrorReason::MissingValue));
+                }
+
+                material.normal_map = Some(components[components.len() - 1].to_string());
+            },
+            "bump" | "map_Bump" | "map_bump" => {
+                // bump -bm 0.5 height.png
+                if components.len() < 2 {
+                    return Err(LoadError::new(line_number, components[0], LoadErrorReason::MissingValue));
+                }
+
+                if let Some(option) = components.iter().position(|component| *component == "-bm") {
+                    material.bump_multiplier = parse_float(&components, option + 1, line_number)?;
+                }
+
+                material.bump_map = Some(components[components.len() - 1].to_string());
+            },
+            _ => {}
+        }
+    }
+
+    return Ok(materials);
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::geometry::{
+    Mesh, Triangle
+};
+
+use crate::color::{Material};
+use crate::texture::{SceneTexture, ImageTexture, WrapMode};
+
+use super::{LoadError, LoadErrorReason, parse_float, load_image};
+use super::mtl::{load_mtl};
+
+use crate::vector3d::{
+    Vector3D,
+    vec_sum, vec_sub, vec_multiplication, vec_dot, vec_cross, vec_get_length, vec_normalize
+};
+
+// Position, texture coordinate and normal indices of a face vertex.
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+/**
+ * Converts an OBJ index, 1 based or negative relative to the end, in a 0 based one.
+ *
+ * @param {&str} token
+ * @param {usize} count Number of elements read so far.
+ * @param {usize} line_number
+ *
+ * @return {Result<usize, LoadError>}
+ */
+fn resolve_index(token: &str, count: usize, line_number: usize) -> Result<usize, LoadError> {
+    let index: i64 = match token.parse::<i64>() {
+        Ok(index) if index != 0 => index,
+        _ => { return Err(LoadError::new(line_number, token, LoadErrorReason::BadIndex)); }
+    };
+
+    let resolved: i64 = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LoadError::new(line_number, token, LoadErrorReason::IndexOutOfRange));
+    }
+
+    return Ok(resolved as usize);
+}
+
+/**
+ * Signed double area of the polygon (x, y) points, positive if counter clockwise.
+ */
+fn get_signed_area(points: &[(f32, f32)]) -> f32 {
+    let mut area: f32 = 0.0;
+
+    for i in 0..points.len() {
+        let current = points[i];
+        let next = points[(i + 1) % points.len()];
+
+        area += current.0 * next.1 - next.0 * current.1;
+    }
+
+    return area;
+}
+
+fn is_point_in_triangle(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> bool {
+    let edge = |u: (f32, f32), v: (f32, f32)| (v.0 - u.0) * (p.1 - u.1) - (v.1 - u.1) * (p.0 - u.0);
+
+    return edge(a, b) >= 0.0 && edge(b, c) >= 0.0 && edge(c, a) >= 0.0;
+}
+
+/**
+ * Splits a planar polygon in triangles using ear clipping.
+ * The triangles keep the winding of the polygon.
+ *
+ * @param {&[Vector3D]} polygon
+ *
+ * @return {Vec<[usize; 3]>} Indices in the polygon of every triangle.
+ */
+fn triangulate(polygon: &[Vector3D]) -> Vec<[usize; 3]> {
+    let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(polygon.len() - 2);
+
+    if polygon.len() == 3 {
+        triangles.push([0, 1, 2]);
+        return triangles;
+    }
+
+    // Newell's method gives a robust normal even for concave polygons.
+    let mut normal = Vector3D::new_as_zero();
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let next = polygon[(i + 1) % polygon.len()];
+
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    // Project on the plane where the polygon looks bigger.
+    let projected: Vec<(f32, f32)> = polygon.iter().map(|p| {
+        if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+            (p.y, p.z)
+        } else if normal.y.abs() >= normal.z.abs() {
+            (p.z, p.x)
+        } else {
+            (p.x, p.y)
+        }
+    }).collect();
+
+    // Work always with a counter clockwise outline, the original order is kept in the output.
+    let orientation: f32 = if get_signed_area(&projected) < 0.0 { -1.0 } else { 1.0 };
+    let points: Vec<(f32, f32)> = projected.iter().map(|p| (p.0 * orientation, p.1)).collect();
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let mut ear: Option<usize> = None;
+
+        for i in 0..count {
+            let prev = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+
+            let (a, b, c) = (points[prev], points[current], points[next]);
+
+            // Reflex or degenerate corners can't be ears.
+            let convexity = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+            if convexity <= 0.0 {
+                continue;
+            }
+
+            let contains_other = remaining.iter().any(|&other| {
+                other != prev && other != current && other != next
+                    && is_point_in_triangle(points[other], a, b, c)
+            });
+
+            if !contains_other {
+                ear = Some(i);
+                break;
+            }
+        }
+
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+                remaining.remove(i);
+            },
+            None => {
+                // Self intersecting or degenerate outline, finish it as a fan.
+                for i in 1..remaining.len() - 1 {
+                    triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+                }
+                return triangles;
+            }
+        }
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    return triangles;
+}
+
+/**
+ * Builds a mesh from the contents of an OBJ file, `usemtl` statements are ignored.
+ *
+ * @param {String} obj_file
+ * @param {usize} material_index Material of every triangle.
+ *
+ * @return {Result<Mesh, LoadError>}
+ */
+pub fn load_obj(obj_file: String, material_index: usize) -> Result<Mesh, LoadError> {
+    return parse_obj(&obj_file, material_index, &HashMap::new());
+}
+
+/**
+ * Loads an OBJ file and the material libraries it references.
+ * The library materials are appended to the given list and the faces use them
+ * through `usemtl`, faces without a known material use the default one.
+ * The diffuse maps of the library become image textures appended to the given list.
+ *
+ * @param {&Path} path
+ * @param {&mut Vec<Material>} materials Usually the world materials.
+ * @param {&mut Vec<SceneTexture>} textures Usually the world textures.
+ * @param {usize} material_index Default material.
+ *
+ * @return {Result<Mesh, LoadError>}
+ */
+pub fn load_obj_file(path: &Path, materials: &mut Vec<Material>, textures: &mut Vec<SceneTexture>, material_index: usize) -> Result<Mesh, LoadError> {
+    let obj_file = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => { return Err(LoadError::new_unreadable(path)); }
+    };
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut library: HashMap<String, usize> = HashMap::new();
+    let mut maps: HashMap<(PathBuf, bool), usize> = HashMap::new();
+
+    for line in obj_file.lines() {
+        let content = line.split('#').next().unwrap();
+        let mut components = content.split_whitespace();
+
+        if components.next() != Some("mtllib") {
+            continue;
+        }
+
+        for file_name in components {
+            let mtl_path = directory.join(file_name);
+
+            let mtl_file = match fs::read_to_string(&mtl_path) {
+                Ok(content) => content,
+                Err(_) => { return Err(LoadError::new_unreadable(&mtl_path)); }
+            };
+
+            let mtl_materials = load_mtl(mtl_file).map_err(|error| error.in_file(&mtl_path))?;
+
+            for mtl_material in mtl_materials {
+                let mut material = mtl_material.to_material();
+
+                let mtl_directory = mtl_path.parent().unwrap_or(Path::new(""));
+
+                if let Some(ref diffuse_map) = mtl_material.diffuse_map {
+                    material.base_color_texture = Some(load_map(&mtl_directory.join(diffuse_map), true, textures, &mut maps)?);
+                }
+
+                // Normals and heights are data, not colors.
+                if let Some(ref normal_map) = mtl_material.normal_map {
+                    material.normal_texture = Some(load_map(&mtl_directory.join(normal_map), false, textures, &mut maps)?);
+                }
+
+                if let Some(ref bump_map) = mtl_material.bump_map {
+                    material.bump_texture = Some(load_map(&mtl_directory.join(bump_map), false, textures, &mut maps)?);
+                    material.bump_scale = mtl_material.bump_multiplier;
+                }
+
+                library.insert(mtl_material.name.clone(), materials.len());
+                materials.push(material);
+            }
+        }
+    }
+
+    return parse_obj(&obj_file, material_index, &library).map_err(|error| error.in_file(path));
+}
+
+/**
+ * Loads the image of a material map once, materials can share the same image.
+ *
+ * @param {&Path} path
+ * @param {bool} srgb Decodes the colors from sRGB.
+ * @param {&mut Vec<SceneTexture>} textures
+ * @param {&mut HashMap<(PathBuf, bool), usize>} maps Texture indices of the images already loaded.
+ *
+ * @return {Result<usize, LoadError>} Index of the texture.
+ */
+fn load_map(path: &Path, srgb: bool, textures: &mut Vec<SceneTexture>, maps: &mut HashMap<(PathBuf, bool), usize>) -> Result<usize, LoadError> {
+    let key = (path.to_path_buf(), srgb);
+
+    if let Some(index) = maps.get(&key) {
+        return Ok(*index);
+    }
+
+    let image = load_image(path, srgb)?;
+
+    textures.push(SceneTexture::Image(ImageTexture::new(image, WrapMode::Repeat)));
+    maps.insert(key, textures.len() - 1);
+
+    return Ok(textures.len() - 1);
+}
+
+/**
+ * Sets smooth tangents on the triangles with uvs, averaged over the faces that share
+ * a vertex and weighted by their area. Only faces with the same mapping handedness are
+ * averaged, so the mirrored halves of a model don't cancel out.
+ *
+ * @param {&mut [Triangle]} triangles
+ * @param {&[[VertexKey; 3]]} keys Vertices of every triangle, in the a, b, c order.
+ */
+fn generate_tangents(triangles: &mut [Triangle], keys: &[[VertexKey; 3]]) {
+    let mut sums: HashMap<(VertexKey, bool), Vector3D> = HashMap::new();
+    let mut faces: Vec<Option<(Vector3D, bool)>> = Vec::with_capacity(triangles.len());
+
+    for (triangle, triangle_keys) in triangles.iter().zip(keys.iter()) {
+        let (dpdu, dpdv) = match triangle.get_uv_derivatives() {
+            Some(derivatives) => derivatives,
+            None => {
+                faces.push(None);
+                continue;
+            }
+        };
+
+        let normal = triangle.inner_plane.normal;
+        let is_mirrored = vec_dot(&vec_cross(&normal, &dpdu), &dpdv) < 0.0;
+
+        let area = vec_get_length(&vec_cross(&vec_sub(&triangle.b, &triangle.a), &vec_sub(&triangle.c, &triangle.a))) / 2.0;
+        let weighted = vec_multiplication(&vec_normalize(&dpdu), area);
+
+        for key in triangle_keys.iter() {
+            let sum = sums.entry((*key, is_mirrored)).or_insert_with(Vector3D::new_as_zero);
+            *sum = vec_sum(sum, &weighted);
+        }
+
+        faces.push(Some((dpdu, is_mirrored)));
+    }
+
+    for ((triangle, triangle_keys), face) in triangles.iter_mut().zip(keys.iter()).zip(faces.iter()) {
+        let (dpdu, is_mirrored) = match face {
+            Some(face) => *face,
+            None => { continue; }
+        };
+
+        let mut tangents = [dpdu; 3];
+        for (index, key) in triangle_keys.iter().enumerate() {
+            let normal = match triangle.normals {
+                Some(normals) => normals[index],
+                None => triangle.inner_plane.normal
+            };
+
+            // Perpendicular to the vertex normal, or the flat one if they cancel out.
+            let sum = sums[&(*key, is_mirrored)];
+            let tangent = vec_sub(&sum, &vec_multiplication(&normal, vec_dot(&normal, &sum)));
+
+            if vec_get_length(&tangent) > 1e-8 {
+                tangents[index] = tangent;
+            }
+        }
+
+        triangle.set_tangents(tangents);
+    }
+}
+
+/**
+ * Builds a mesh from the contents of an OBJ file.
+ *
+ * @param {&str} obj_file
+ * @param {usize} material_index Default material.
+ * @param {&HashMap<String, usize>} library Material indices by name.
+ *
+ * @return {Result<Mesh, LoadError>}
+ */
+fn parse_obj(obj_file: &str, material_index: usize, library: &HashMap<String, usize>) -> Result<Mesh, LoadError> {
+    let mut mesh: Mesh = Mesh::new();
+    let mut current_material: usize = material_index;
+    let mut vertices: Vec<Vector3D> = Vec::new();
+    let mut normals: Vec<Vector3D> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+
+    // Vertices of every triangle, to share the tangents.
+    let mut triangle_keys: Vec<[VertexKey; 3]> = Vec::new();
+
+    for (line_index, line) in obj_file.lines().enumerate() {
+        let line_number = line_index + 1;
+
+        // Drop the comments
+        let content = line.split('#').next().unwrap();
+        let components: Vec<&str> = content.split_whitespace().collect();
+
+        if components.is_empty() {
+            continue;
+        }
+
+        match components[0] {
+            "v" => {
+                // v 1.000000 1.000000 -1.000000
+                let x: f32 = parse_float(&components, 1, line_number)?;
+                let y: f32 = parse_float(&components, 2, line_number)?;
+                let z: f32 = parse_float(&components, 3, line_number)?;
+
+                vertices.push(Vector3D::new(x, y, z));
+            },
+            "vt" => {
+                // vt 0.500000 1.000000
+                let u: f32 = parse_float(&components, 1, line_number)?;
+                let mut v: f32 = 0.0;
+                if components.len() > 2 {
+                    v = parse_float(&components, 2, line_number)?;
+                }
+
+                uvs.push((u, v));
+            },
+            "vn" => {
+                // vn 0.000000 1.000000 0.000000
+                let x: f32 = parse_float(&components, 1, line_number)?;
+                let y: f32 = parse_float(&components, 2, line_number)?;
+                let z: f32 = parse_float(&components, 3, line_number)?;
+
+                normals.push(Vector3D::new(x, y, z));
+            },
+            "f" => {
+                // This is a polygon
+                // f 1/1/1 5/2/1 7/3/1 3/4/1
+                // f 1//1 -3//1 -2//1
+                let mut face_vertices: Vec<Vector3D> = Vec::new();
+                let mut face_uvs: Vec<(f32, f32)> = Vec::new();
+                let mut face_normals: Vec<Vector3D> = Vec::new();
+                let mut face_keys: Vec<VertexKey> = Vec::new();
+
+                for component in components.iter().skip(1) {
+                    let indices: Vec<&str> = component.split('/').collect();
+
+                    let vertex_index = resolve_index(indices[0], vertices.len(), line_number)?;
+                    face_vertices.push(vertices[vertex_index]);
+
+                    let mut uv_index: Option<usize> = None;
+                    if indices.len() > 1 && !indices[1].is_empty() {
+                        uv_index = Some(resolve_index(indices[1], uvs.len(), line_number)?);
+                        face_uvs.push(uvs[uv_index.unwrap()]);
+                    }
+
+                    let mut normal_index: Option<usize> = None;
+                    if indices.len() > 2 && !indices[2].is_empty() {
+                        normal_index = Some(resolve_index(indices[2], normals.len(), line_number)?);
+                        face_normals.push(normals[normal_index.unwrap()]);
+                    }
+
+                    face_keys.push((vertex_index, uv_index, normal_index));
+                }
+
+                if face_vertices.len() < 3 {
+                    return Err(LoadError::new(line_number, content.trim(), LoadErrorReason::TooFewVertices));
+                }
+
+                // Only use the attributes when every vertex has them.
+                let has_uvs = face_uvs.len() == face_vertices.len();
+                let has_normals = face_normals.len() == face_vertices.len();
+
+                for [i0, i1, i2] in triangulate(&face_vertices) {
+                    let a = face_vertices[i0];
+                    let b = face_vertices[i1];
+                    let c = face_vertices[i2];
+
+                    // Skip slivers left by collinear points.
+                    if vec_get_length(&vec_cross(&vec_sub(&b, &a), &vec_sub(&c, &a))) <= 0.0 {
+                        continue;
+                    }
+
+                    // The triangle normal is built with the opposite winding.
+                    let mut triangle = Triangle::new(a, c, b, current_material);
+
+                    if has_normals {
+                        triangle.set_normals([face_normals[i0], face_normals[i2], face_normals[i1]]);
+                    }
+
+                    if has_uvs {
+                        triangle.set_uvs([face_uvs[i0], face_uvs[i2], face_uvs[i1]]);
+                    }
+
+                    mesh.triangles.push(triangle);
+                    triangle_keys.push([face_keys[i0], face_keys[i2], face_keys[i1]]);
+                }
+            },
+            "usemtl" => {
+                // usemtl Material.001
+                let name = components[1..].join(" ");
+                current_material = *library.get(&name).unwrap_or(&material_index);
+            },
+            _ => {}
+        }
+    }
+
+    generate_tangents(&mut mesh.triangles, &triangle_keys);
+
+    return Ok(mesh);
+}
\ No newline at end of file
@@ -0,0 +1,865 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{World, PointLight, SceneLight, RenderSettings};
+use crate::lights::{DirectionalLight, SpotLight};
+use crate::camera::{Camera};
+use crate::color::{Material, ToneMapping, ToneMapOperator};
+use crate::environment::{Environment, EnvironmentMap, Gradient};
+use crate::sky::{PreethamSky};
+use crate::geometry::{Plane, Sphere, Primitive};
+use crate::texture::{
+    SceneTexture, ImageTexture, CheckerTexture, NoiseTexture, GradientTexture,
+    WrapMode, GradientAxis
+};
+use crate::integrator::{IntegratorKind};
+use crate::vector3d::{Vector3D, vec_sub, vec_get_length};
+
+use super::{LoadError, load_obj_file, load_image};
+
+// ================================================
+// Scene file description
+// ================================================
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default)]
+    settings: SettingsDescription,
+    #[serde(default)]
+    tone_mapping: ToneMappingDescription,
+    camera: CameraDescription,
+
+    sky: Option<SkyDescription>,
+    environment: Option<EnvironmentDescription>,
+
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDescription>,
+
+    #[serde(default)]
+    spheres: Vec<SphereDescription>,
+    #[serde(default)]
+    planes: Vec<PlaneDescription>,
+    #[serde(default)]
+    meshes: Vec<MeshDescription>,
+
+    #[serde(default)]
+    lights: Vec<LightDescription>,
+    #[serde(default)]
+    directional_lights: Vec<DirectionalLightDescription>,
+    #[serde(default)]
+    spot_lights: Vec<SpotLightDescription>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SettingsDescription {
+    width: usize,
+    height: usize,
+    samples: u32,
+    max_bounces: u32,
+    threads: usize,
+    integrator: String,
+    ao_distance: f32,
+    ao_samples: u32
+}
+
+impl Default for SettingsDescription {
+    fn default() -> SettingsDescription {
+        return SettingsDescription {
+            width: 512,
+            height: 512,
+            samples: 8,
+            max_bounces: 16,
+            threads: 4,
+            integrator: "path".to_string(),
+            ao_distance: 1.0,
+            ao_samples: 4
+        };
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct ToneMappingDescription {
+    operator: String,
+    exposure: f32,
+    white_point: f32
+}
+
+impl Default for ToneMappingDescription {
+    fn default() -> ToneMappingDescription {
+        let tone_mapping = ToneMapping::new_clamp();
+
+        return ToneMappingDescription {
+            operator: "clamp".to_string(),
+            exposure: tone_mapping.exposure,
+            white_point: tone_mapping.white_point
+        };
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    position: [f32; 3],
+    target: [f32; 3],
+
+    #[serde(default = "default_plane_distance")]
+    plane_distance: f32
+}
+
+fn default_plane_distance() -> f32 { 2.0 }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SkyDescription {
+    color: [f32; 3]
+}
+
+impl Default for SkyDescription {
+    fn default() -> SkyDescription {
+        return SkyDescription { color: [0.0, 0.0, 0.0] };
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDescription {
+    // constant, gradient, map or sky
+    #[serde(rename = "type")]
+    kind: String,
+
+    color: Option<[f32; 3]>,
+
+    zenith: Option<[f32; 3]>,
+    horizon: Option<[f32; 3]>,
+    ground: Option<[f32; 3]>,
+
+    path: Option<String>,
+    // Degrees around the up axis.
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "default_environment_intensity")]
+    intensity: f32,
+
+    // Degrees above the horizon and from -Z towards +X.
+    sun_elevation: Option<f32>,
+    #[serde(default)]
+    sun_azimuth: f32,
+    #[serde(default = "default_turbidity")]
+    turbidity: f32,
+    // Adds the directional light of the sun.
+    #[serde(default = "default_sun")]
+    sun: bool,
+    // Degrees.
+    #[serde(default = "default_sun_angular_diameter")]
+    sun_angular_diameter: f32
+}
+
+fn default_environment_intensity() -> f32 { 1.0 }
+fn default_turbidity() -> f32 { 3.0 }
+fn default_sun() -> bool { true }
+fn default_sun_angular_diameter() -> f32 { 0.53 }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDescription {
+    // image, checker, noise or gradient
+    #[serde(rename = "type")]
+    kind: String,
+
+    path: Option<String>,
+    // repeat, clamp or mirror
+    #[serde(default = "default_wrap")]
+    wrap: String,
+    // Decodes PNG and JPEG colors, turn it off for roughness and metalic maps.
+    #[serde(default = "default_srgb")]
+    srgb: bool,
+
+    even: Option<[f32; 3]>,
+    odd: Option<[f32; 3]>,
+
+    low: Option<[f32; 3]>,
+    high: Option<[f32; 3]>,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default = "default_persistence")]
+    persistence: f32,
+    #[serde(default)]
+    seed: u64,
+
+    start: Option<[f32; 3]>,
+    end: Option<[f32; 3]>,
+    // u or v
+    #[serde(default = "default_axis")]
+    axis: String,
+
+    // Checker squares per texture unit or noise cycles per world unit.
+    #[serde(default = "default_texture_scale")]
+    scale: f32
+}
+
+fn default_wrap() -> String { "repeat".to_string() }
+fn default_srgb() -> bool { true }
+fn default_octaves() -> u32 { 4 }
+fn default_persistence() -> f32 { 0.5 }
+fn default_axis() -> String { "u".to_string() }
+fn default_texture_scale() -> f32 { 1.0 }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDescription {
+    // Without a value, the textures are used as they are.
+    base_color: Option<[f32; 3]>,
+    emission: Option<[f32; 3]>,
+
+    roughness: Option<f32>,
+    #[serde(alias = "metallic")]
+    metalic: Option<f32>,
+
+    // Names of the textures that multiply the values.
+    base_color_texture: Option<String>,
+    emission_texture: Option<String>,
+    roughness_texture: Option<String>,
+    #[serde(alias = "metallic_texture")]
+    metalic_texture: Option<String>,
+
+    // Tangent space normal map and height map, load their images with srgb = false.
+    normal_texture: Option<String>,
+    #[serde(default = "default_map_scale")]
+    normal_scale: f32,
+    bump_texture: Option<String>,
+    #[serde(default = "default_map_scale")]
+    bump_scale: f32,
+
+    #[serde(default)]
+    transmission: f32,
+    #[serde(default = "default_ior")]
+    ior: f32,
+    #[serde(default = "default_absorption_color")]
+    absorption_color: [f32; 3]
+}
+
+fn default_base_color() -> [f32; 3] { [0.8, 0.8, 0.8] }
+fn default_map_scale() -> f32 { 1.0 }
+fn default_roughness() -> f32 { 0.5 }
+fn default_ior() -> f32 { 1.5 }
+fn default_absorption_color() -> [f32; 3] { [1.0, 1.0, 1.0] }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDescription {
+    center: [f32; 3],
+    radius: f32,
+    material: String
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDescription {
+    normal: [f32; 3],
+    point: [f32; 3],
+    material: String
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDescription {
+    path: String,
+    // Used by the faces without a material from the MTL library.
+    material: String
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDescription {
+    position: [f32; 3],
+    #[serde(default = "default_light_color")]
+    color: [f32; 3],
+
+    // Either the radiant intensity in watts per steradian or the power in watts.
+    intensity: Option<f32>,
+    power: Option<f32>,
+
+    range: Option<f32>
+}
+
+fn default_light_color() -> [f32; 3] { [1.0, 1.0, 1.0] }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirectionalLightDescription {
+    // Direction the light travels in.
+    direction: [f32; 3],
+    #[serde(default = "default_light_color")]
+    color: [f32; 3],
+
+    // Watts per square meter.
+    irradiance: f32,
+
+    // Degrees, 0 casts hard shadows.
+    #[serde(default)]
+    angular_diameter: f32
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpotLightDescription {
+    position: [f32; 3],
+    direction: [f32; 3],
+    #[serde(default = "default_light_color")]
+    color: [f32; 3],
+
+    // Watts per steradian along the axis.
+    intensity: f32,
+
+    // Half angles of the cone, in degrees.
+    #[serde(default = "default_inner_angle")]
+    inner_angle: f32,
+    #[serde(default = "default_outer_angle")]
+    outer_angle: f32,
+    #[serde(default = "default_falloff")]
+    falloff: f32,
+
+    range: Option<f32>
+}
+
+fn default_inner_angle() -> f32 { 20.0 }
+fn default_outer_angle() -> f32 { 30.0 }
+fn default_falloff() -> f32 { 1.0 }
+
+fn to_vector(v: &[f32; 3]) -> Vector3D {
+    return Vector3D::new(v[0], v[1], v[2]);
+}
+
+// ================================================
+// Scene loading
+// ================================================
+
+/**
+ * Everything needed to render a scene file.
+ */
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+    pub settings: RenderSettings
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    // The scene file can't be read.
+    Io(PathBuf),
+    // The scene file is not valid TOML or doesn't follow the schema.
+    Parse(String),
+    // Something references a material that is not declared: (element, material).
+    UnknownMaterial(String, String),
+    // A material references a texture that is not declared: (element, texture).
+    UnknownTexture(String, String),
+    // A value that makes no sense: (element, reason).
+    Invalid(String, String),
+    // A mesh file failed to load.
+    Mesh(LoadError),
+    // An environment or texture image failed to load.
+    Image(LoadError)
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            SceneError::Io(path) => write!(f, "'{}' can't be read", path.display()),
+            SceneError::Parse(message) => write!(f, "{}", message),
+            SceneError::UnknownMaterial(element, material) => {
+                write!(f, "{} uses the unknown material '{}'", element, material)
+            },
+            SceneError::UnknownTexture(element, texture) => {
+                write!(f, "{} uses the unknown texture '{}'", element, texture)
+            },
+            SceneError::Invalid(element, reason) => write!(f, "{} {}", element, reason),
+            SceneError::Mesh(error) => write!(f, "{}", error),
+            SceneError::Image(error) => write!(f, "{}", error)
+        };
+    }
+}
+
+impl Error for SceneError {}
+
+fn find_material(names: &HashMap<String, usize>, element: &str, material: &str) -> Result<usize, SceneError> {
+    return match names.get(material) {
+        Some(index) => Ok(*index),
+        None => Err(SceneError::UnknownMaterial(element.to_string(), material.to_string()))
+    };
+}
+
+/**
+ * Finds the index of an optional texture reference.
+ *
+ * @param {&HashMap<String, usize>} names
+ * @param {&str} element
+ * @param {Option<String>} texture
+ *
+ * @return {Result<Option<usize>, SceneError>}
+ */
+fn find_texture(names: &HashMap<String, usize>, element: &str, texture: &Option<String>) -> Result<Option<usize>, SceneError> {
+    let texture = match texture {
+        Some(texture) => texture,
+        None => { return Ok(None); }
+    };
+
+    return match names.get(texture) {
+        Some(index) => Ok(Some(*index)),
+        None => Err(SceneError::UnknownTexture(element.to_string(), texture.to_string()))
+    };
+}
+
+/**
+ * Creates a texture, image paths are relative to the scene directory.
+ *
+ * @param {&str} name
+ * @param {TextureDescription} description
+ * @param {&Path} directory
+ *
+ * @return {Result<SceneTexture, SceneError>}
+ */
+fn build_texture(name: &str, description: &TextureDescription, directory: &Path) -> Result<SceneTexture, SceneError> {
+    let element = format!("texture '{}'", name);
+
+    let missing = |field: &str| SceneError::Invalid(element.clone(), format!("of type '{}' needs a {}", description.kind, field));
+
+    return match description.kind.as_str() {
+        "image" => {
+            let path = description.path.as_ref().ok_or_else(|| missing("path"))?;
+
+            let wrap = match WrapMode::from_name(&description.wrap) {
+                Some(wrap) => wrap,
+                None => { return Err(SceneError::Invalid(element, format!("has the unknown wrap '{}'", description.wrap))); }
+            };
+
+            let image = match load_image(&directory.join(path), description.srgb) {
+                Ok(image) => image,
+                Err(error) => { return Err(SceneError::Image(error)); }
+            };
+
+            Ok(SceneTexture::Image(ImageTexture::new(image, wrap)))
+        },
+        "checker" => {
+            let even = description.even.ok_or_else(|| missing("even"))?;
+            let odd = description.odd.ok_or_else(|| missing("odd"))?;
+
+            Ok(SceneTexture::Checker(CheckerTexture::new(to_vector(&even), to_vector(&odd), description.scale)))
+        },
+        "noise" => {
+            let low = description.low.ok_or_else(|| missing("low"))?;
+            let high = description.high.ok_or_else(|| missing("high"))?;
+
+            if description.octaves == 0 {
+                return Err(SceneError::Invalid(element, "needs at least one octave".to_string()));
+            }
+
+            let noise = NoiseTexture::new(to_vector(&low), to_vector(&high), description.scale)
+                .with_octaves(description.octaves, description.persistence)
+                .with_seed(description.seed);
+
+            Ok(SceneTexture::Noise(noise))
+        },
+        "gradient" => {
+            let start = description.start.ok_or_else(|| missing("start"))?;
+            let end = description.end.ok_or_else(|| missing("end"))?;
+
+            let axis = match description.axis.to_lowercase().as_str() {
+                "u" => GradientAxis::U,
+                "v" => GradientAxis::V,
+                axis => { return Err(SceneError::Invalid(element, format!("has the unknown axis '{}'", axis))); }
+            };
+
+            Ok(SceneTexture::Gradient(GradientTexture::new(to_vector(&start), to_vector(&end), axis)))
+        },
+        kind => Err(SceneError::Invalid(element, format!("has an unknown type '{}'", kind)))
+    };
+}
+
+/**
+ * Creates the environment, image paths are relative to the scene directory.
+ *
+ * @param {EnvironmentDescription} description
+ * @param {&Path} directory
+ *
+ * @return {Result<Environment, SceneError>}
+ */
+fn build_environment(description: &EnvironmentDescription, directory: &Path) -> Result<Environment, SceneError> {
+    let element = "environment".to_string();
+
+    let missing = |field: &str| SceneError::Invalid(element.clone(), format!("of type '{}' needs a {}", description.kind, field));
+
+    return match description.kind.as_str() {
+        "constant" => {
+            let color = description.color.ok_or_else(|| missing("color"))?;
+
+            Ok(Environment::Constant(to_vector(&color)))
+        },
+        "gradient" => {
+            let zenith = description.zenith.ok_or_else(|| missing("zenith"))?;
+            let horizon = description.horizon.ok_or_else(|| missing("horizon"))?;
+            let ground = description.ground.ok_or_else(|| missing("ground"))?;
+
+            Ok(Environment::Gradient(Gradient::new(to_vector(&zenith), to_vector(&horizon), to_vector(&ground))))
+        },
+        "map" => {
+            let path = description.path.as_ref().ok_or_else(|| missing("path"))?;
+
+            if description.intensity < 0.0 {
+                return Err(SceneError::Invalid(element, "can't have a negative intensity".to_string()));
+            }
+
+            let image = match load_image(&directory.join(path), true) {
+                Ok(image) => image,
+                Err(error) => { return Err(SceneError::Image(error)); }
+            };
+
+            Ok(Environment::Map(EnvironmentMap::new(image, description.rotation.to_radians(), description.intensity)))
+        },
+        "sky" => {
+            let elevation = description.sun_elevation.ok_or_else(|| missing("sun_elevation"))?;
+
+            if !(0.0..=90.0).contains(&elevation) {
+                return Err(SceneError::Invalid(element, "needs a sun_elevation between 0 and 90".to_string()));
+            }
+
+            if !(2.0..=10.0).contains(&description.turbidity) {
+                return Err(SceneError::Invalid(element, "needs a turbidity between 2 and 10".to_string()));
+            }
+
+            if description.intensity < 0.0 {
+                return Err(SceneError::Invalid(element, "can't have a negative intensity".to_string()));
+            }
+
+            if description.sun_angular_diameter < 0.0 {
+                return Err(SceneError::Invalid(element, "can't have a negative sun_angular_diameter".to_string()));
+            }
+
+            let sky = PreethamSky::new(elevation.to_radians(), description.sun_azimuth.to_radians(), description.turbidity)
+                .with_intensity(description.intensity);
+
+            Ok(Environment::Sky(sky))
+        },
+        kind => Err(SceneError::Invalid(element, format!("has an unknown type '{}'", kind)))
+    };
+}
+
+/**
+ * Loads a TOML scene file, mesh and image paths are relative to it.
+ *
+ * @param {&Path} path
+ *
+ * @return {Result<Scene, SceneError>}
+ */
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => { return Err(SceneError::Io(path.to_path_buf())); }
+    };
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    return parse_scene(&source, directory);
+}
+
+/**
+ * Builds a scene from TOML source.
+ *
+ * @param {&str} source
+ * @param {&Path} directory Base directory of the relative mesh paths.
+ *
+ * @return {Result<Scene, SceneError>}
+ */
+pub fn parse_scene(source: &str, directory: &Path) -> Result<Scene, SceneError> {
+    let description: SceneDescription = match toml::from_str(source) {
+        Ok(description) => description,
+        Err(error) => { return Err(SceneError::Parse(error.to_string())); }
+    };
+
+    // --- Settings ---
+    let settings_description = &description.settings;
+    // The camera maps the first and the last pixel to the film edges, it needs two of them.
+    if settings_description.width < 2 || settings_description.height < 2 {
+        return Err(SceneError::Invalid("settings".to_string(), "needs a width and height of at least 2 pixels".to_string()));
+    }
+
+    if settings_description.samples == 0 {
+        return Err(SceneError::Invalid("settings".to_string(), "needs at least one sample".to_string()));
+    }
+
+    if settings_description.threads == 0 {
+        return Err(SceneError::Invalid("settings".to_string(), "needs at least one thread".to_string()));
+    }
+
+    let mut settings = RenderSettings::new(
+        settings_description.width,
+        settings_description.height,
+        settings_description.samples,
+        settings_description.max_bounces
+    );
+    settings.threads = settings_description.threads;
+
+    settings.integrator = match IntegratorKind::from_name(&settings_description.integrator) {
+        Some(integrator) => integrator,
+        None => {
+            return Err(SceneError::Invalid(
+                "settings".to_string(),
+                format!("has the unknown integrator '{}'", settings_description.integrator)
+            ));
+        }
+    };
+
+    if settings_description.ao_distance <= 0.0 || settings_description.ao_samples == 0 {
+        return Err(SceneError::Invalid("settings".to_string(), "needs a positive ao distance and samples".to_string()));
+    }
+
+    if let IntegratorKind::AmbientOcclusion(ref mut ambient_occlusion) = settings.integrator {
+        ambient_occlusion.distance = settings_description.ao_distance;
+        ambient_occlusion.samples = settings_description.ao_samples;
+    }
+
+    // --- Tone mapping ---
+    let tone_mapping_description = &description.tone_mapping;
+    let operator = match ToneMapOperator::from_name(&tone_mapping_description.operator) {
+        Some(operator) => operator,
+        None => {
+            return Err(SceneError::Invalid(
+                "tone mapping".to_string(),
+                format!("has the unknown operator '{}'", tone_mapping_description.operator)
+            ));
+        }
+    };
+
+    if tone_mapping_description.white_point <= 0.0 {
+        return Err(SceneError::Invalid("tone mapping".to_string(), "needs a positive white point".to_string()));
+    }
+
+    settings.tone_mapping = ToneMapping::new(operator, tone_mapping_description.exposure, tone_mapping_description.white_point);
+
+    // --- Camera ---
+    let camera_position = to_vector(&description.camera.position);
+    let camera_target = to_vector(&description.camera.target);
+
+    if vec_get_length(&vec_sub(&camera_target, &camera_position)) <= 0.0 {
+        return Err(SceneError::Invalid("camera".to_string(), "target is the camera position".to_string()));
+    }
+
+    let camera = Camera::new(camera_position, camera_target, description.camera.plane_distance);
+
+    let mut world = World::new();
+
+    // --- Environment ---
+    world.environment = match (&description.sky, &description.environment) {
+        (Some(_), Some(_)) => {
+            return Err(SceneError::Invalid("sky".to_string(), "can't be used with an environment".to_string()));
+        },
+        (Some(sky), None) => Environment::Constant(to_vector(&sky.color)),
+        (None, Some(environment)) => build_environment(environment, directory)?,
+        (None, None) => Environment::Constant(Vector3D::new_as_zero())
+    };
+
+    if let (Environment::Sky(sky), Some(environment)) = (&world.environment, &description.environment) {
+        if environment.sun {
+            world.lights.push(SceneLight::Directional(sky.get_sun_light(environment.sun_angular_diameter.to_radians())));
+        }
+    }
+
+    // --- Textures ---
+    let mut texture_names: HashMap<String, usize> = HashMap::new();
+    for (name, texture) in description.textures.iter() {
+        texture_names.insert(name.clone(), world.textures.len());
+        world.textures.push(build_texture(name, texture, directory)?);
+    }
+
+    // --- Materials ---
+    let mut names: HashMap<String, usize> = HashMap::new();
+    for (name, material) in description.materials.iter() {
+        let element = format!("material '{}'", name);
+
+        let base_color_texture = find_texture(&texture_names, &element, &material.base_color_texture)?;
+        let emission_texture = find_texture(&texture_names, &element, &material.emission_texture)?;
+        let roughness_texture = find_texture(&texture_names, &element, &material.roughness_texture)?;
+        let metalic_texture = find_texture(&texture_names, &element, &material.metalic_texture)?;
+        let normal_texture = find_texture(&texture_names, &element, &material.normal_texture)?;
+        let bump_texture = find_texture(&texture_names, &element, &material.bump_texture)?;
+
+        // Textured values keep the texture as it is by default.
+        let base_color = material.base_color.unwrap_or(if base_color_texture.is_some() { [1.0; 3] } else { default_base_color() });
+        let emission = material.emission.unwrap_or(if emission_texture.is_some() { [1.0; 3] } else { [0.0; 3] });
+        let roughness = material.roughness.unwrap_or(if roughness_texture.is_some() { 1.0 } else { default_roughness() });
+        let metalic = material.metalic.unwrap_or(if metalic_texture.is_some() { 1.0 } else { 0.0 });
+
+        if material.transmission < 0.0 || material.transmission > 1.0 {
+            return Err(SceneError::Invalid(element, "needs a transmission between 0 and 1".to_string()));
+        }
+
+        if material.ior <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a positive ior".to_string()));
+        }
+
+        names.insert(name.clone(), world.materials.len());
+
+        let mut new_material = Material::new(
+            to_vector(&base_color),
+            to_vector(&emission),
+            roughness,
+            metalic
+        );
+        new_material.transmission = material.transmission;
+        new_material.ior = material.ior;
+        new_material.absorption_color = to_vector(&material.absorption_color);
+
+        new_material.base_color_texture = base_color_texture;
+        new_material.emission_texture = emission_texture;
+        new_material.roughness_texture = roughness_texture;
+        new_material.metalic_texture = metalic_texture;
+
+        new_material.normal_texture = normal_texture;
+        new_material.normal_scale = material.normal_scale;
+        new_material.bump_texture = bump_texture;
+        new_material.bump_scale = material.bump_scale;
+
+        world.materials.push(new_material);
+    }
+
+    // --- Objects ---
+    for (index, sphere) in description.spheres.iter().enumerate() {
+        let element = format!("sphere {}", index + 1);
+        let material_index = find_material(&names, &element, &sphere.material)?;
+
+        if sphere.radius <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a positive radius".to_string()));
+        }
+
+        world.add_object(Primitive::Sphere(Sphere::new(to_vector(&sphere.center), sphere.radius, material_index)));
+    }
+
+    for (index, plane) in description.planes.iter().enumerate() {
+        let element = format!("plane {}", index + 1);
+        let material_index = find_material(&names, &element, &plane.material)?;
+
+        if vec_get_length(&to_vector(&plane.normal)) <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a non zero normal".to_string()));
+        }
+
+        world.add_object(Primitive::Plane(Plane::new(to_vector(&plane.normal), to_vector(&plane.point), material_index)));
+    }
+
+    for (index, mesh) in description.meshes.iter().enumerate() {
+        let element = format!("mesh {} ({})", index + 1, mesh.path);
+        let material_index = find_material(&names, &element, &mesh.material)?;
+
+        let mesh_path = directory.join(&mesh.path);
+        let loaded = match load_obj_file(&mesh_path, &mut world.materials, &mut world.textures, material_index) {
+            Ok(loaded) => loaded,
+            Err(error) => { return Err(SceneError::Mesh(error)); }
+        };
+
+        world.add_mesh(loaded);
+    }
+
+    // --- Lights ---
+    for (index, light) in description.lights.iter().enumerate() {
+        let element = format!("light {}", index + 1);
+
+        let mut point_light = match (light.intensity, light.power) {
+            (Some(intensity), None) => PointLight::new(to_vector(&light.position), to_vector(&light.color), intensity),
+            (None, Some(power)) => PointLight::new_with_power(to_vector(&light.position), to_vector(&light.color), power),
+            (None, None) => { return Err(SceneError::Invalid(element, "needs an intensity or a power".to_string())); },
+            (Some(_), Some(_)) => { return Err(SceneError::Invalid(element, "can't have both an intensity and a power".to_string())); }
+        };
+
+        if point_light.intensity < 0.0 {
+            return Err(SceneError::Invalid(element, "can't have a negative intensity".to_string()));
+        }
+
+        if let Some(range) = light.range {
+            if range <= 0.0 {
+                return Err(SceneError::Invalid(element, "needs a positive range".to_string()));
+            }
+
+            point_light = point_light.with_range(range);
+        }
+
+        world.lights.push(SceneLight::Point(point_light));
+    }
+
+    for (index, light) in description.directional_lights.iter().enumerate() {
+        let element = format!("directional light {}", index + 1);
+        let direction = to_vector(&light.direction);
+
+        if vec_get_length(&direction) <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a non zero direction".to_string()));
+        }
+
+        if light.irradiance < 0.0 {
+            return Err(SceneError::Invalid(element, "can't have a negative irradiance".to_string()));
+        }
+
+        if light.angular_diameter < 0.0 {
+            return Err(SceneError::Invalid(element, "can't have a negative angular_diameter".to_string()));
+        }
+
+        let directional_light = DirectionalLight::new(direction, to_vector(&light.color), light.irradiance)
+            .with_angular_diameter(light.angular_diameter.to_radians());
+
+        world.lights.push(SceneLight::Directional(directional_light));
+    }
+
+    for (index, light) in description.spot_lights.iter().enumerate() {
+        let element = format!("spot light {}", index + 1);
+        let direction = to_vector(&light.direction);
+
+        if vec_get_length(&direction) <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a non zero direction".to_string()));
+        }
+
+        if light.intensity < 0.0 {
+            return Err(SceneError::Invalid(element, "can't have a negative intensity".to_string()));
+        }
+
+        if light.inner_angle < 0.0 || light.inner_angle > light.outer_angle || light.outer_angle > 180.0 {
+            return Err(SceneError::Invalid(element, "needs 0 <= inner_angle <= outer_angle <= 180".to_string()));
+        }
+
+        if light.falloff <= 0.0 {
+            return Err(SceneError::Invalid(element, "needs a positive falloff".to_string()));
+        }
+
+        let mut spot_light = SpotLight::new(
+            to_vector(&light.position),
+            direction,
+            to_vector(&light.color),
+            light.intensity,
+            light.inner_angle.to_radians(),
+            light.outer_angle.to_radians()
+        ).with_falloff(light.falloff);
+
+        if let Some(range) = light.range {
+            if range <= 0.0 {
+                return Err(SceneError::Invalid(element, "needs a positive range".to_string()));
+            }
+
+            spot_light = spot_light.with_range(range);
+        }
+
+        world.lights.push(SceneLight::Spot(spot_light));
+    }
+
+    world.build();
+
+    return Ok(Scene {
+        world: world,
+        camera: camera,
+        settings: settings
+    });
+}
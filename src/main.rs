@@ -1,270 +1,314 @@
-use std::io::Result as IOResult;
-use std::time::Instant;
-use std::fs;
-
-extern crate bmp;
-use bmp::{Image, Pixel};
+use std::path::Path;
+use std::str::FromStr;
+use std::env;
+use std::process;
 
+#[cfg(feature = "window")]
 extern crate minifb;
+#[cfg(feature = "window")]
 use minifb::{
     Window, WindowOptions,
     Menu
 };
 
-extern crate rand;
-use rand::prelude::*;
-
 extern crate raytracer;
 use raytracer::{
-    trace, World, PointLight
-};
-use raytracer::loaders::{load_obj};
-use raytracer::geometry::{Line};
-
-use raytracer::vector3d::{
-    Vector3D,
-    vec_sum, vec_sum_components, vec_sub, vec_multiplication,
-    vec_normalize
-};
-use raytracer::color::{
-    Material, color_to_u32,
-    float_color_from_bytes, linear_color_to_srgb
+    World, RenderSettings
 };
+use raytracer::loaders::{load_scene};
+use raytracer::renderer::{Renderer, RenderStats, Film};
+#[cfg(feature = "window")]
+use raytracer::renderer::{Tile};
+
+use raytracer::output::{ImageFormat, OutputError, save_image_as};
+#[cfg(feature = "window")]
+use raytracer::color::{color_to_u32};
+use raytracer::color::{ToneMapping, ToneMapOperator};
+use raytracer::integrator::{IntegratorKind};
 use raytracer::camera::{Camera};
 
-use std::sync::{mpsc, Mutex, Arc};
+#[cfg(feature = "window")]
+use std::sync::mpsc::{self, TryRecvError};
+#[cfg(feature = "window")]
 use std::thread;
+#[cfg(feature = "window")]
 use std::time::Duration;
 
-fn save_buffer_to_bmp(buffer: &Vec<Vector3D>, img_width: u32, img_height: u32, file_name: &str) -> IOResult<()> {
-    let mut final_image = Image::new(img_width, img_height);
-
-    for j in 0..img_height {
-        for i in 0..img_width {
-            let color: Vector3D = buffer[(j * img_width + i) as usize];
-
-            final_image.set_pixel(
-                i, j,
-                Pixel::new(
-                    (color.x * 255.0) as u8,
-                    (color.y * 255.0) as u8,
-                    (color.z * 255.0) as u8
-                )
-            );
-        }
-    }
-
-    return final_image.save(file_name);
+const USAGE: &str = "Usage: raytracer [options] [scene.toml]
+
+Options:
+    --scene <path>      Scene file to render (default: scene.toml)
+    --output <path>     Image file to write, the format comes from the extension:
+                        .bmp, .png, .ppm, .pfm, .exr or .hdr (default: result.bmp)
+    --bit-depth <bits>  Bits per channel of PNG files, 8 or 16 (default: 8)
+    --width <pixels>    Image width, overrides the scene settings
+    --height <pixels>   Image height, overrides the scene settings
+    --samples <count>   Samples per pixel, overrides the scene settings
+    --bounces <count>   Max bounces per sample, overrides the scene settings
+    --threads <count>   Render threads, overrides the scene settings
+    --integrator <name> Rendering mode, overrides the scene settings: path, whitted,
+                        ao, normals, depth, material_id, barycentrics or bounces
+    --ao-distance <d>   Reach of the ambient occlusion, overrides the scene settings
+    --ao-samples <n>    Ambient occlusion lines per hit, overrides the scene settings
+    --tone-map <name>   Tone mapping operator for PNG, BMP, PPM and the window:
+                        clamp, reinhard, extended_reinhard, aces or hable
+    --exposure <ev>     Exposure value, every step doubles the brightness
+    --white-point <v>   Radiance mapped to white by extended_reinhard and hable
+    --headless          Render to the output file without opening a window,
+                        always on when built without the window feature
+    --help              Show this message";
+
+/**
+ * Command line options, the unset ones come from the scene file.
+ */
+struct Options {
+    scene_path: String,
+    output_path: String,
+    output_format: ImageFormat,
+    headless: bool,
+
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: Option<u32>,
+    max_bounces: Option<u32>,
+    threads: Option<usize>,
+    integrator: Option<IntegratorKind>,
+    ao_distance: Option<f32>,
+    ao_samples: Option<u32>,
+
+    tone_map: Option<ToneMapOperator>,
+    exposure: Option<f32>,
+    white_point: Option<f32>
 }
 
+/**
+ * Parses the value that follows a flag.
+ *
+ * @param {&str} flag
+ * @param {Option<String>} value
+ *
+ * @return {Result<T, String>}
+ */
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = match value {
+        Some(value) => value,
+        None => { return Err(format!("{} needs a value", flag)); }
+    };
+
+    return match value.parse::<T>() {
+        Ok(parsed) => Ok(parsed),
+        Err(_) => Err(format!("'{}' is not a valid value for {}", value, flag))
+    };
+}
 
-fn main() {
-    const WIDTH: usize = 512;
-    const HEIGHT: usize = 512;
-
-    // --- Window setup ---
-
-    // The integer buffer is used to display the image on screen
-    let mut buffer: Vec<u32> = vec![0; WIDTH * HEIGHT];
-
-    // The float buffer is needed to save the final image as BPM.
-    let mut float_buffer: Vec<Vector3D> = vec![Vector3D::new_as_zero(); WIDTH * HEIGHT];
-
-    let mut window = Window::new("Raycaster - The Begining", WIDTH, HEIGHT, WindowOptions::default()).unwrap();
-
-    // --- Raycast stuff ---
-
-    // Le camera
-    let camera: Camera = Camera::new(
-        Vector3D::new(0.0, 2.0, 6.0),
-        Vector3D::new(0.0, 0.0, 0.0),
-        2.0
-    );
-
-    // --- World objects ---
-    let mut world = World::new();
-
-    // Save result image Menu
-    let mut save_menu = Menu::new("File").unwrap();
-    save_menu.add_item("Save Image", 42).build();
-    window.add_menu(&save_menu);
-
-    world.materials = vec![
-        // Sky color and ambient light
-        Material::new_light(
-            // Vector3D::new(0.25, 0.8, 0.9)
-            // Vector3D::new(0.2, 0.2, 0.25)
-            Vector3D::new(0.1, 0.1, 0.15)
-            // Vector3D::new_as_zero()
-        ),
-        // Floor
-        Material::new(
-            float_color_from_bytes(88, 117, 167),
-            Vector3D::new_as_zero(),
-            0.8,
-            0.0
-        ),
-
-        //Spheres
-        Material::new(
-            float_color_from_bytes(250, 250, 250),
-            Vector3D::new_as_zero(),
-            0.05,
-            0.9
-        ),
-        Material::new(
-            float_color_from_bytes(212, 175, 55),
-            Vector3D::new_as_zero(),
-            0.05,
-            0.2
-        ),
-        // Lights
-        Material::new_light(Vector3D::new(5.0, 5.0, 5.0)),
-        Material::new_light(Vector3D::new(5.0, 0.25, 0.25))
-    ];
-
-    world.lights.push(PointLight::new( Vector3D::new(2.0, 5.0, 0.0), Vector3D::new(0.9, 0.9, 0.9), 5.0 ));
-    world.lights.push(PointLight::new( Vector3D::new(-2.0, 5.0, 0.0), Vector3D::new(0.9, 0.7, 0.9), 5.0 ));
-
-    let mesh = load_obj(fs::read_to_string("./cube.obj").unwrap(), 3);
-
-    world.objects = mesh.triangles;
-
-    // Stats
-    let mut finised = false;
-    let num_rays_arc = Arc::new(Mutex::new(0));
-
-    let time = Instant::now();
-
-    // --------------------------------------
-    // Multithread stuff
-    // --------------------------------------
-    let slice_count = 256;
-    let buffer_length = buffer.len();
-    // Get the slice size.
-    let thread_buffer_length = buffer_length / slice_count;
-
-    // (start, end)
-    let mut slices: Vec<(usize, usize)> = Vec::with_capacity(slice_count);
-
-    for index in 0..slice_count {
-        // Start and end indices
-        let rect_buffer_start = index * thread_buffer_length;
-        let rect_buffer_end = rect_buffer_start + thread_buffer_length;
+/**
+ * Reads the command line arguments.
+ *
+ * @param {Vec<String>} arguments Arguments without the program name.
+ *
+ * @return {Result<Option<Options>, String>} None if the help was requested.
+ */
+fn parse_arguments(arguments: Vec<String>) -> Result<Option<Options>, String> {
+    let mut bit_depth: u32 = 8;
+
+    let mut options = Options {
+        scene_path: "scene.toml".to_string(),
+        output_path: "result.bmp".to_string(),
+        output_format: ImageFormat::Bmp,
+        headless: false,
+
+        width: None,
+        height: None,
+        samples: None,
+        max_bounces: None,
+        threads: None,
+        integrator: None,
+        ao_distance: None,
+        ao_samples: None,
+
+        tone_map: None,
+        exposure: None,
+        white_point: None
+    };
+
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--help" | "-h" => { return Ok(None); },
+            "--headless" => { options.headless = true; },
+            "--scene" => { options.scene_path = parse_value(&argument, arguments.next())?; },
+            "--output" | "-o" => { options.output_path = parse_value(&argument, arguments.next())?; },
+            "--width" => { options.width = Some(parse_value(&argument, arguments.next())?); },
+            "--height" => { options.height = Some(parse_value(&argument, arguments.next())?); },
+            "--samples" => { options.samples = Some(parse_value(&argument, arguments.next())?); },
+            "--bounces" => { options.max_bounces = Some(parse_value(&argument, arguments.next())?); },
+            "--bit-depth" => { bit_depth = parse_value(&argument, arguments.next())?; },
+            "--threads" => { options.threads = Some(parse_value(&argument, arguments.next())?); },
+            "--integrator" => {
+                let name: String = parse_value(&argument, arguments.next())?;
+                match IntegratorKind::from_name(&name) {
+                    Some(integrator) => { options.integrator = Some(integrator); },
+                    None => { return Err(format!("unknown integrator {}", name)); }
+                }
+            },
+            "--ao-distance" => { options.ao_distance = Some(parse_value(&argument, arguments.next())?); },
+            "--ao-samples" => { options.ao_samples = Some(parse_value(&argument, arguments.next())?); },
+            "--tone-map" => {
+                let name: String = parse_value(&argument, arguments.next())?;
+                match ToneMapOperator::from_name(&name) {
+                    Some(operator) => { options.tone_map = Some(operator); },
+                    None => { return Err(format!("unknown tone mapping operator {}", name)); }
+                }
+            },
+            "--exposure" => { options.exposure = Some(parse_value(&argument, arguments.next())?); },
+            "--white-point" => { options.white_point = Some(parse_value(&argument, arguments.next())?); },
+            _ => {
+                if argument.starts_with("-") {
+                    return Err(format!("unknown option {}", argument));
+                }
 
-        slices.push((rect_buffer_start, rect_buffer_end));
+                options.scene_path = argument;
+            }
+        }
     }
 
-    slices.shuffle(&mut rand::thread_rng());
-
-    // (index, colour)
-    let (sender, receiver) = mpsc::channel::<Vec<(usize, Vector3D)>>();
-
-    // Mutex and stuff conversion
-    let slices_atomic_arc = Arc::new(Mutex::new(slices));
-    let world_arc = Arc::new(world);
-
-    // Create four threads
-    let max_thread_count = 4;
-
-    let thread_counter_arc = Arc::new(Mutex::from(max_thread_count));
-
-    for thread_index in 0..max_thread_count {
-        let one_sender = sender.clone();
-        let local_arc = Arc::clone(&slices_atomic_arc);
-
-        let local_world_arc = Arc::clone(&world_arc);
-        let local_thread_counter_arc = Arc::clone(&thread_counter_arc);
-
-        let local_num_rays_arc = Arc::clone(&num_rays_arc);
-
-        thread::spawn(move || {
-            let mut rng = rand::thread_rng();
-
-            loop {
-                let mut local_slices = local_arc.lock().unwrap();
+    // The camera maps the first and the last pixel to the film edges, it needs two of them.
+    if options.width.unwrap_or(2) < 2 || options.height.unwrap_or(2) < 2 {
+        return Err("the image needs at least 2 pixels of width and height".to_string());
+    }
 
-                if local_slices.len() == 0 {
-                    break;
-                }
+    if options.samples == Some(0) || options.threads == Some(0) {
+        return Err("the samples and threads can't be zero".to_string());
+    }
 
-                let slice = local_slices.pop().unwrap();
-                drop(local_slices);
+    options.output_format = match ImageFormat::from_path(Path::new(&options.output_path)) {
+        Some(format) => format,
+        None => { return Err(format!("'{}' has an unknown image format", options.output_path)); }
+    };
 
-                let start = slice.0;
-                let end = slice.1;
+    match (bit_depth, options.output_format) {
+        (8, _) => {},
+        (16, ImageFormat::Png) => { options.output_format = ImageFormat::Png16; },
+        (16, _) => { return Err("16 bit output is only available for PNG files".to_string()); },
+        _ => { return Err(format!("the bit depth can't be {}", bit_depth)); }
+    }
 
-                let mut pixels: Vec<(usize, Vector3D)> = Vec::with_capacity(thread_buffer_length);
-                for buffer_index in start..end {
-                    let i = buffer_index % WIDTH;
-                    let j = buffer_index / WIDTH;
+    if matches!(options.ao_distance, Some(distance) if distance <= 0.0) || options.ao_samples == Some(0) {
+        return Err("the ao distance and samples must be positive".to_string());
+    }
 
-                    let film_plane_point = camera.screen_point_to_projection_plane(i, WIDTH, j, HEIGHT);
+    if matches!(options.white_point, Some(white_point) if white_point <= 0.0) {
+        return Err("the white point must be positive".to_string());
+    }
 
-                    // Sample rays
-                    let samples: u32 = 8;
-                    let single_color_contribution: f32 = 1.0 / samples as f32;
+    return Ok(Some(options));
+}
 
-                    // Initialize the pixel color
-                    let mut pixel_color: Vector3D = Vector3D::new_as_zero();
+fn print_stats(stats: &RenderStats) {
+    if stats.cancelled {
+        println!("Render cancelled.");
+    }
 
+    println!("Number of rays: {}", stats.rays);
+    println!("Time elapsed: {}ms", stats.elapsed.as_millis());
+}
 
-                    let mut num_no_bounce: u32 = 0;
-                    let max_num_no_bounce: u32 = if samples / 4 >= 2 { samples / 4 } else { 2 };
+/**
+ * Saves the film, tone mapped unless the format keeps the high dynamic range.
+ *
+ * @param {&Film} film
+ * @param {ToneMapping} tone_mapping
+ * @param {ImageFormat} output_format
+ * @param {&str} output_path
+ *
+ * @return {Result<(), OutputError>}
+ */
+fn save_film(film: &Film, tone_mapping: &ToneMapping, output_format: ImageFormat, output_path: &str) -> Result<(), OutputError> {
+    if output_format.is_high_dynamic_range() {
+        return save_image_as(film, output_format, Path::new(output_path));
+    }
 
-                    let mut s: u32 = 0;
-                    while s < samples {
-                        // Ray random vibration
-                        let small_pixel_offset_x = (2.0 * rng.gen::<f32>() - 1.0) * (0.5 / WIDTH as f32);
-                        let small_pixel_offset_y = (2.0 * rng.gen::<f32>() - 1.0) * (0.5 / HEIGHT as f32);
-                        let sample_film_plane_point = vec_sum_components(
-                            &film_plane_point,
-                            small_pixel_offset_x, small_pixel_offset_y, 0.0
-                        );
+    return save_image_as(&film.get_tone_mapped(tone_mapping), output_format, Path::new(output_path));
+}
 
-                        // Ray
-                        let direction = vec_normalize(&vec_sub(&sample_film_plane_point, &camera.position));
-                        let line: Line = Line::new(camera.position, direction);
+/**
+ * Renders in the current thread and saves the image.
+ *
+ * @param {Renderer} renderer
+ * @param {&str} output_path
+ * @param {ImageFormat} output_format
+ *
+ * @return {Result<(), String>}
+ */
+fn render_headless(renderer: Renderer, output_path: &str, output_format: ImageFormat) -> Result<(), String> {
+    let tone_mapping = renderer.get_settings().tone_mapping;
+    let mut last_percent = 0;
+
+    let (film, stats) = renderer.render(|_, progress| {
+        let percent = 100 * progress.finished_tiles / progress.total_tiles;
+
+        if percent >= last_percent + 10 {
+            last_percent = percent - percent % 10;
+            println!("{}%", last_percent);
+        }
+    });
 
-                        let (trace_color, bounces) = trace(&local_world_arc, &line, 16);
+    print_stats(&stats);
 
-                        let mut num_rays = local_num_rays_arc.lock().unwrap();
-                        *num_rays += bounces as u64;
-                        drop(num_rays);
+    println!("Saving result image...");
+    match save_film(&film, &tone_mapping, output_format, output_path) {
+        Ok(_) => { println!("Saved."); },
+        Err(error) => { return Err(error.to_string()); }
+    }
 
-                        if bounces == 0 {
-                            num_no_bounce += 1;
-                        } else {
-                            num_no_bounce = 0;
-                        }
+    return Ok(());
+}
 
-                        // Add the result color to the pixel's final color.
-                        pixel_color = vec_sum(&pixel_color, &vec_multiplication(&trace_color, single_color_contribution));
+/**
+ * Shows the render progress in a window, the image is saved from the "File" menu.
+ * Closing the window cancels the render.
+ *
+ * @param {Renderer} renderer
+ * @param {&str} output_path
+ * @param {ImageFormat} output_format
+ *
+ * @return {Result<(), String>}
+ */
+#[cfg(feature = "window")]
+fn render_window(renderer: Renderer, output_path: &str, output_format: ImageFormat) -> Result<(), String> {
+    let settings = renderer.get_settings();
+    let width: usize = settings.width;
+    let height: usize = settings.height;
+    let tone_mapping = settings.tone_mapping;
 
-                        s += 1;
+    // --- Window setup ---
 
-                        // Break in case we have no bounces in this pixel.
-                        if num_no_bounce >= max_num_no_bounce {
-                            pixel_color = trace_color;
-                            break;
-                        }
-                    }
+    // The integer buffer is used to display the image on screen
+    let mut buffer: Vec<u32> = vec![0; width * height];
 
-                    let gamma_corrected_color = linear_color_to_srgb(&pixel_color);
+    // The film is needed to save the final image.
+    let mut film = Film::new(width, height);
 
-                    pixels.push((buffer_index, gamma_corrected_color));
-                }
+    let mut window = match Window::new("Raycaster - The Begining", width, height, WindowOptions::default()) {
+        Ok(window) => window,
+        Err(error) => { return Err(format!("the window can't be opened ({}), try --headless", error)); }
+    };
 
-                one_sender.send(pixels).unwrap();
-            }
+    // Save result image Menu
+    let mut save_menu = Menu::new("File").unwrap();
+    save_menu.add_item("Save Image", 42).build();
+    window.add_menu(&save_menu);
 
-            println!("Thread < {} > finised", thread_index);
-            let mut counter = local_thread_counter_arc.lock().unwrap();
-            *counter = *counter - 1;
+    // --- Render thread ---
+    let cancel_handle = renderer.get_cancel_handle();
+    let (sender, receiver) = mpsc::channel::<Tile>();
 
-            drop(counter);
+    let mut render_thread = Some(thread::spawn(move || {
+        return renderer.render(|tile, _| {
+            // The window may be closed already.
+            let _ = sender.send(tile.clone());
         });
-    }
+    }));
 
     // --------------------------------------
     // Display the window
@@ -273,40 +317,36 @@ fn main() {
 
         window.is_menu_pressed().map(|_| {
             println!("Saving result image...");
-            match save_buffer_to_bmp(&float_buffer, WIDTH as u32, HEIGHT as u32, "result.bmp") {
+            match save_film(&film, &tone_mapping, output_format, output_path) {
                 Ok(_) => { println!("Saved."); },
-                Err(message) => { println!("[ERROR]: {}", message); }
+                Err(error) => { println!("[ERROR]: {}", error); }
             }
         });
 
         let mut should_redraw = false;
-        if !finised {
-            let mut message_count = 0;
-
-            // Retrieve the thread results.
-            // Iterate over the full message list generated since the last iteration.
-            for message in receiver.try_iter() {
-                for value in message.iter() {
-                    buffer[value.0] = color_to_u32(&value.1);
-                    float_buffer[value.0] = value.1;
-                }
-
-                message_count += 1;
-            }
-
-            should_redraw = message_count != 0;
-
-            let active_threads = thread_counter_arc.lock().unwrap();
-            if *active_threads <= 0 && message_count == 0 {
-                finised = true;
-
-                let num_rays = num_rays_arc.lock().unwrap();
-                println!("Number of rays: {}", num_rays);
-                drop(num_rays);
+        if render_thread.is_some() {
+            // Retrieve the finished tiles since the last iteration.
+            loop {
+                match receiver.try_recv() {
+                    Ok(tile) => {
+                        film.add_tile(&tile);
+
+                        for j in tile.y..tile.y + tile.height {
+                            for i in tile.x..tile.x + tile.width {
+                                buffer[j * width + i] = color_to_u32(&tone_mapping.to_srgb(&film.get_pixel(i, j)));
+                            }
+                        }
 
-                println!("Time elapsed: {}ms", time.elapsed().as_millis());
+                        should_redraw = true;
+                    },
+                    Err(TryRecvError::Empty) => { break; },
+                    Err(TryRecvError::Disconnected) => {
+                        let (_, stats) = render_thread.take().unwrap().join().unwrap();
+                        print_stats(&stats);
+                        break;
+                    }
+                }
             }
-            drop(active_threads);
         }
 
         if should_redraw {
@@ -316,4 +356,74 @@ fn main() {
         }
         thread::sleep(Duration::from_millis(10));
     }
+
+    if let Some(render_thread) = render_thread {
+        cancel_handle.cancel();
+        let _ = render_thread.join();
+    }
+
+    return Ok(());
+}
+
+fn main() {
+    let options = match parse_arguments(env::args().skip(1).collect()) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(message) => {
+            eprintln!("[ERROR]: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    // --- Scene ---
+    let scene = match load_scene(Path::new(&options.scene_path)) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("[ERROR]: {}", error);
+            process::exit(1);
+        }
+    };
+
+    let camera: Camera = scene.camera;
+    let world: World = scene.world;
+    let mut settings: RenderSettings = scene.settings;
+
+    // The command line wins over the scene file.
+    settings.width = options.width.unwrap_or(settings.width);
+    settings.height = options.height.unwrap_or(settings.height);
+    settings.samples = options.samples.unwrap_or(settings.samples);
+    settings.max_bounces = options.max_bounces.unwrap_or(settings.max_bounces);
+    settings.threads = options.threads.unwrap_or(settings.threads);
+    settings.integrator = options.integrator.unwrap_or(settings.integrator);
+
+    if let IntegratorKind::AmbientOcclusion(ref mut ambient_occlusion) = settings.integrator {
+        ambient_occlusion.distance = options.ao_distance.unwrap_or(ambient_occlusion.distance);
+        ambient_occlusion.samples = options.ao_samples.unwrap_or(ambient_occlusion.samples);
+    }
+
+    settings.tone_mapping.operator = options.tone_map.unwrap_or(settings.tone_mapping.operator);
+    settings.tone_mapping.exposure = options.exposure.unwrap_or(settings.tone_mapping.exposure);
+    settings.tone_mapping.white_point = options.white_point.unwrap_or(settings.tone_mapping.white_point);
+
+    let renderer = Renderer::new(world, camera, settings);
+
+    #[cfg(feature = "window")]
+    let result = if options.headless {
+        render_headless(renderer, &options.output_path, options.output_format)
+    } else {
+        render_window(renderer, &options.output_path, options.output_format)
+    };
+
+    // Without a window there is nothing else to do.
+    #[cfg(not(feature = "window"))]
+    let result = render_headless(renderer, &options.output_path, options.output_format);
+
+    if let Err(message) = result {
+        eprintln!("[ERROR]: {}", message);
+        process::exit(1);
+    }
 }
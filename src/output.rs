@@ -0,0 +1,399 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use bmp::{Image, Pixel};
+use exr::prelude::{Image as ExrImage, SpecificChannels, WritableImage, Vec2};
+
+use crate::renderer::{Film};
+use crate::color::{linear_color_to_srgb};
+use crate::vector3d::{Vector3D};
+
+/**
+ * Image file formats the film can be saved as.
+ * The 8 and 16 bit formats are sRGB encoded, the float ones keep the linear radiance.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Bmp,
+    // 8 bits per channel
+    Png,
+    // 16 bits per channel
+    Png16,
+    // Binary PPM (P6), 8 bits per channel
+    Ppm,
+    // Portable float map, 32 bit floats per channel
+    Pfm,
+    // OpenEXR, 32 bit floats per channel
+    Exr,
+    // Radiance RGBE
+    Hdr
+}
+
+impl ImageFormat {
+    /**
+     * Chooses the format from the file extension, PNG files are 8 bit.
+     *
+     * @param {&Path} path
+     *
+     * @return {Option<ImageFormat>}
+     */
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        return match extension.as_str() {
+            "bmp" => Some(ImageFormat::Bmp),
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None
+        };
+    }
+    /**
+     * Float formats keep the radiance as it is, the rest need tone mapping first.
+     *
+     * @return {bool}
+     */
+    pub fn is_high_dynamic_range(&self) -> bool {
+        return matches!(self, ImageFormat::Pfm | ImageFormat::Exr | ImageFormat::Hdr);
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    // The extension doesn't match any format.
+    UnknownFormat(PathBuf),
+    // The file can't be written: (path, reason).
+    Io(PathBuf, String)
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            OutputError::UnknownFormat(path) => {
+                write!(f, "'{}' has an unknown image format, use .bmp, .png, .ppm, .pfm, .exr or .hdr", path.display())
+            },
+            OutputError::Io(path, reason) => write!(f, "'{}' can't be written: {}", path.display(), reason)
+        };
+    }
+}
+
+impl Error for OutputError {}
+
+// ================================================
+// Quantization
+// ================================================
+
+/**
+ * Converts a channel to 8 bits, rounding to the nearest value.
+ * Values out of [0, 1] are clamped and NaN is black.
+ *
+ * @param {f32} value
+ *
+ * @return {u8}
+ */
+pub fn channel_to_u8(value: f32) -> u8 {
+    if value.is_nan() {
+        return 0;
+    }
+
+    return (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+}
+
+/**
+ * Converts a channel to 16 bits, rounding to the nearest value.
+ * Values out of [0, 1] are clamped and NaN is black.
+ *
+ * @param {f32} value
+ *
+ * @return {u16}
+ */
+pub fn channel_to_u16(value: f32) -> u16 {
+    if value.is_nan() {
+        return 0;
+    }
+
+    return (value.clamp(0.0, 1.0) * 65535.0).round() as u16;
+}
+
+/**
+ * Encodes a linear color as 8 bit sRGB.
+ *
+ * @param {Vector3D} color
+ *
+ * @return {[u8; 3]}
+ */
+pub fn color_to_bytes(color: &Vector3D) -> [u8; 3] {
+    let encoded = linear_color_to_srgb(color);
+
+    return [channel_to_u8(encoded.x), channel_to_u8(encoded.y), channel_to_u8(encoded.z)];
+}
+
+/**
+ * Encodes a linear color as 16 bit sRGB.
+ *
+ * @param {Vector3D} color
+ *
+ * @return {[u16; 3]}
+ */
+pub fn color_to_u16s(color: &Vector3D) -> [u16; 3] {
+    let encoded = linear_color_to_srgb(color);
+
+    return [channel_to_u16(encoded.x), channel_to_u16(encoded.y), channel_to_u16(encoded.z)];
+}
+
+/**
+ * Encodes a linear color in the shared exponent format of Radiance files.
+ * Negative values and NaN are black.
+ *
+ * @param {Vector3D} color
+ *
+ * @return {[u8; 4]} Red, green and blue mantissas and the exponent.
+ */
+pub fn color_to_rgbe(color: &Vector3D) -> [u8; 4] {
+    let channels: Vec<f32> = [color.x, color.y, color.z].iter()
+        .map(|value| if value.is_nan() { 0.0 } else { value.max(0.0) })
+        .collect();
+
+    let max = channels[0].max(channels[1]).max(channels[2]);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // max = mantissa * 2^exponent, with the mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2.0f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+
+    if exponent > 127 {
+        return [255, 255, 255, 255];
+    }
+
+    let scale = 256.0 / 2.0f32.powi(exponent);
+
+    return [
+        (channels[0] * scale).min(255.0) as u8,
+        (channels[1] * scale).min(255.0) as u8,
+        (channels[2] * scale).min(255.0) as u8,
+        (exponent + 128) as u8
+    ];
+}
+
+// ================================================
+// Writers
+// ================================================
+
+fn to_io_error<E: fmt::Display>(error: E) -> io::Error {
+    return io::Error::other(error.to_string());
+}
+
+/**
+ * Writes a 24 bit sRGB BMP.
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_bmp<W: Write>(film: &Film, writer: &mut W) -> io::Result<()> {
+    let mut image = Image::new(film.width as u32, film.height as u32);
+
+    for j in 0..film.height {
+        for i in 0..film.width {
+            let [r, g, b] = color_to_bytes(&film.get_pixel(i, j));
+
+            image.set_pixel(i as u32, j as u32, Pixel::new(r, g, b));
+        }
+    }
+
+    return image.to_writer(writer);
+}
+
+/**
+ * Writes an sRGB PNG.
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ * @param {bool} sixteen_bit Use 16 bits per channel instead of 8.
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_png<W: Write>(film: &Film, writer: &mut W, sixteen_bit: bool) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, film.width as u32, film.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+
+    let mut data: Vec<u8>;
+    if sixteen_bit {
+        encoder.set_depth(png::BitDepth::Sixteen);
+
+        // PNG samples are big endian.
+        data = Vec::with_capacity(film.pixels.len() * 6);
+        for color in film.pixels.iter() {
+            for value in color_to_u16s(color).iter() {
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    } else {
+        encoder.set_depth(png::BitDepth::Eight);
+
+        data = Vec::with_capacity(film.pixels.len() * 3);
+        for color in film.pixels.iter() {
+            data.extend_from_slice(&color_to_bytes(color));
+        }
+    }
+
+    let mut png_writer = encoder.write_header().map_err(to_io_error)?;
+    png_writer.write_image_data(&data).map_err(to_io_error)?;
+
+    return png_writer.finish().map_err(to_io_error);
+}
+
+/**
+ * Writes an sRGB binary PPM (P6).
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_ppm<W: Write>(film: &Film, writer: &mut W) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", film.width, film.height)?;
+
+    let mut data: Vec<u8> = Vec::with_capacity(film.pixels.len() * 3);
+    for color in film.pixels.iter() {
+        data.extend_from_slice(&color_to_bytes(color));
+    }
+
+    return writer.write_all(&data);
+}
+
+/**
+ * Writes a color portable float map, the values are stored without clamping.
+ * A negative scale means little endian, and the rows go from the bottom to the top.
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_pfm<W: Write>(film: &Film, writer: &mut W) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", film.width, film.height)?;
+
+    let mut data: Vec<u8> = Vec::with_capacity(film.pixels.len() * 12);
+    for j in (0..film.height).rev() {
+        for i in 0..film.width {
+            let color = film.get_pixel(i, j);
+
+            for value in [color.x, color.y, color.z].iter() {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    return writer.write_all(&data);
+}
+
+/**
+ * Writes an OpenEXR file with 32 bit float channels.
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_exr<W: Write>(film: &Film, writer: &mut W) -> io::Result<()> {
+    let channels = SpecificChannels::rgb(|Vec2(x, y)| {
+        let color = film.get_pixel(x, y);
+
+        return (color.x, color.y, color.z);
+    });
+
+    // The encoder needs to seek, build the file in memory.
+    let mut data = Cursor::new(Vec::new());
+    ExrImage::from_channels((film.width, film.height), channels)
+        .write()
+        .to_buffered(&mut data)
+        .map_err(to_io_error)?;
+
+    return writer.write_all(data.get_ref());
+}
+
+/**
+ * Writes a Radiance HDR file with uncompressed RGBE scanlines.
+ *
+ * @param {&Film} film
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_hdr<W: Write>(film: &Film, writer: &mut W) -> io::Result<()> {
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", film.height, film.width)?;
+
+    let mut data: Vec<u8> = Vec::with_capacity(film.pixels.len() * 4);
+    for color in film.pixels.iter() {
+        data.extend_from_slice(&color_to_rgbe(color));
+    }
+
+    return writer.write_all(&data);
+}
+
+/**
+ * Writes the film in the given format.
+ *
+ * @param {&Film} film
+ * @param {ImageFormat} format
+ * @param {W} writer
+ *
+ * @return {io::Result<()>}
+ */
+pub fn write_image<W: Write>(film: &Film, format: ImageFormat, writer: &mut W) -> io::Result<()> {
+    return match format {
+        ImageFormat::Bmp => write_bmp(film, writer),
+        ImageFormat::Png => write_png(film, writer, false),
+        ImageFormat::Png16 => write_png(film, writer, true),
+        ImageFormat::Ppm => write_ppm(film, writer),
+        ImageFormat::Pfm => write_pfm(film, writer),
+        ImageFormat::Exr => write_exr(film, writer),
+        ImageFormat::Hdr => write_hdr(film, writer)
+    };
+}
+
+/**
+ * Saves the film to a file in the given format.
+ *
+ * @param {&Film} film
+ * @param {ImageFormat} format
+ * @param {&Path} path
+ *
+ * @return {Result<(), OutputError>}
+ */
+pub fn save_image_as(film: &Film, format: ImageFormat, path: &Path) -> Result<(), OutputError> {
+    let to_output_error = |error: io::Error| OutputError::Io(path.to_path_buf(), error.to_string());
+
+    let file = File::create(path).map_err(to_output_error)?;
+    let mut writer = BufWriter::new(file);
+
+    write_image(film, format, &mut writer).map_err(to_output_error)?;
+
+    return writer.flush().map_err(to_output_error);
+}
+
+/**
+ * Saves the film to a file, the format comes from the extension.
+ *
+ * @param {&Film} film
+ * @param {&Path} path
+ *
+ * @return {Result<(), OutputError>}
+ */
+pub fn save_image(film: &Film, path: &Path) -> Result<(), OutputError> {
+    return match ImageFormat::from_path(path) {
+        Some(format) => save_image_as(film, format, path),
+        None => Err(OutputError::UnknownFormat(path.to_path_buf()))
+    };
+}
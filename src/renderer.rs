@@ -0,0 +1,398 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+
+use crate::{World};
+use crate::camera::{Camera};
+use crate::color::{ToneMapping};
+use crate::integrator::{Integrator, IntegratorKind, PathTracer};
+use crate::geometry::{Line};
+use crate::vector3d::{
+    Vector3D,
+    vec_sum, vec_sum_components, vec_sub, vec_multiplication,
+    vec_normalize
+};
+
+/**
+ * Image and sampling parameters of a render.
+ */
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+
+    pub samples: u32,
+    pub max_bounces: u32,
+
+    // Light transport algorithm, the path tracer unless a debug view is needed.
+    pub integrator: IntegratorKind,
+
+    pub threads: usize,
+    pub tile_size: usize,
+
+    // Used when the film is shown or saved in a low dynamic range format.
+    pub tone_mapping: ToneMapping
+}
+
+impl RenderSettings {
+    pub fn new(width: usize, height: usize, samples: u32, max_bounces: u32) -> RenderSettings {
+        return RenderSettings {
+            width: width,
+            height: height,
+
+            samples: samples,
+            max_bounces: max_bounces,
+
+            integrator: IntegratorKind::Path(PathTracer),
+
+            threads: 4,
+            tile_size: 32,
+
+            tone_mapping: ToneMapping::new_clamp()
+        };
+    }
+}
+
+// ================================================
+// Film implementation
+// ================================================
+
+/**
+ * Float framebuffer of linear, unclamped radiance.
+ * Pixels are stored by rows from the top left corner.
+ */
+#[derive(Clone)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+
+    pub pixels: Vec<Vector3D>
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        return Film {
+            width: width,
+            height: height,
+
+            pixels: vec![Vector3D::new_as_zero(); width * height]
+        };
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Vector3D {
+        return self.pixels[y * self.width + x];
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Vector3D) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /**
+     * Creates a copy of the film with the tone mapping applied, ready for the sRGB encoding.
+     *
+     * @param {ToneMapping} tone_mapping
+     *
+     * @return {Film}
+     */
+    pub fn get_tone_mapped(&self, tone_mapping: &ToneMapping) -> Film {
+        return Film {
+            width: self.width,
+            height: self.height,
+
+            pixels: self.pixels.iter().map(|color| tone_mapping.apply(color)).collect()
+        };
+    }
+
+    /**
+     * Copies the tile pixels in their place.
+     *
+     * @param {Tile} tile
+     */
+    pub fn add_tile(&mut self, tile: &Tile) {
+        for j in 0..tile.height {
+            for i in 0..tile.width {
+                self.set_pixel(tile.x + i, tile.y + j, tile.pixels[j * tile.width + i]);
+            }
+        }
+    }
+}
+
+/**
+ * Rectangle of the image rendered as a unit of work.
+ */
+#[derive(Clone)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+
+    // Rows of the tile, empty until it's rendered.
+    pub pixels: Vec<Vector3D>
+}
+
+impl Tile {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Tile {
+        return Tile {
+            x: x, y: y,
+            width: width, height: height,
+
+            pixels: Vec::new()
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderProgress {
+    pub finished_tiles: usize,
+    pub total_tiles: usize
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderStats {
+    pub rays: u64,
+    pub elapsed: Duration,
+    pub cancelled: bool
+}
+
+// ================================================
+// Cancellation
+// ================================================
+
+/**
+ * Stops a render from any thread, the tiles in progress are finished.
+ */
+#[derive(Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancelHandle {
+    pub fn new() -> CancelHandle {
+        return CancelHandle {
+            cancelled: Arc::new(AtomicBool::new(false))
+        };
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+
+// ================================================
+// Renderer implementation
+// ================================================
+
+pub struct Renderer {
+    world: Arc<World>,
+    camera: Camera,
+    settings: RenderSettings,
+
+    cancel_handle: CancelHandle
+}
+
+impl Renderer {
+    pub fn new(world: World, camera: Camera, settings: RenderSettings) -> Renderer {
+        return Renderer {
+            world: Arc::new(world),
+            camera: camera,
+            settings: settings,
+
+            cancel_handle: CancelHandle::new()
+        };
+    }
+
+    pub fn get_settings(&self) -> RenderSettings {
+        return self.settings;
+    }
+
+    pub fn get_cancel_handle(&self) -> CancelHandle {
+        return self.cancel_handle.clone();
+    }
+
+    /**
+     * Splits the image in tiles, in random order so the progress is spread over the image.
+     *
+     * @return {Vec<Tile>}
+     */
+    fn create_tiles(&self) -> Vec<Tile> {
+        let tile_size = self.settings.tile_size.max(1);
+        let mut tiles: Vec<Tile> = Vec::new();
+
+        let mut y = 0;
+        while y < self.settings.height {
+            let mut x = 0;
+            while x < self.settings.width {
+                let width = tile_size.min(self.settings.width - x);
+                let height = tile_size.min(self.settings.height - y);
+
+                tiles.push(Tile::new(x, y, width, height));
+                x += tile_size;
+            }
+            y += tile_size;
+        }
+
+        tiles.shuffle(&mut rand::thread_rng());
+
+        return tiles;
+    }
+
+    /**
+     * Renders the image using the configured number of threads.
+     * The callback runs in the calling thread every time a tile is finished.
+     *
+     * @param {FnMut(&Tile, &RenderProgress)} on_tile
+     *
+     * @return {(Film, RenderStats)} The film is incomplete if the render was cancelled.
+     */
+    pub fn render<F: FnMut(&Tile, &RenderProgress)>(&self, mut on_tile: F) -> (Film, RenderStats) {
+        let time = Instant::now();
+
+        let tiles = self.create_tiles();
+        let total_tiles = tiles.len();
+
+        let tiles_arc = Arc::new(Mutex::new(tiles));
+
+        // (tile, rays)
+        let (sender, receiver) = mpsc::channel::<(Tile, u64)>();
+
+        let mut handles = Vec::with_capacity(self.settings.threads);
+        for _ in 0..self.settings.threads.max(1) {
+            let one_sender = sender.clone();
+            let local_tiles_arc = Arc::clone(&tiles_arc);
+
+            let local_world_arc = Arc::clone(&self.world);
+            let camera = self.camera;
+            let settings = self.settings;
+            let cancel_handle = self.cancel_handle.clone();
+
+            handles.push(thread::spawn(move || {
+                let mut rng = rand::thread_rng();
+
+                loop {
+                    if cancel_handle.is_cancelled() {
+                        break;
+                    }
+
+                    let mut local_tiles = local_tiles_arc.lock().unwrap();
+                    let mut tile = match local_tiles.pop() {
+                        Some(tile) => tile,
+                        None => { break; }
+                    };
+                    drop(local_tiles);
+
+                    let mut rays: u64 = 0;
+
+                    tile.pixels.reserve(tile.width * tile.height);
+                    for j in tile.y..tile.y + tile.height {
+                        for i in tile.x..tile.x + tile.width {
+                            let (color, pixel_rays) = render_pixel(&local_world_arc, &camera, &settings, i, j, &mut rng);
+
+                            tile.pixels.push(color);
+                            rays += pixel_rays;
+                        }
+                    }
+
+                    if one_sender.send((tile, rays)).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        // Only the threads keep a sender, the channel closes when all of them finish.
+        drop(sender);
+
+        let mut film = Film::new(self.settings.width, self.settings.height);
+        let mut progress = RenderProgress { finished_tiles: 0, total_tiles: total_tiles };
+        let mut rays: u64 = 0;
+
+        for (tile, tile_rays) in receiver.iter() {
+            film.add_tile(&tile);
+
+            progress.finished_tiles += 1;
+            rays += tile_rays;
+
+            on_tile(&tile, &progress);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = RenderStats {
+            rays: rays,
+            elapsed: time.elapsed(),
+            cancelled: progress.finished_tiles < total_tiles
+        };
+
+        return (film, stats);
+    }
+}
+
+/**
+ * Samples a single pixel.
+ *
+ * @return {(Vector3D, u64)} The linear color and the number of rays traced.
+ */
+fn render_pixel(world: &World, camera: &Camera, settings: &RenderSettings, i: usize, j: usize, rng: &mut ThreadRng) -> (Vector3D, u64) {
+    let width = settings.width;
+    let height = settings.height;
+
+    let film_plane_point = camera.screen_point_to_projection_plane(i, width, j, height);
+
+    // Sample rays
+    let samples: u32 = settings.samples;
+    let single_color_contribution: f32 = 1.0 / samples as f32;
+
+    // Initialize the pixel color
+    let mut pixel_color: Vector3D = Vector3D::new_as_zero();
+    let mut rays: u64 = 0;
+
+    let mut num_no_bounce: u32 = 0;
+    let max_num_no_bounce: u32 = if samples / 4 >= 2 { samples / 4 } else { 2 };
+
+    let mut s: u32 = 0;
+    while s < samples {
+        // Ray random vibration
+        let small_pixel_offset_x = (2.0 * rng.gen::<f32>() - 1.0) * (0.5 / width as f32);
+        let small_pixel_offset_y = (2.0 * rng.gen::<f32>() - 1.0) * (0.5 / height as f32);
+        let sample_film_plane_point = vec_sum_components(
+            &film_plane_point,
+            small_pixel_offset_x, small_pixel_offset_y, 0.0
+        );
+
+        // Ray
+        let direction = vec_normalize(&vec_sub(&sample_film_plane_point, &camera.position));
+        let line: Line = Line::new(camera.position, direction);
+
+        let (trace_color, bounces) = settings.integrator.radiance(world, &line, settings.max_bounces);
+        rays += bounces as u64;
+
+        if bounces == 0 {
+            num_no_bounce += 1;
+        } else {
+            num_no_bounce = 0;
+        }
+
+        // Add the result color to the pixel's final color.
+        pixel_color = vec_sum(&pixel_color, &vec_multiplication(&trace_color, single_color_contribution));
+
+        s += 1;
+
+        // Break in case we have no bounces in this pixel.
+        if num_no_bounce >= max_num_no_bounce {
+            pixel_color = trace_color;
+            break;
+        }
+    }
+
+    return (pixel_color, rays);
+}
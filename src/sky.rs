@@ -0,0 +1,315 @@
+use std::f32;
+
+use crate::lights::{DirectionalLight};
+use crate::vector3d::{
+    Vector3D,
+    vec_dot, vec_sum, vec_multiplication, vec_normalize
+};
+
+// Luminance of the model is in kilocandelas, radiance uses 683 lumens per watt.
+const LUMINANCE_TO_RADIANCE: f32 = 1000.0 / 683.0;
+
+// Illuminance of the sun outside the atmosphere, in lux.
+const SUN_ILLUMINANCE: f32 = 127500.0;
+
+// Wavelengths in micrometers used for the red, green and blue sun transmittance.
+const WAVELENGTHS: [f32; 3] = [0.680, 0.550, 0.440];
+
+/**
+ * Perez distribution of the sky, relative to the zenith.
+ */
+#[derive(Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32
+}
+
+impl Perez {
+    /**
+     * @param {f32} cos_theta Cosine of the angle between the direction and the zenith.
+     * @param {f32} gamma Angle between the direction and the sun.
+     *
+     * @return {f32}
+     */
+    fn evaluate(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+
+        return (1.0 + self.a * (self.b / cos_theta.max(1e-3)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma);
+    }
+}
+
+/**
+ * Zenith chromaticity polynomial of the Preetham model.
+ *
+ * @param {[[f32; 4]; 3]} matrix
+ * @param {f32} turbidity
+ * @param {f32} theta_sun
+ *
+ * @return {f32}
+ */
+fn get_zenith_chromaticity(matrix: &[[f32; 4]; 3], turbidity: f32, theta_sun: f32) -> f32 {
+    let turbidities = [turbidity * turbidity, turbidity, 1.0];
+    let thetas = [theta_sun * theta_sun * theta_sun, theta_sun * theta_sun, theta_sun, 1.0];
+
+    let mut result = 0.0;
+    for (row, t) in matrix.iter().zip(turbidities.iter()) {
+        for (value, theta) in row.iter().zip(thetas.iter()) {
+            result += t * value * theta;
+        }
+    }
+
+    return result;
+}
+
+/**
+ * CIE xyY color to linear sRGB, negative channels are clamped.
+ *
+ * @param {f32} x
+ * @param {f32} y
+ * @param {f32} luminance
+ *
+ * @return {Vector3D}
+ */
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vector3D {
+    if y <= 0.0 {
+        return Vector3D::new_as_zero();
+    }
+
+    let cie_x = x / y * luminance;
+    let cie_z = (1.0 - x - y) / y * luminance;
+
+    return Vector3D::new(
+        (3.2406 * cie_x - 1.5372 * luminance - 0.4986 * cie_z).max(0.0),
+        (-0.9689 * cie_x + 1.8758 * luminance + 0.0415 * cie_z).max(0.0),
+        (0.0557 * cie_x - 0.2040 * luminance + 1.0570 * cie_z).max(0.0)
+    );
+}
+
+/**
+ * Direction towards the sun.
+ *
+ * @param {f32} elevation Radians above the horizon.
+ * @param {f32} azimuth Radians from -Z towards +X.
+ *
+ * @return {Vector3D}
+ */
+pub fn get_sun_direction(elevation: f32, azimuth: f32) -> Vector3D {
+    return Vector3D::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        -elevation.cos() * azimuth.cos()
+    );
+}
+
+// ================================================
+// Preetham sky
+// ================================================
+
+/**
+ * Analytic daylight sky of Preetham, Shirley and Smits, "A Practical Analytic Model
+ * for Daylight" (1999). Valid for a turbidity between 2 (very clear) and 10 (hazy)
+ * and the sun above the horizon. The ground below the horizon is black.
+ * Radiance is in watts per steradian per square meter, use the exposure to bring it down.
+ */
+#[derive(Clone, Copy)]
+pub struct PreethamSky {
+    // Towards the sun.
+    pub sun_direction: Vector3D,
+    pub turbidity: f32,
+
+    // Scales the sky and the sun.
+    pub intensity: f32,
+
+    luminance: Perez,
+    x: Perez,
+    y: Perez,
+
+    // Zenith values divided by the distribution at the zenith.
+    zenith_luminance: f32,
+    zenith_x: f32,
+    zenith_y: f32,
+
+    // Mean radiance over the sphere, without the intensity.
+    average: Vector3D
+}
+
+impl PreethamSky {
+    /**
+     * @param {f32} elevation Radians above the horizon, clamped to [0, pi / 2].
+     * @param {f32} azimuth Radians from -Z towards +X.
+     * @param {f32} turbidity Clamped to [2, 10].
+     *
+     * @return {PreethamSky}
+     */
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> PreethamSky {
+        let elevation = elevation.clamp(0.0, f32::consts::FRAC_PI_2);
+        let t = turbidity.clamp(2.0, 10.0);
+
+        let theta_sun = f32::consts::FRAC_PI_2 - elevation;
+
+        let luminance = Perez {
+            a: 0.1787 * t - 1.4630,
+            b: -0.3554 * t + 0.4275,
+            c: -0.0227 * t + 5.3251,
+            d: 0.1206 * t - 2.5771,
+            e: -0.0670 * t + 0.3703
+        };
+        let x = Perez {
+            a: -0.0193 * t - 0.2592,
+            b: -0.0665 * t + 0.0008,
+            c: -0.0004 * t + 0.2125,
+            d: -0.0641 * t - 0.8989,
+            e: -0.0033 * t + 0.0452
+        };
+        let y = Perez {
+            a: -0.0167 * t - 0.2608,
+            b: -0.0950 * t + 0.0092,
+            c: -0.0079 * t + 0.2102,
+            d: -0.0441 * t - 1.6537,
+            e: -0.0109 * t + 0.0529
+        };
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (f32::consts::PI - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+
+        let zenith_x = get_zenith_chromaticity(&[
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886]
+        ], t, theta_sun);
+        let zenith_y = get_zenith_chromaticity(&[
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688]
+        ], t, theta_sun);
+
+        let mut sky = PreethamSky {
+            sun_direction: get_sun_direction(elevation, azimuth),
+            turbidity: t,
+
+            intensity: 1.0,
+
+            luminance: luminance,
+            x: x,
+            y: y,
+
+            zenith_luminance: zenith_luminance / luminance.evaluate(1.0, theta_sun),
+            zenith_x: zenith_x / x.evaluate(1.0, theta_sun),
+            zenith_y: zenith_y / y.evaluate(1.0, theta_sun),
+
+            average: Vector3D::new_as_zero()
+        };
+
+        sky.average = sky.integrate_average();
+
+        return sky;
+    }
+
+    /**
+     * Scales the sky and the sun.
+     *
+     * @param {f32} intensity
+     *
+     * @return {PreethamSky}
+     */
+    pub fn with_intensity(mut self, intensity: f32) -> PreethamSky {
+        self.intensity = intensity;
+
+        return self;
+    }
+
+    /**
+     * Mean radiance over the sphere, by midpoint integration over the upper hemisphere.
+     *
+     * @return {Vector3D}
+     */
+    fn integrate_average(&self) -> Vector3D {
+        let rings = 32;
+        let segments = 64;
+
+        let mut sum = Vector3D::new_as_zero();
+        for ring in 0..rings {
+            // Uniform heights are uniform in solid angle.
+            let cos_theta = (ring as f32 + 0.5) / rings as f32;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+            for segment in 0..segments {
+                let phi = 2.0 * f32::consts::PI * (segment as f32 + 0.5) / segments as f32;
+                let direction = Vector3D::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+
+                sum = vec_sum(&sum, &self.get_radiance(&direction));
+            }
+        }
+
+        // The lower half is black.
+        return vec_multiplication(&sum, 0.5 / (rings * segments) as f32);
+    }
+
+    pub fn get_radiance(&self, direction: &Vector3D) -> Vector3D {
+        let direction = vec_normalize(direction);
+
+        let cos_theta = direction.y;
+        if cos_theta <= 0.0 {
+            return Vector3D::new_as_zero();
+        }
+
+        let gamma = vec_dot(&direction, &self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let luminance = self.zenith_luminance * self.luminance.evaluate(cos_theta, gamma);
+        let x = self.zenith_x * self.x.evaluate(cos_theta, gamma);
+        let y = self.zenith_y * self.y.evaluate(cos_theta, gamma);
+
+        return vec_multiplication(&xyy_to_rgb(x, y, luminance), LUMINANCE_TO_RADIANCE * self.intensity);
+    }
+
+    pub fn get_average_radiance(&self) -> Vector3D {
+        return vec_multiplication(&self.average, self.intensity);
+    }
+
+    /**
+     * Fraction of the sunlight that crosses the atmosphere for each color channel,
+     * from the Rayleigh and aerosol scattering of the Preetham model.
+     *
+     * @return {Vector3D}
+     */
+    pub fn get_sun_transmittance(&self) -> Vector3D {
+        let theta_sun = vec_dot(&self.sun_direction, &Vector3D::new(0.0, 1.0, 0.0)).clamp(0.0, 1.0).acos();
+
+        // Relative optical mass of the air along the sun direction.
+        let theta_degrees = theta_sun.to_degrees();
+        let mass = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_degrees).max(1e-3).powf(-1.253));
+
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let alpha = 1.3;
+
+        let channels: Vec<f32> = WAVELENGTHS.iter().map(|lambda| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+            let aerosol = (-beta * lambda.powf(-alpha) * mass).exp();
+
+            rayleigh * aerosol
+        }).collect();
+
+        return Vector3D::new(channels[0], channels[1], channels[2]);
+    }
+
+    /**
+     * Sun matching the sky, in the same units.
+     *
+     * @param {f32} angular_diameter Radians.
+     *
+     * @return {DirectionalLight}
+     */
+    pub fn get_sun_light(&self, angular_diameter: f32) -> DirectionalLight {
+        let irradiance = SUN_ILLUMINANCE / 683.0 * self.intensity;
+
+        return DirectionalLight::new(
+            vec_multiplication(&self.sun_direction, -1.0),
+            self.get_sun_transmittance(),
+            irradiance
+        ).with_angular_diameter(angular_diameter);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::renderer::{Film};
+use crate::vector3d::{
+    Vector3D,
+    vec_sum, vec_multiplication
+};
+
+/**
+ * Anything that gives a color to a surface point.
+ */
+pub trait Texture {
+    /**
+     * @param {(f32, f32)} uv Texture coordinates of the hit.
+     * @param {Vector3D} position Hit position in world space.
+     *
+     * @return {Vector3D} Linear color.
+     */
+    fn evaluate(&self, uv: (f32, f32), position: &Vector3D) -> Vector3D;
+}
+
+/**
+ * Linear interpolation between two colors.
+ *
+ * @param {Vector3D} a
+ * @param {Vector3D} b
+ * @param {f32} t
+ *
+ * @return {Vector3D}
+ */
+fn mix(a: &Vector3D, b: &Vector3D, t: f32) -> Vector3D {
+    return vec_sum(&vec_multiplication(a, 1.0 - t), &vec_multiplication(b, t));
+}
+
+// ================================================
+// Image
+// ================================================
+
+/**
+ * What happens to the texture coordinates outside [0, 1].
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    // The image tiles.
+    Repeat,
+    // The edge pixels stretch.
+    Clamp,
+    // The image tiles, every other copy flipped.
+    Mirror
+}
+
+impl WrapMode {
+    /**
+     * Finds the wrap mode by the name used in scene files.
+     *
+     * @param {&str} name
+     *
+     * @return {Option<WrapMode>}
+     */
+    pub fn from_name(name: &str) -> Option<WrapMode> {
+        return match name.to_lowercase().as_str() {
+            "repeat" => Some(WrapMode::Repeat),
+            "clamp" => Some(WrapMode::Clamp),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None
+        };
+    }
+
+    /**
+     * Brings a pixel index inside the image.
+     *
+     * @param {i64} index
+     * @param {usize} size Pixels along the axis.
+     *
+     * @return {usize}
+     */
+    fn apply(&self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+
+        let wrapped = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Clamp => index.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = index.rem_euclid(2 * size);
+                if period < size { period } else { 2 * size - 1 - period }
+            }
+        };
+
+        return wrapped as usize;
+    }
+}
+
+/**
+ * Image mapped on the texture coordinates with bilinear filtering.
+ * The coordinate v goes from the bottom of the image to the top.
+ */
+#[derive(Clone)]
+pub struct ImageTexture {
+    // Linear colors, decoded from sRGB when loaded.
+    pub image: Film,
+    pub wrap: WrapMode
+}
+
+impl ImageTexture {
+    pub fn new(image: Film, wrap: WrapMode) -> ImageTexture {
+        return ImageTexture {
+            image: image,
+            wrap: wrap
+        };
+    }
+}
+
+impl Texture for ImageTexture {
+    fn evaluate(&self, uv: (f32, f32), _position: &Vector3D) -> Vector3D {
+        let width = self.image.width;
+        let height = self.image.height;
+
+        if width == 0 || height == 0 {
+            return Vector3D::new_as_zero();
+        }
+
+        // Pixel centers are at half coordinates.
+        let x = uv.0 * width as f32 - 0.5;
+        let y = (1.0 - uv.1) * height as f32 - 0.5;
+
+        let x_floor = x.floor();
+        let y_floor = y.floor();
+        let tx = x - x_floor;
+        let ty = y - y_floor;
+
+        let x0 = self.wrap.apply(x_floor as i64, width);
+        let x1 = self.wrap.apply(x_floor as i64 + 1, width);
+        let y0 = self.wrap.apply(y_floor as i64, height);
+        let y1 = self.wrap.apply(y_floor as i64 + 1, height);
+
+        let top = mix(&self.image.get_pixel(x0, y0), &self.image.get_pixel(x1, y0), tx);
+        let bottom = mix(&self.image.get_pixel(x0, y1), &self.image.get_pixel(x1, y1), tx);
+
+        return mix(&top, &bottom, ty);
+    }
+}
+
+// ================================================
+// Checker
+// ================================================
+
+/**
+ * Squares of two alternating colors on the texture coordinates.
+ */
+#[derive(Clone, Copy)]
+pub struct CheckerTexture {
+    pub even: Vector3D,
+    pub odd: Vector3D,
+
+    // Squares per texture coordinate unit.
+    pub scale: f32
+}
+
+impl CheckerTexture {
+    pub fn new(even: Vector3D, odd: Vector3D, scale: f32) -> CheckerTexture {
+        return CheckerTexture {
+            even: even,
+            odd: odd,
+            scale: scale
+        };
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn evaluate(&self, uv: (f32, f32), _position: &Vector3D) -> Vector3D {
+        let square = (uv.0 * self.scale).floor() as i64 + (uv.1 * self.scale).floor() as i64;
+
+        if square.rem_euclid(2) == 0 {
+            return self.even;
+        }
+
+        return self.odd;
+    }
+}
+
+// ================================================
+// Noise
+// ================================================
+
+fn fade(t: f32) -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    return a + (b - a) * t;
+}
+
+/**
+ * Dot product of the offset with one of the 12 gradient directions of improved Perlin noise.
+ *
+ * @param {usize} hash
+ * @param {f32} x
+ * @param {f32} y
+ * @param {f32} z
+ *
+ * @return {f32}
+ */
+fn get_gradient(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+
+    return (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v });
+}
+
+/**
+ * Fractal Brownian motion of Perlin noise on the world position, mapped between two colors.
+ * Every octave doubles the frequency and scales the amplitude by the persistence.
+ */
+#[derive(Clone)]
+pub struct NoiseTexture {
+    pub low: Vector3D,
+    pub high: Vector3D,
+
+    // Frequency of the first octave, in cycles per world unit.
+    pub scale: f32,
+    pub octaves: u32,
+    pub persistence: f32,
+
+    // Shuffled 0 to 255, twice to skip the wrapping.
+    permutation: Vec<usize>
+}
+
+impl NoiseTexture {
+    pub fn new(low: Vector3D, high: Vector3D, scale: f32) -> NoiseTexture {
+        return NoiseTexture {
+            low: low,
+            high: high,
+
+            scale: scale,
+            octaves: 1,
+            persistence: 0.5,
+
+            permutation: NoiseTexture::get_permutation(0)
+        };
+    }
+
+    /**
+     * Adds finer octaves on top of the first one.
+     *
+     * @param {u32} octaves
+     * @param {f32} persistence Amplitude of every octave relative to the previous one.
+     *
+     * @return {NoiseTexture}
+     */
+    pub fn with_octaves(mut self, octaves: u32, persistence: f32) -> NoiseTexture {
+        self.octaves = octaves.max(1);
+        self.persistence = persistence;
+
+        return self;
+    }
+
+    /**
+     * Changes the pattern, the same seed always gives the same one.
+     *
+     * @param {u64} seed
+     *
+     * @return {NoiseTexture}
+     */
+    pub fn with_seed(mut self, seed: u64) -> NoiseTexture {
+        self.permutation = NoiseTexture::get_permutation(seed);
+
+        return self;
+    }
+
+    /**
+     * Fisher-Yates shuffle driven by a xorshift generator.
+     *
+     * @param {u64} seed
+     *
+     * @return {Vec<usize>}
+     */
+    fn get_permutation(seed: u64) -> Vec<usize> {
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        let mut values: Vec<usize> = (0..256).collect();
+
+        for index in (1..values.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+
+            values.swap(index, (state % (index as u64 + 1)) as usize);
+        }
+
+        return values.iter().chain(values.iter()).cloned().collect();
+    }
+
+    /**
+     * Improved Perlin noise, roughly in [-1, 1] and 0 on the integer lattice.
+     *
+     * @param {Vector3D} point
+     *
+     * @return {f32}
+     */
+    fn get_noise(&self, point: &Vector3D) -> f32 {
+        let p = &self.permutation;
+
+        let (x_floor, y_floor, z_floor) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let xi = (x_floor as i64 & 255) as usize;
+        let yi = (y_floor as i64 & 255) as usize;
+        let zi = (z_floor as i64 & 255) as usize;
+
+        let (x, y, z) = (point.x - x_floor, point.y - y_floor, point.z - z_floor);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        let near = lerp(
+            lerp(get_gradient(p[aa], x, y, z), get_gradient(p[ba], x - 1.0, y, z), u),
+            lerp(get_gradient(p[ab], x, y - 1.0, z), get_gradient(p[bb], x - 1.0, y - 1.0, z), u),
+            v
+        );
+        let far = lerp(
+            lerp(get_gradient(p[aa + 1], x, y, z - 1.0), get_gradient(p[ba + 1], x - 1.0, y, z - 1.0), u),
+            lerp(get_gradient(p[ab + 1], x, y - 1.0, z - 1.0), get_gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0), u),
+            v
+        );
+
+        return lerp(near, far, w);
+    }
+
+    /**
+     * Sum of the octaves at a world position.
+     *
+     * @param {Vector3D} position
+     *
+     * @return {f32} Value in [0, 1].
+     */
+    pub fn get_value(&self, position: &Vector3D) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+
+        let mut frequency = self.scale;
+        let mut amplitude = 1.0;
+
+        for _ in 0..self.octaves {
+            sum += amplitude * self.get_noise(&vec_multiplication(position, frequency));
+            total_amplitude += amplitude;
+
+            frequency *= 2.0;
+            amplitude *= self.persistence;
+        }
+
+        if total_amplitude <= 0.0 {
+            return 0.5;
+        }
+
+        return (0.5 + 0.5 * sum / total_amplitude).clamp(0.0, 1.0);
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn evaluate(&self, _uv: (f32, f32), position: &Vector3D) -> Vector3D {
+        return mix(&self.low, &self.high, self.get_value(position));
+    }
+}
+
+// ================================================
+// Gradient
+// ================================================
+
+/**
+ * Texture coordinate followed by a gradient.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientAxis {
+    U,
+    V
+}
+
+/**
+ * Fades between two colors along one texture coordinate, from 0 to 1.
+ */
+#[derive(Clone, Copy)]
+pub struct GradientTexture {
+    pub start: Vector3D,
+    pub end: Vector3D,
+    pub axis: GradientAxis
+}
+
+impl GradientTexture {
+    pub fn new(start: Vector3D, end: Vector3D, axis: GradientAxis) -> GradientTexture {
+        return GradientTexture {
+            start: start,
+            end: end,
+            axis: axis
+        };
+    }
+}
+
+impl Texture for GradientTexture {
+    fn evaluate(&self, uv: (f32, f32), _position: &Vector3D) -> Vector3D {
+        let t = match self.axis {
+            GradientAxis::U => uv.0,
+            GradientAxis::V => uv.1
+        };
+
+        return mix(&self.start, &self.end, t.clamp(0.0, 1.0));
+    }
+}
+
+// ================================================
+// Scene textures
+// ================================================
+
+/**
+ * Any texture of a scene, the materials use them by their index in the world.
+ */
+#[derive(Clone)]
+pub enum SceneTexture {
+    Image(ImageTexture),
+    Checker(CheckerTexture),
+    Noise(NoiseTexture),
+    Gradient(GradientTexture)
+}
+
+impl Texture for SceneTexture {
+    fn evaluate(&self, uv: (f32, f32), position: &Vector3D) -> Vector3D {
+        return match self {
+            SceneTexture::Image(texture) => texture.evaluate(uv, position),
+            SceneTexture::Checker(texture) => texture.evaluate(uv, position),
+            SceneTexture::Noise(texture) => texture.evaluate(uv, position),
+            SceneTexture::Gradient(texture) => texture.evaluate(uv, position)
+        };
+    }
+}
@@ -25,9 +25,16 @@ impl Vector3D {
         return Vector3D::new(1.0, 1.0, 1.0);
     }
 
+    /**
+     * Random direction uniformly distributed over the whole sphere.
+     *
+     * @param {f32} multiplier Length of the vector.
+     *
+     * @return {Vector3D}
+     */
     pub fn new_random(multiplier: f32) -> Vector3D {
         let mut rng = rand::thread_rng();
-        let phi = rng.gen::<f32>() * std::f32::consts::PI;
+        let phi = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
         let cos_theta = rng.gen::<f32>() * 2.0 - 1.0;
 
         let theta = cos_theta.acos();
@@ -135,3 +142,131 @@ pub fn vec_hadamard(u: &Vector3D, v: &Vector3D) -> Vector3D {
         u.z * v.z
     );
 }
+
+pub fn vec_min(u: &Vector3D, v: &Vector3D) -> Vector3D {
+    return Vector3D::new(
+        u.x.min(v.x),
+        u.y.min(v.y),
+        u.z.min(v.z)
+    );
+}
+
+pub fn vec_max(u: &Vector3D, v: &Vector3D) -> Vector3D {
+    return Vector3D::new(
+        u.x.max(v.x),
+        u.y.max(v.y),
+        u.z.max(v.z)
+    );
+}
+
+// ================================================
+// Hemisphere sampling
+// ================================================
+
+/**
+ * Cosine weighted direction around +Z, mapped from the unit square through a
+ * concentric disk (Shirley-Chiu). The density is cos(theta) / PI.
+ *
+ * @param {f32} u1 Uniform number in [0, 1).
+ * @param {f32} u2 Uniform number in [0, 1).
+ *
+ * @return {Vector3D} Unit vector in local coordinates.
+ */
+pub fn sample_cosine_hemisphere(u1: f32, u2: f32) -> Vector3D {
+    let a = 2.0 * u1 - 1.0;
+    let b = 2.0 * u2 - 1.0;
+
+    let (radius, phi) = if a == 0.0 && b == 0.0 {
+        (0.0, 0.0)
+    } else if a.abs() > b.abs() {
+        (a, std::f32::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, std::f32::consts::FRAC_PI_2 - std::f32::consts::FRAC_PI_4 * (a / b))
+    };
+
+    let x = radius * phi.cos();
+    let y = radius * phi.sin();
+
+    return Vector3D::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt());
+}
+
+/**
+ * Uniform direction around +Z, the density is 1 / (2 * PI).
+ *
+ * @param {f32} u1 Uniform number in [0, 1).
+ * @param {f32} u2 Uniform number in [0, 1).
+ *
+ * @return {Vector3D} Unit vector in local coordinates.
+ */
+pub fn sample_uniform_hemisphere(u1: f32, u2: f32) -> Vector3D {
+    let z = u1;
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u2;
+
+    return Vector3D::new(radius * phi.cos(), radius * phi.sin(), z);
+}
+
+// ================================================
+// Orthonormal basis
+// ================================================
+
+/**
+ * Local frame around a direction, `w` is the direction itself.
+ */
+#[derive(Copy, Clone)]
+pub struct Onb {
+    pub u: Vector3D,
+    pub v: Vector3D,
+    pub w: Vector3D
+}
+
+impl Onb {
+    /**
+     * Builds the basis around a unit vector without branching on its orientation.
+     * Duff et al. "Building an Orthonormal Basis, Revisited".
+     *
+     * @param {Vector3D} w Unit vector.
+     *
+     * @return {Onb}
+     */
+    pub fn new_from_w(w: &Vector3D) -> Onb {
+        let sign = 1.0f32.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+
+        return Onb {
+            u: Vector3D::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vector3D::new(b, sign + w.y * w.y * a, -w.y),
+            w: *w
+        };
+    }
+
+    /**
+     * Converts local coordinates to world space.
+     *
+     * @param {Vector3D} local
+     *
+     * @return {Vector3D}
+     */
+    pub fn to_world(&self, local: &Vector3D) -> Vector3D {
+        return vec_sum(
+            &vec_sum(&vec_multiplication(&self.u, local.x), &vec_multiplication(&self.v, local.y)),
+            &vec_multiplication(&self.w, local.z)
+        );
+    }
+
+    /**
+     * Converts a world space vector to local coordinates.
+     *
+     * @param {Vector3D} world
+     *
+     * @return {Vector3D}
+     */
+    pub fn to_local(&self, world: &Vector3D) -> Vector3D {
+        return Vector3D::new(
+            vec_dot(world, &self.u),
+            vec_dot(world, &self.v),
+            vec_dot(world, &self.w)
+        );
+    }
+}
@@ -0,0 +1,315 @@
+extern crate rand;
+extern crate raytracer;
+
+use std::f32::consts::PI;
+
+use rand::prelude::*;
+
+use raytracer::bsdf::{Bsdf, Lambertian, MetallicRoughness, Dielectric, fresnel_schlick, fresnel_dielectric};
+use raytracer::vector3d::{Vector3D, Onb, sample_cosine_hemisphere, vec_dot, vec_cross, vec_get_length, vec_normalize};
+
+const SAMPLES: usize = 200_000;
+
+fn new_rng() -> StdRng {
+    return StdRng::seed_from_u64(7);
+}
+
+fn random_hemisphere_direction(rng: &mut StdRng, normal: &Vector3D) -> Vector3D {
+    let cos_theta = rng.gen::<f32>();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    return Onb::new_from_w(normal).to_world(&Vector3D::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+}
+
+fn materials() -> Vec<MetallicRoughness> {
+    let white = Vector3D::new_as_one();
+
+    return vec![
+        MetallicRoughness::new(white, 1.0, 0.0),
+        MetallicRoughness::new(white, 0.5, 0.0),
+        MetallicRoughness::new(white, 0.2, 0.5),
+        MetallicRoughness::new(white, 0.5, 1.0),
+        MetallicRoughness::new(white, 0.05, 1.0)
+    ];
+}
+
+#[test]
+fn basis_is_orthonormal() {
+    let mut rng = new_rng();
+
+    for _ in 0..1000 {
+        let w = vec_normalize(&Vector3D::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5));
+        let basis = Onb::new_from_w(&w);
+
+        assert!((vec_get_length(&basis.u) - 1.0).abs() < 1e-4);
+        assert!((vec_get_length(&basis.v) - 1.0).abs() < 1e-4);
+        assert!(vec_dot(&basis.u, &basis.v).abs() < 1e-4);
+        assert!(vec_dot(&basis.u, &w).abs() < 1e-4);
+        assert!(vec_dot(&basis.v, &w).abs() < 1e-4);
+
+        // Right handed
+        assert!((vec_dot(&vec_cross(&basis.u, &basis.v), &w) - 1.0).abs() < 1e-4);
+
+        let local = basis.to_local(&Vector3D::new(0.3, -0.2, 0.9));
+        let world = basis.to_world(&local);
+        assert!((world.x - 0.3).abs() < 1e-4 && (world.y + 0.2).abs() < 1e-4 && (world.z - 0.9).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn fresnel_goes_from_f0_to_one() {
+    let f0 = Vector3D::new(0.04, 0.5, 0.9);
+
+    let normal_incidence = fresnel_schlick(&f0, 1.0);
+    assert!((normal_incidence.x - 0.04).abs() < 1e-6);
+    assert!((normal_incidence.z - 0.9).abs() < 1e-6);
+
+    let grazing = fresnel_schlick(&f0, 0.0);
+    assert!((grazing.x - 1.0).abs() < 1e-6);
+    assert!((grazing.y - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn evaluation_is_reciprocal() {
+    let mut rng = new_rng();
+    let normal = vec_normalize(&Vector3D::new(0.2, 1.0, -0.3));
+
+    for material in materials().iter() {
+        for _ in 0..100 {
+            let a = random_hemisphere_direction(&mut rng, &normal);
+            let b = random_hemisphere_direction(&mut rng, &normal);
+
+            let ab = material.evaluate(&normal, &a, &b);
+            let ba = material.evaluate(&normal, &b, &a);
+
+            assert!((ab.x - ba.x).abs() <= 1e-3 * ab.x.max(1.0));
+        }
+    }
+}
+
+#[test]
+fn sample_weight_matches_evaluation() {
+    let mut rng = new_rng();
+    let normal = Vector3D::new(0.0, 1.0, 0.0);
+    let outgoing = vec_normalize(&Vector3D::new(0.5, 1.0, 0.0));
+
+    for material in materials().iter() {
+        for _ in 0..100 {
+            if let Some(sample) = material.sample(&normal, &outgoing, &mut rng) {
+                let cos_theta = vec_dot(&normal, &sample.direction);
+                let expected = material.evaluate(&normal, &outgoing, &sample.direction).y * cos_theta
+                    / material.pdf(&normal, &outgoing, &sample.direction);
+
+                assert!(cos_theta > 0.0);
+                assert!((sample.weight.y - expected).abs() <= 1e-3 * expected.max(1.0));
+            }
+        }
+    }
+}
+
+#[test]
+fn white_material_does_not_create_energy() {
+    let mut rng = new_rng();
+    let normal = Vector3D::new(0.0, 0.0, 1.0);
+
+    for material in materials().iter() {
+        for cos_theta_o in [1.0f32, 0.7, 0.3].iter() {
+            let outgoing = Vector3D::new((1.0 - cos_theta_o * cos_theta_o).sqrt(), 0.0, *cos_theta_o);
+
+            let mut albedo = 0.0;
+            for _ in 0..SAMPLES {
+                if let Some(sample) = material.sample(&normal, &outgoing, &mut rng) {
+                    albedo += sample.weight.x;
+                }
+            }
+            albedo /= SAMPLES as f32;
+
+            assert!(albedo <= 1.02, "albedo {} for roughness {} metallic {}", albedo, material.roughness, material.metallic);
+            // Single scattering loses some energy on rough surfaces, but not most of it.
+            assert!(albedo >= 0.7, "albedo {} for roughness {} metallic {}", albedo, material.roughness, material.metallic);
+        }
+    }
+}
+
+#[test]
+fn pdf_integrates_to_at_most_one() {
+    let mut rng = new_rng();
+    let normal = Vector3D::new(0.0, 0.0, 1.0);
+    let outgoing = vec_normalize(&Vector3D::new(0.3, 0.0, 1.0));
+
+    for material in materials().iter().filter(|material| material.roughness >= 0.2) {
+        // Uniform hemisphere estimate of the integral.
+        let mut integral = 0.0;
+        for _ in 0..SAMPLES {
+            let incoming = random_hemisphere_direction(&mut rng, &normal);
+            integral += material.pdf(&normal, &outgoing, &incoming) * 2.0 * PI;
+        }
+        integral /= SAMPLES as f32;
+
+        assert!(integral <= 1.03, "pdf integral {}", integral);
+        assert!(integral >= 0.8, "pdf integral {}", integral);
+    }
+}
+
+#[test]
+fn dielectric_fresnel() {
+    // 4% at normal incidence for glass, from both sides.
+    assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-4);
+    assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-4);
+
+    // Everything is reflected at grazing angles.
+    assert!(fresnel_dielectric(0.0, 1.5) > 0.99);
+
+    // Total internal reflection past the critical angle from inside.
+    let critical_cos = (1.0f32 - (1.0 / 1.5f32).powi(2)).sqrt();
+    assert_eq!(fresnel_dielectric(critical_cos - 0.01, 1.0 / 1.5), 1.0);
+    assert!(fresnel_dielectric(critical_cos + 0.01, 1.0 / 1.5) < 1.0);
+
+    // Nothing is reflected without a change of medium.
+    assert!(fresnel_dielectric(0.5, 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn dielectric_refraction_follows_snell() {
+    let mut rng = new_rng();
+    let normal = Vector3D::new(0.0, 1.0, 0.0);
+    let outgoing = vec_normalize(&Vector3D::new(1.0, 1.0, 0.0));
+    let glass = Dielectric::new(1.5);
+
+    let mut reflections = 0;
+    for _ in 0..10_000 {
+        let sample = glass.sample(&normal, &outgoing, &mut rng).unwrap();
+        assert!(sample.is_delta);
+        assert_eq!((sample.weight.x, sample.weight.y, sample.weight.z), (1.0, 1.0, 1.0));
+
+        if sample.direction.y > 0.0 {
+            reflections += 1;
+
+            // Mirror of the outgoing direction.
+            assert!((sample.direction.x + outgoing.x).abs() < 1e-5);
+            assert!((sample.direction.y - outgoing.y).abs() < 1e-5);
+        } else {
+            let sin_theta_o = outgoing.x;
+            let sin_theta_t = -sample.direction.x;
+
+            assert!((sin_theta_o - 1.5 * sin_theta_t).abs() < 1e-4);
+            assert!((vec_get_length(&sample.direction) - 1.0).abs() < 1e-4);
+        }
+    }
+
+    let expected = fresnel_dielectric(outgoing.y, 1.5);
+    assert!((reflections as f32 / 10_000.0 - expected).abs() < 0.01);
+}
+
+#[test]
+fn dielectric_total_internal_reflection() {
+    let mut rng = new_rng();
+    let normal = Vector3D::new(0.0, 1.0, 0.0);
+    // Grazing from inside glass.
+    let outgoing = vec_normalize(&Vector3D::new(1.0, 0.2, 0.0));
+    let inside = Dielectric::new(1.0 / 1.5);
+
+    for _ in 0..100 {
+        let sample = inside.sample(&normal, &outgoing, &mut rng).unwrap();
+        assert!(sample.direction.y > 0.0);
+    }
+}
+
+#[test]
+fn cosine_hemisphere_has_the_expected_moments() {
+    let mut rng = new_rng();
+
+    let mut mean = Vector3D::new_as_zero();
+    let mut mean_cos2 = 0.0;
+    for _ in 0..SAMPLES {
+        let direction = sample_cosine_hemisphere(rng.gen::<f32>(), rng.gen::<f32>());
+
+        assert!((vec_get_length(&direction) - 1.0).abs() < 1e-4);
+        assert!(direction.z >= 0.0);
+
+        mean.x += direction.x;
+        mean.y += direction.y;
+        mean.z += direction.z;
+        mean_cos2 += direction.z * direction.z;
+    }
+
+    let count = SAMPLES as f32;
+
+    // E[cos] = 2/3 and E[cos^2] = 1/2 for a cos / PI density.
+    assert!((mean.x / count).abs() < 0.01);
+    assert!((mean.y / count).abs() < 0.01);
+    assert!((mean.z / count - 2.0 / 3.0).abs() < 0.01);
+    assert!((mean_cos2 / count - 0.5).abs() < 0.01);
+}
+
+#[test]
+fn lambertian_sampling_is_consistent() {
+    let mut rng = new_rng();
+    let normal = vec_normalize(&Vector3D::new(1.0, 2.0, 0.5));
+    let outgoing = normal;
+    let albedo = Vector3D::new(0.8, 0.5, 0.2);
+    let lambertian = Lambertian::new(albedo);
+
+    for _ in 0..1000 {
+        let sample = lambertian.sample(&normal, &outgoing, &mut rng).unwrap();
+        let cos_theta = vec_dot(&normal, &sample.direction);
+
+        assert!(cos_theta >= 0.0);
+        assert!(!sample.is_delta);
+        assert!((sample.pdf - lambertian.pdf(&normal, &outgoing, &sample.direction)).abs() < 1e-4);
+
+        // The whole albedo comes back with every sample.
+        assert_eq!((sample.weight.x, sample.weight.y, sample.weight.z), (albedo.x, albedo.y, albedo.z));
+
+        let value = lambertian.evaluate(&normal, &outgoing, &sample.direction);
+        assert!((value.x * cos_theta / sample.pdf - albedo.x).abs() < 1e-3);
+    }
+
+    // Nothing goes through the surface.
+    let below = vec_normalize(&Vector3D::new(-1.0, -2.0, 0.0));
+    assert_eq!(lambertian.evaluate(&normal, &outgoing, &below).x, 0.0);
+    assert_eq!(lambertian.pdf(&normal, &outgoing, &below), 0.0);
+}
+
+#[test]
+fn metallic_roughness_base_is_lambertian() {
+    let normal = Vector3D::new(0.0, 0.0, 1.0);
+    let outgoing = vec_normalize(&Vector3D::new(0.3, 0.0, 1.0));
+    let incoming = vec_normalize(&Vector3D::new(-0.5, 0.4, 1.0));
+
+    let half_metal = MetallicRoughness::new(Vector3D::new(0.8, 0.4, 0.2), 0.5, 0.25);
+    let diffuse = half_metal.get_diffuse();
+    assert!((diffuse.albedo.x - 0.6).abs() < 1e-6);
+    assert!((diffuse.albedo.z - 0.15).abs() < 1e-6);
+
+    // The same lobe the renderer samples, cosine weighted.
+    assert!((diffuse.pdf(&normal, &outgoing, &incoming) - vec_dot(&normal, &incoming) / PI).abs() < 1e-6);
+
+    // Metals have no base left.
+    let metal = MetallicRoughness::new(Vector3D::new(0.8, 0.4, 0.2), 0.5, 1.0);
+    assert_eq!(metal.get_diffuse().evaluate(&normal, &outgoing, &incoming).x, 0.0);
+}
+
+#[test]
+fn random_vectors_cover_the_whole_sphere() {
+    let mut octants = [0; 8];
+    let mut mean = Vector3D::new_as_zero();
+
+    for _ in 0..20_000 {
+        let v = Vector3D::new_random(1.0);
+
+        let octant = (v.x > 0.0) as usize + 2 * (v.y > 0.0) as usize + 4 * (v.z > 0.0) as usize;
+        octants[octant] += 1;
+
+        mean.x += v.x;
+        mean.y += v.y;
+        mean.z += v.z;
+    }
+
+    for count in octants.iter() {
+        assert!(*count > 2000, "octants {:?}", octants);
+    }
+
+    assert!((mean.y / 20_000.0).abs() < 0.05);
+}
@@ -0,0 +1,166 @@
+extern crate rand;
+extern crate raytracer;
+
+use rand::prelude::*;
+
+use raytracer::World;
+use raytracer::bvh::Bvh;
+use raytracer::geometry::{Line, Intersect, Primitive, Triangle, Sphere, Plane};
+use raytracer::vector3d::{Vector3D, vec_sum, vec_normalize};
+
+fn random_point(rng: &mut StdRng, size: f32) -> Vector3D {
+    return Vector3D::new(
+        (rng.gen::<f32>() * 2.0 - 1.0) * size,
+        (rng.gen::<f32>() * 2.0 - 1.0) * size,
+        (rng.gen::<f32>() * 2.0 - 1.0) * size
+    );
+}
+
+fn random_primitives(rng: &mut StdRng) -> Vec<Primitive> {
+    let mut primitives: Vec<Primitive> = Vec::new();
+
+    for _ in 0..500 {
+        let a = random_point(rng, 10.0);
+        let b = vec_sum(&a, &random_point(rng, 1.0));
+        let c = vec_sum(&a, &random_point(rng, 1.0));
+
+        primitives.push(Primitive::Triangle(Triangle::new(a, b, c, 1)));
+    }
+
+    for _ in 0..20 {
+        primitives.push(Primitive::Sphere(Sphere::new(random_point(rng, 10.0), rng.gen::<f32>() + 0.1, 2)));
+    }
+
+    primitives.push(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -12.0, 0.0), 3)));
+
+    return primitives;
+}
+
+fn random_line(rng: &mut StdRng) -> Line {
+    return Line::new(random_point(rng, 15.0), vec_normalize(&random_point(rng, 1.0)));
+}
+
+fn brute_force_closest(primitives: &[Primitive], line: &Line) -> Option<(f32, usize)> {
+    let mut closest: Option<(f32, usize)> = None;
+
+    for (index, primitive) in primitives.iter().enumerate() {
+        if let Some(hit) = primitive.intersects(line) {
+            let closer = match closest {
+                Some((closest_t, _)) => hit.t < closest_t,
+                None => true
+            };
+
+            if closer {
+                closest = Some((hit.t, index));
+            }
+        }
+    }
+
+    return closest;
+}
+
+#[test]
+fn closest_hit_matches_brute_force() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let primitives = random_primitives(&mut rng);
+    let bvh = Bvh::build(&primitives);
+
+    let mut hits = 0;
+    for _ in 0..5000 {
+        let line = random_line(&mut rng);
+
+        let expected = brute_force_closest(&primitives, &line);
+        let result = bvh.closest_hit(&primitives, &line).map(|hit| (hit.t, hit.primitive_index));
+
+        match (expected, result) {
+            (Some((et, ei)), Some((rt, ri))) => {
+                assert!((et - rt).abs() < 1e-5);
+                assert_eq!(ei, ri);
+                hits += 1;
+            },
+            (None, None) => {},
+            _ => panic!("BVH and brute force disagree")
+        }
+    }
+
+    assert!(hits > 0);
+}
+
+#[test]
+fn any_hit_matches_brute_force() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let primitives = random_primitives(&mut rng);
+    let bvh = Bvh::build(&primitives);
+
+    for _ in 0..5000 {
+        let line = random_line(&mut rng);
+        let max_t = rng.gen::<f32>() * 20.0;
+
+        let expected = match brute_force_closest(&primitives, &line) {
+            Some((t, _)) => t < max_t,
+            None => false
+        };
+
+        assert_eq!(bvh.any_hit(&primitives, &line, max_t), expected);
+    }
+}
+
+#[test]
+fn world_uses_the_same_hits_before_and_after_build() {
+    let mut rng = StdRng::seed_from_u64(3);
+
+    let mut world = World::new();
+    *world.get_objects_mut() = random_primitives(&mut rng);
+
+    let lines: Vec<Line> = (0..1000).map(|_| random_line(&mut rng)).collect();
+    let before: Vec<Option<usize>> = lines.iter().map(|line| world.closest_hit(line).map(|hit| hit.primitive_index)).collect();
+
+    world.build();
+
+    for (line, expected) in lines.iter().zip(before.iter()) {
+        assert_eq!(world.closest_hit(line).map(|hit| hit.primitive_index), *expected);
+    }
+}
+
+#[test]
+fn moved_objects_are_hit_where_they_are() {
+    let mut world = World::new();
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new(0.0, 0.0, -5.0), 1.0, 0)));
+    world.build();
+
+    let line = Line::new(Vector3D::new_as_zero(), Vector3D::new(0.0, 0.0, -1.0));
+    assert!(world.closest_hit(&line).is_some());
+
+    // Same number of objects, but the hierarchy was built for the old place.
+    world.get_objects_mut()[0] = Primitive::Sphere(Sphere::new(Vector3D::new(10.0, 0.0, -5.0), 1.0, 0));
+
+    assert!(world.closest_hit(&line).is_none());
+    assert!(!world.any_hit(&line, f32::MAX));
+
+    world.build();
+    assert!(world.closest_hit(&line).is_none());
+}
+
+#[test]
+fn build_accepts_non_finite_vertices() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut primitives = random_primitives(&mut rng);
+
+    // A broken mesh must not stop the build, the bad triangles are just never hit.
+    for index in 0..50 {
+        let a = Vector3D::new(f32::NAN, index as f32 * 0.01, 0.0);
+        let b = vec_sum(&a, &Vector3D::new(1.0, 0.0, 0.0));
+        let c = vec_sum(&a, &Vector3D::new(0.0, 1.0, 0.0));
+
+        primitives.push(Primitive::Triangle(Triangle::new(a, b, c, 1)));
+    }
+
+    let bvh = Bvh::build(&primitives);
+
+    for _ in 0..1000 {
+        let line = random_line(&mut rng);
+        let expected = brute_force_closest(&primitives, &line).map(|(_, index)| index);
+
+        assert_eq!(bvh.closest_hit(&primitives, &line).map(|hit| hit.primitive_index), expected);
+    }
+}
@@ -0,0 +1,48 @@
+extern crate raytracer;
+
+use raytracer::distribution::Distribution1D;
+
+#[test]
+fn indices_are_chosen_by_weight() {
+    let distribution = Distribution1D::new(&[1.0, 0.0, 3.0]);
+
+    assert_eq!(distribution.len(), 3);
+    assert_eq!(distribution.get_total(), 4.0);
+
+    assert_eq!(distribution.get_probability(0), 0.25);
+    assert_eq!(distribution.get_probability(1), 0.0);
+    assert_eq!(distribution.get_probability(2), 0.75);
+    assert_eq!(distribution.get_probability(3), 0.0);
+
+    assert_eq!(distribution.sample(0.0), Some((0, 0.25)));
+    assert_eq!(distribution.sample(0.2), Some((0, 0.25)));
+    assert_eq!(distribution.sample(0.25), Some((2, 0.75)));
+    assert_eq!(distribution.sample(0.99), Some((2, 0.75)));
+}
+
+#[test]
+fn empty_distributions_are_not_sampled() {
+    assert_eq!(Distribution1D::new(&[]).sample(0.5), None);
+
+    let zeros = Distribution1D::new(&[0.0, -1.0, f32::NAN]);
+    assert!(zeros.is_empty());
+    assert_eq!(zeros.sample(0.5), None);
+    assert_eq!(zeros.get_probability(0), 0.0);
+}
+
+#[test]
+fn trailing_zero_weights_are_never_sampled() {
+    let distribution = Distribution1D::new(&[0.1, 0.2, 0.7, 0.0, 0.0]);
+
+    // Rounding can bring u up to 1.
+    for u in [1.0 - f32::EPSILON, 1.0 - f32::EPSILON / 2.0, 1.0].iter() {
+        let (index, probability) = distribution.sample(*u).unwrap();
+
+        assert_eq!(index, 2);
+        assert!(probability > 0.0);
+    }
+
+    // The weights still add up to one.
+    let sum: f32 = (0..distribution.len()).map(|index| distribution.get_probability(index)).sum();
+    assert!((sum - 1.0).abs() < 1e-6);
+}
@@ -0,0 +1,282 @@
+extern crate raytracer;
+
+extern crate rand;
+
+use std::f32::consts::PI;
+use std::path::Path;
+
+use raytracer::{trace, World};
+use raytracer::color::Material;
+use raytracer::environment::{Environment, EnvironmentMap, Gradient};
+use raytracer::geometry::{Line, Sphere, Primitive};
+use raytracer::lights::Light;
+use raytracer::loaders::{load_image, parse_hdr, parse_exr, LoadErrorReason};
+use raytracer::output::{write_hdr, write_exr};
+use raytracer::renderer::{Film};
+use raytracer::vector3d::{Vector3D, vec_sum, vec_division};
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 4;
+
+/**
+ * Image with a different color on every pixel.
+ */
+fn new_image() -> Film {
+    let mut film = Film::new(WIDTH, HEIGHT);
+
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            film.set_pixel(x, y, Vector3D::new(0.1 + x as f32, 0.5 + y as f32 * 2.0, 0.25 * (x + y) as f32));
+        }
+    }
+
+    return film;
+}
+
+fn assert_close(value: f32, expected: f32, tolerance: f32) {
+    assert!((value - expected).abs() <= tolerance * expected.abs().max(1e-3), "{} {}", value, expected);
+}
+
+// ================================================
+// Images
+// ================================================
+
+#[test]
+fn hdr_images_are_read() {
+    let film = new_image();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_hdr(&film, &mut data).unwrap();
+
+    let image = parse_hdr(&data).unwrap();
+    assert_eq!((image.width, image.height), (WIDTH, HEIGHT));
+
+    // The shared exponent keeps 8 bits relative to the largest channel.
+    for (pixel, color) in image.pixels.iter().zip(film.pixels.iter()) {
+        let max = color.x.max(color.y).max(color.z);
+
+        assert!((pixel.x - color.x).abs() <= max / 128.0);
+        assert!((pixel.y - color.y).abs() <= max / 128.0);
+        assert!((pixel.z - color.z).abs() <= max / 128.0);
+    }
+}
+
+#[test]
+fn run_length_encoded_hdr_scanlines_are_read() {
+    let mut data: Vec<u8> = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+
+    data.extend_from_slice(&[2, 2, 0, 8]);
+    // Red: a run of 8 values.
+    data.extend_from_slice(&[128 + 8, 128]);
+    // Green: 4 literal values and a run of 4.
+    data.extend_from_slice(&[4, 0, 64, 128, 192, 128 + 4, 32]);
+    // Blue: zero.
+    data.extend_from_slice(&[128 + 8, 0]);
+    // Exponent: 2^(129 - 128).
+    data.extend_from_slice(&[128 + 8, 129]);
+
+    let image = parse_hdr(&data).unwrap();
+    assert_eq!((image.width, image.height), (8, 1));
+
+    let greens = [0.0, 0.5, 1.0, 1.5, 0.25, 0.25, 0.25, 0.25];
+    for (x, green) in greens.iter().enumerate() {
+        let pixel = image.get_pixel(x, 0);
+
+        assert_eq!((pixel.x, pixel.y, pixel.z), (1.0, *green, 0.0));
+    }
+
+    // Truncated scanline.
+    let error = parse_hdr(&data[..data.len() - 2]).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::BadImage);
+    assert_eq!(error.token, "scanline 1");
+}
+
+#[test]
+fn exr_images_are_read() {
+    let film = new_image();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_exr(&film, &mut data).unwrap();
+
+    let image = parse_exr(&data).unwrap();
+    assert_eq!((image.width, image.height), (WIDTH, HEIGHT));
+
+    for (pixel, color) in image.pixels.iter().zip(film.pixels.iter()) {
+        assert_eq!((pixel.x, pixel.y, pixel.z), (color.x, color.y, color.z));
+    }
+}
+
+#[test]
+fn bad_images_are_reported() {
+    let error = parse_hdr(b"P6\n2 2\n255\n").err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::BadImage);
+
+    let error = parse_hdr(b"#?RADIANCE\n\n+Y 2 +X 2\n").err().unwrap();
+    assert_eq!(error.token, "+Y 2 +X 2");
+
+    let error = load_image(Path::new("sky.tga"), true).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::UnsupportedFormat);
+    assert_eq!(error.to_string(), "sky.tga: 'tga' is not a supported format");
+
+    let error = load_image(Path::new("missing_sky.hdr"), true).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::UnreadableFile);
+}
+
+// ================================================
+// Environments
+// ================================================
+
+#[test]
+fn gradient_fades_from_the_horizon_to_the_zenith() {
+    let zenith = Vector3D::new(0.2, 0.4, 1.0);
+    let horizon = Vector3D::new(1.0, 1.0, 1.0);
+    let ground = Vector3D::new(0.3, 0.2, 0.1);
+    let environment = Environment::Gradient(Gradient::new(zenith, horizon, ground));
+
+    let up = environment.get_radiance(&Vector3D::new(0.0, 1.0, 0.0));
+    assert_eq!((up.x, up.y, up.z), (zenith.x, zenith.y, zenith.z));
+
+    let side = environment.get_radiance(&Vector3D::new(1.0, 0.0, 0.0));
+    assert_eq!((side.x, side.y, side.z), (horizon.x, horizon.y, horizon.z));
+
+    let down = environment.get_radiance(&Vector3D::new(0.0, -0.5, 0.866));
+    assert_eq!((down.x, down.y, down.z), (ground.x, ground.y, ground.z));
+
+    let middle = environment.get_radiance(&Vector3D::new(0.866, 0.5, 0.0));
+    assert_close(middle.x, 0.6, 1e-5);
+}
+
+#[test]
+fn map_is_centered_on_the_forward_direction_and_rotates() {
+    let image = new_image();
+    let center = image.get_pixel(WIDTH / 2, HEIGHT / 2);
+    let left = image.get_pixel(WIDTH / 4, HEIGHT / 2);
+
+    let map = EnvironmentMap::new(image.clone(), 0.0, 2.0);
+
+    let forward = map.get_radiance(&Vector3D::new(0.0, -0.1, -1.0));
+    assert_eq!((forward.x, forward.y, forward.z), (center.x * 2.0, center.y * 2.0, center.z * 2.0));
+
+    let side = map.get_radiance(&Vector3D::new(-1.0, -0.1, 0.0));
+    assert_eq!((side.x, side.y, side.z), (left.x * 2.0, left.y * 2.0, left.z * 2.0));
+
+    // A quarter turn brings the center of the image to the left.
+    let rotated = EnvironmentMap::new(image, PI / 2.0, 1.0);
+
+    let side = rotated.get_radiance(&Vector3D::new(-1.0, -0.1, 0.0));
+    assert_eq!((side.x, side.y, side.z), (center.x, center.y, center.z));
+}
+
+#[test]
+fn map_samples_follow_the_pdf() {
+    let map = EnvironmentMap::new(new_image(), 0.7, 1.0);
+    let position = Vector3D::new_as_zero();
+    let mut rng = rand::thread_rng();
+
+    let samples = 200000;
+    let mut sum = 0.0;
+    let mut mismatches = 0;
+    for _ in 0..samples {
+        let sample = map.sample(&position, &mut rng).unwrap();
+
+        let radiance = map.get_radiance(&sample.direction);
+        if (sample.pdf - map.pdf(&position, &sample.direction)).abs() > 1e-5 * sample.pdf || sample.radiance.y != radiance.y {
+            mismatches += 1;
+        }
+
+        sum += sample.radiance.y / sample.pdf;
+    }
+
+    // Directions right on a pixel edge can round to the neighbour pixel.
+    assert!(mismatches < samples / 1000, "{}", mismatches);
+
+    // Exact integral of the green channel over the sphere.
+    let mut expected = 0.0;
+    let image = new_image();
+    for y in 0..HEIGHT {
+        let solid_angle = 2.0 * PI / WIDTH as f32
+            * ((PI * y as f32 / HEIGHT as f32).cos() - (PI * (y + 1) as f32 / HEIGHT as f32).cos());
+
+        for x in 0..WIDTH {
+            expected += image.get_pixel(x, y).y * solid_angle;
+        }
+    }
+
+    assert_close(sum / samples as f32, expected, 0.01);
+    assert_close(map.get_average_radiance().y * 4.0 * PI, expected, 1e-4);
+}
+
+#[test]
+fn bright_pixels_are_sampled_more() {
+    let mut image = Film::new(WIDTH, HEIGHT);
+    for pixel in image.pixels.iter_mut() {
+        *pixel = Vector3D::new(0.1, 0.1, 0.1);
+    }
+    image.set_pixel(3, 1, Vector3D::new(1000.0, 1000.0, 1000.0));
+
+    let map = EnvironmentMap::new(image, 0.0, 1.0);
+    let mut rng = rand::thread_rng();
+
+    let samples = 1000;
+    let mut bright = 0;
+    for _ in 0..samples {
+        let sample = map.sample(&Vector3D::new_as_zero(), &mut rng).unwrap();
+
+        if sample.radiance.x > 100.0 {
+            bright += 1;
+        }
+    }
+
+    assert!(bright > samples * 9 / 10, "{}", bright);
+}
+
+#[test]
+fn black_environments_are_not_sampled() {
+    let mut rng = rand::thread_rng();
+    let position = Vector3D::new_as_zero();
+
+    let constant = Environment::Constant(Vector3D::new_as_zero());
+    assert!(constant.is_black());
+    assert!(constant.sample(&position, &mut rng).is_none());
+
+    let map = Environment::Map(EnvironmentMap::new(Film::new(WIDTH, HEIGHT), 0.0, 1.0));
+    assert!(map.is_black());
+    assert!(map.sample(&position, &mut rng).is_none());
+    assert_eq!(map.pdf(&position, &Vector3D::new(0.0, 1.0, 0.0)), 0.0);
+}
+
+#[test]
+fn white_furnace_with_a_map_is_unbiased() {
+    // Uniform map, so the diffuse sphere must look like the constant furnace.
+    let mut image = Film::new(WIDTH, HEIGHT);
+    for pixel in image.pixels.iter_mut() {
+        *pixel = Vector3D::new_as_one();
+    }
+
+    let material = Material::new(Vector3D::new(0.5, 0.5, 0.5), Vector3D::new_as_zero(), 1.0, 0.0);
+
+    let mut world = World::new();
+    world.materials = vec![material];
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 0)));
+    world.build();
+
+    let line = Line::new(Vector3D::new(0.0, 0.0, 4.0), Vector3D::new(0.0, 0.0, -1.0));
+    let paths = 20000;
+
+    let get_radiance = |world: &World| {
+        let mut sum = Vector3D::new_as_zero();
+        for _ in 0..paths {
+            sum = vec_sum(&sum, &trace(world, &line, 4).0);
+        }
+
+        return vec_division(&sum, paths as f32);
+    };
+
+    world.environment = Environment::Constant(Vector3D::new_as_one());
+    let constant = get_radiance(&world);
+
+    world.environment = Environment::Map(EnvironmentMap::new(image, 1.0, 1.0));
+    let map = get_radiance(&world);
+
+    assert_close(map.x, constant.x, 0.03);
+}
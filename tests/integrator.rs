@@ -0,0 +1,254 @@
+extern crate raytracer;
+
+use raytracer::{World, PointLight, SceneLight};
+use raytracer::bsdf::{Bsdf, MetallicRoughness};
+use raytracer::lights::DirectionalLight;
+use raytracer::geometry::{Line, Plane, Sphere, Triangle, Primitive};
+use raytracer::vector3d::{Vector3D, vec_sum, vec_normalize};
+use raytracer::color::Material;
+use raytracer::environment::Environment;
+use raytracer::integrator::{
+    Integrator, IntegratorKind,
+    PathTracer, Whitted, AmbientOcclusion, DebugMode
+};
+
+fn new_world() -> World {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new_base(Vector3D::new(0.8, 0.2, 0.2)),
+        Material::new_base(Vector3D::new(0.2, 0.8, 0.2))
+    ];
+    world.environment = Environment::Constant(Vector3D::new(0.5, 0.5, 0.5));
+
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 0)));
+    world.lights.push(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 0.0, 5.0), Vector3D::new_as_one(), 20.0)));
+    world.build();
+
+    return world;
+}
+
+fn new_center_line() -> Line {
+    return Line::new(Vector3D::new(0.0, 0.0, 5.0), Vector3D::new(0.0, 0.0, -1.0));
+}
+
+fn new_miss_line() -> Line {
+    return Line::new(Vector3D::new(0.0, 0.0, 5.0), Vector3D::new(0.0, 0.0, 1.0));
+}
+
+fn assert_color(color: &Vector3D, expected: (f32, f32, f32)) {
+    assert!((color.x - expected.0).abs() < 1e-4, "{} {} {}", color.x, color.y, color.z);
+    assert!((color.y - expected.1).abs() < 1e-4, "{} {} {}", color.x, color.y, color.z);
+    assert!((color.z - expected.2).abs() < 1e-4, "{} {} {}", color.x, color.y, color.z);
+}
+
+#[test]
+fn names_select_the_integrators() {
+    assert_eq!(IntegratorKind::from_name("path"), Some(IntegratorKind::Path(PathTracer)));
+    assert_eq!(IntegratorKind::from_name("Whitted"), Some(IntegratorKind::Whitted(Whitted)));
+    assert_eq!(IntegratorKind::from_name("heatmap"), Some(IntegratorKind::Debug(DebugMode::Bounces)));
+    assert_eq!(IntegratorKind::from_name("photon_mapping"), None);
+
+    match IntegratorKind::from_name("ao") {
+        Some(IntegratorKind::AmbientOcclusion(_)) => {},
+        _ => panic!("ao should be ambient occlusion")
+    }
+}
+
+#[test]
+fn debug_views_show_the_first_hit() {
+    let world = new_world();
+    let line = new_center_line();
+
+    let (normal, bounces) = DebugMode::Normals.radiance(&world, &line, 4);
+    assert_eq!(bounces, 1);
+    assert_color(&normal, (0.5, 0.5, 1.0));
+
+    // The sphere is 4 units away.
+    let (depth, _) = DebugMode::Depth.radiance(&world, &line, 4);
+    assert_color(&depth, (0.2, 0.2, 0.2));
+
+    let mut green_world = new_world();
+    green_world.get_objects_mut()[0] = Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 1));
+    green_world.build();
+
+    let (red_id, _) = DebugMode::MaterialId.radiance(&world, &line, 4);
+    let (green_id, _) = DebugMode::MaterialId.radiance(&green_world, &line, 4);
+    assert!(red_id.x != green_id.x || red_id.y != green_id.y || red_id.z != green_id.z);
+
+    let (miss, bounces) = DebugMode::Normals.radiance(&world, &new_miss_line(), 4);
+    assert_eq!(bounces, 0);
+    assert_color(&miss, (0.0, 0.0, 0.0));
+}
+
+#[test]
+fn barycentrics_are_the_vertex_weights() {
+    let mut world = new_world();
+    *world.get_objects_mut() = vec![Primitive::Triangle(Triangle::new(
+        Vector3D::new(-1.0, -1.0, 0.0),
+        Vector3D::new(1.0, -1.0, 0.0),
+        Vector3D::new(-1.0, 1.0, 0.0),
+        0
+    ))];
+    world.build();
+
+    // Straight on the a vertex.
+    let line = Line::new(Vector3D::new(-1.0, -1.0, 5.0), Vector3D::new(0.0, 0.0, -1.0));
+    let (color, _) = DebugMode::Barycentrics.radiance(&world, &line, 4);
+
+    assert!(color.x > 0.99 && color.y < 0.01 && color.z < 0.01);
+}
+
+#[test]
+fn ambient_occlusion_darkens_corners() {
+    let mut world = new_world();
+    *world.get_objects_mut() = vec![
+        Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)),
+        Primitive::Plane(Plane::new(Vector3D::new(1.0, 0.0, 0.0), Vector3D::new(-1.0, 0.0, 0.0), 0))
+    ];
+    world.build();
+
+    let ambient_occlusion = AmbientOcclusion::new(1.0, 256);
+
+    // Far from the wall the floor sees the whole sky.
+    let open_line = Line::new(Vector3D::new(5.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let (open, _) = ambient_occlusion.radiance(&world, &open_line, 4);
+    assert_color(&open, (1.0, 1.0, 1.0));
+
+    // Next to the wall half of the hemisphere is covered at most.
+    let corner_line = Line::new(Vector3D::new(-0.95, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let (corner, _) = ambient_occlusion.radiance(&world, &corner_line, 4);
+    assert!(corner.x > 0.3 && corner.x < 0.9, "{}", corner.x);
+
+    // The sky is open.
+    let (sky, bounces) = ambient_occlusion.radiance(&world, &new_miss_line(), 4);
+    assert_eq!(bounces, 0);
+    assert_color(&sky, (1.0, 1.0, 1.0));
+}
+
+#[test]
+fn whitted_is_noise_free() {
+    let mut world = new_world();
+    world.materials.push(Material::new_dielectric(1.5, Vector3D::new_as_one()));
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new(0.0, 0.0, 2.5), 0.5, 2)));
+    world.build();
+
+    let line = new_center_line();
+    let (first, bounces) = Whitted.radiance(&world, &line, 8);
+    let (second, _) = Whitted.radiance(&world, &line, 8);
+
+    // The glass and the sphere behind it.
+    assert!(bounces >= 3);
+    assert_color(&second, (first.x, first.y, first.z));
+
+    // The environment is seen through the clear glass without changes.
+    world.get_objects_mut().remove(0);
+    world.build();
+
+    let (through_glass, _) = Whitted.radiance(&world, &line, 8);
+    assert!((through_glass.x - 0.5).abs() < 0.01, "{}", through_glass.x);
+}
+
+#[test]
+fn bounce_heatmap_goes_from_blue_to_red() {
+    let world = new_world();
+
+    let (miss, _) = DebugMode::Bounces.radiance(&world, &new_miss_line(), 4);
+    assert_color(&miss, (0.0, 0.0, 1.0));
+
+    let (hit, bounces) = DebugMode::Bounces.radiance(&world, &new_center_line(), 4);
+    assert!(bounces >= 1);
+    assert!(hit.z < 1.0);
+}
+
+#[test]
+fn whitted_is_lit_by_emissive_objects() {
+    let mut world = World::new();
+    world.materials = vec![
+        Material::new_base(Vector3D::new(0.8, 0.8, 0.8)),
+        Material::new_light(Vector3D::new(10.0, 10.0, 10.0))
+    ];
+    world.environment = Environment::Constant(Vector3D::new_as_zero());
+
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new(0.0, 2.0, 0.0), 0.5, 1)));
+    world.build();
+
+    // The mirror direction misses the light, only the direct light is left.
+    let line = Line::new(Vector3D::new(-3.0, 2.0, 0.0), vec_normalize(&Vector3D::new(3.0, -2.0, 0.0)));
+
+    let samples = 4000;
+    let mut whitted = Vector3D::new_as_zero();
+    let mut path = Vector3D::new_as_zero();
+    for _ in 0..samples {
+        whitted = vec_sum(&whitted, &Whitted.radiance(&world, &line, 1).0);
+        path = vec_sum(&path, &PathTracer.radiance(&world, &line, 1).0);
+    }
+
+    // With a single bounce the path tracer only adds the direct light too.
+    let whitted = whitted.x / samples as f32;
+    let path = path.x / samples as f32;
+    assert!(whitted > 0.1, "{}", whitted);
+    assert!((whitted - path).abs() < 0.05 * path, "{} {}", whitted, path);
+}
+
+const UP: Vector3D = Vector3D { x: 0.0, y: 1.0, z: 0.0 };
+
+fn new_sun_floor_world(floor: Material, sun: DirectionalLight) -> World {
+    let mut world = World::new();
+
+    world.materials = vec![floor];
+    world.environment = Environment::Constant(Vector3D::new_as_zero());
+    world.add_object(Primitive::Plane(Plane::new(UP, Vector3D::new_as_zero(), 0)));
+    world.lights.push(SceneLight::Directional(sun));
+    world.build();
+
+    return world;
+}
+
+fn get_whitted_average(world: &World, line: &Line) -> f32 {
+    let samples = 20000;
+    let sum: f32 = (0..samples).map(|_| Whitted.radiance(world, line, 4).0.x).sum();
+
+    return sum / samples as f32;
+}
+
+#[test]
+fn whitted_floor_gets_the_sun_irradiance() {
+    // A big disk, the BSDF samples would take a good part of its light with the path tracer.
+    let angular_radius = 45f32.to_radians();
+    let irradiance = 3.0;
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), irradiance)
+        .with_angular_diameter(2.0 * angular_radius);
+
+    let floor = Material::new(Vector3D::new(0.8, 0.8, 0.8), Vector3D::new_as_zero(), 1.0, 0.0);
+    let world = new_sun_floor_world(floor, sun);
+
+    // Uniform radiance over a cone around the normal.
+    let floor_irradiance = irradiance * (1.0 + angular_radius.cos()) / 2.0;
+    let bsdf = MetallicRoughness::from_material(&floor);
+    let expected = bsdf.evaluate(&UP, &UP, &UP).x * floor_irradiance;
+
+    let line = Line::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let average = get_whitted_average(&world, &line);
+
+    assert!((average - expected).abs() < 0.03 * expected, "{} {}", average, expected);
+}
+
+#[test]
+fn whitted_mirror_does_not_count_the_sun_twice() {
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 3.0)
+        .with_angular_diameter(20f32.to_radians());
+
+    // Half of the mirror is left, the sun disk is already in the glossy highlight.
+    let floor = Material::new(Vector3D::new(0.9, 0.9, 0.9), Vector3D::new_as_zero(), 0.5, 1.0);
+    let world = new_sun_floor_world(floor, sun);
+
+    let line = Line::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let whitted = get_whitted_average(&world, &line);
+
+    let samples = 20000;
+    let path: f32 = (0..samples).map(|_| PathTracer.radiance(&world, &line, 1).0.x).sum::<f32>() / samples as f32;
+
+    assert!((whitted - path).abs() < 0.05 * path, "{} {}", whitted, path);
+}
@@ -0,0 +1,308 @@
+extern crate raytracer;
+
+extern crate rand;
+
+use raytracer::{World, PointLight, SceneLight};
+use raytracer::{trace};
+use raytracer::lights::{Light, AreaLight, DirectionalLight, SpotLight};
+use raytracer::geometry::{Line, Plane, Sphere, Triangle, Primitive};
+use raytracer::vector3d::{Vector3D, vec_normalize};
+use raytracer::color::Material;
+use raytracer::integrator::{Integrator, Whitted};
+
+#[test]
+fn irradiance_falls_with_the_square_of_the_distance() {
+    let light = PointLight::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), 8.0);
+
+    assert!((light.get_irradiance(1.0) - 8.0).abs() < 1e-5);
+    assert!((light.get_irradiance(2.0) - 2.0).abs() < 1e-5);
+    assert!((light.get_irradiance(4.0) - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn power_is_spread_over_the_sphere() {
+    let light = PointLight::new_with_power(Vector3D::new_as_zero(), Vector3D::new_as_one(), 100.0);
+
+    assert!((light.intensity - 100.0 / (4.0 * std::f32::consts::PI)).abs() < 1e-5);
+    assert!((light.get_power() - 100.0).abs() < 1e-3);
+}
+
+#[test]
+fn range_fades_the_light_out_smoothly() {
+    let light = PointLight::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), 1.0).with_range(10.0);
+
+    // Close to the light the window barely changes the inverse square law.
+    assert!((light.get_irradiance(1.0) - 1.0).abs() < 1e-3);
+
+    let mut previous = light.get_irradiance(1.0);
+    for step in 2..10 {
+        let irradiance = light.get_irradiance(step as f32);
+
+        assert!(irradiance < previous);
+        previous = irradiance;
+    }
+
+    assert_eq!(light.get_irradiance(10.0), 0.0);
+    assert_eq!(light.get_irradiance(12.0), 0.0);
+}
+
+/**
+ * Black environment around a floor lit from above, seen from the light position.
+ */
+fn floor_radiance(light: SceneLight, height: f32) -> Vector3D {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new(Vector3D::new(0.8, 0.8, 0.8), Vector3D::new_as_zero(), 1.0, 0.0)
+    ];
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+    world.lights.push(light);
+    world.build();
+
+    let line = Line::new(Vector3D::new(0.0, height, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let (color, _) = Whitted.radiance(&world, &line, 1);
+
+    return color;
+}
+
+#[test]
+fn lit_surfaces_follow_the_inverse_square_law() {
+    let near = floor_radiance(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_one(), 1.0)), 1.0);
+    let far = floor_radiance(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 2.0, 0.0), Vector3D::new_as_one(), 1.0)), 2.0);
+
+    assert!(far.x > 0.0);
+    assert!((near.x / far.x - 4.0).abs() < 1e-3, "{}", near.x / far.x);
+}
+
+#[test]
+fn surfaces_facing_away_are_not_lit() {
+    let below = PointLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 100.0);
+    let color = floor_radiance(SceneLight::Point(below), 1.0);
+    assert_eq!((color.x, color.y, color.z), (0.0, 0.0, 0.0));
+
+    let upwards = DirectionalLight::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_one(), 100.0);
+    let color = floor_radiance(SceneLight::Directional(upwards), 1.0);
+    assert_eq!((color.x, color.y, color.z), (0.0, 0.0, 0.0));
+}
+
+#[test]
+fn directional_light_does_not_depend_on_the_distance() {
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -2.0, 0.0), Vector3D::new_as_one(), 3.0);
+    let mut rng = rand::thread_rng();
+
+    let sample = sun.sample(&Vector3D::new(10.0, 0.0, -4.0), &mut rng).unwrap();
+    assert_eq!((sample.direction.x, sample.direction.y, sample.direction.z), (0.0, 1.0, 0.0));
+    assert_eq!(sample.radiance.x, 3.0);
+    assert!(sample.is_delta);
+
+    let near = floor_radiance(SceneLight::Directional(sun), 1.0);
+    let far = floor_radiance(SceneLight::Directional(sun), 100.0);
+    assert!(near.x > 0.0);
+    assert!((near.x - far.x).abs() < 1e-5);
+}
+
+#[test]
+fn directional_light_is_blocked_by_far_occluders() {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new_base(Vector3D::new(0.8, 0.8, 0.8))
+    ];
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+    world.build();
+
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 1.0);
+    let mut rng = rand::thread_rng();
+
+    let sample = sun.sample(&Vector3D::new(0.0, -1000.0, 0.0), &mut rng).unwrap();
+    assert!(!sample.is_visible(&world, &Vector3D::new(0.0, -1000.0, 0.0)));
+    assert!(sample.is_visible(&world, &Vector3D::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn spot_light_fades_between_the_cones() {
+    let spot = SpotLight::new(
+        Vector3D::new_as_zero(),
+        Vector3D::new(0.0, -1.0, 0.0),
+        Vector3D::new_as_one(),
+        1.0,
+        20.0f32.to_radians(),
+        40.0f32.to_radians()
+    );
+
+    let at_angle = |degrees: f32| {
+        let radians = degrees.to_radians();
+        return spot.get_cone_attenuation(&Vector3D::new(radians.sin(), -radians.cos(), 0.0));
+    };
+
+    assert_eq!(at_angle(0.0), 1.0);
+    assert_eq!(at_angle(19.0), 1.0);
+    assert!(at_angle(30.0) > 0.0 && at_angle(30.0) < 1.0);
+    assert!(at_angle(35.0) < at_angle(25.0));
+    assert_eq!(at_angle(41.0), 0.0);
+    assert_eq!(at_angle(180.0), 0.0);
+
+    // A higher exponent makes the edge sharper.
+    let sharp = spot.with_falloff(4.0);
+    let direction = Vector3D::new(30.0f32.to_radians().sin(), -30.0f32.to_radians().cos(), 0.0);
+    assert!(sharp.get_cone_attenuation(&direction) < spot.get_cone_attenuation(&direction));
+}
+
+#[test]
+fn spot_light_only_lights_inside_the_cone() {
+    let spot = SpotLight::new(
+        Vector3D::new(0.0, 1.0, 0.0),
+        Vector3D::new(0.0, -1.0, 0.0),
+        Vector3D::new_as_one(),
+        1.0,
+        10.0f32.to_radians(),
+        15.0f32.to_radians()
+    );
+    let mut rng = rand::thread_rng();
+
+    // Straight below it behaves like a point light.
+    let below = spot.sample(&Vector3D::new_as_zero(), &mut rng).unwrap();
+    assert!((below.radiance.x - 1.0).abs() < 1e-5);
+    assert!((below.distance - 1.0).abs() < 1e-5);
+
+    assert!(spot.sample(&Vector3D::new(1.0, 0.0, 0.0), &mut rng).is_none());
+
+    let color = floor_radiance(SceneLight::Spot(spot), 1.0);
+    let point = floor_radiance(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_one(), 1.0)), 1.0);
+    assert!((color.x - point.x).abs() < 1e-5);
+}
+
+// ================================================
+// Area lights
+// ================================================
+
+/**
+ * Checks that `pdf` agrees with the density of the samples, and that the
+ * samples cover the expected solid angle.
+ */
+fn check_area_light_pdf(light: &AreaLight, position: &Vector3D, solid_angle: Option<f32>) {
+    let mut rng = rand::thread_rng();
+    let samples = 20000;
+
+    let mut covered = 0.0;
+    for _ in 0..samples {
+        let sample = light.sample(position, &mut rng).unwrap();
+        let pdf = light.pdf(position, &sample.direction);
+
+        assert!(!sample.is_delta);
+        assert!((sample.pdf - pdf).abs() <= 1e-2 * pdf, "{} {}", sample.pdf, pdf);
+
+        covered += 1.0 / sample.pdf;
+    }
+
+    if let Some(solid_angle) = solid_angle {
+        covered /= samples as f32;
+        assert!((covered - solid_angle).abs() < 0.02 * solid_angle, "{} {}", covered, solid_angle);
+    }
+}
+
+#[test]
+fn sphere_lights_are_sampled_by_solid_angle() {
+    let sphere = Sphere::new(Vector3D::new(0.0, 0.0, -4.0), 1.0, 1);
+    let light = AreaLight::new(Primitive::Sphere(sphere), 0, Vector3D::new_as_one()).unwrap();
+
+    let cos_theta_max = (1.0f32 - 1.0 / 16.0).sqrt();
+    check_area_light_pdf(&light, &Vector3D::new_as_zero(), Some(2.0 * std::f32::consts::PI * (1.0 - cos_theta_max)));
+
+    // From the inside it covers every direction.
+    check_area_light_pdf(&light, &Vector3D::new(0.0, 0.3, -4.2), Some(4.0 * std::f32::consts::PI));
+}
+
+#[test]
+fn triangle_lights_are_sampled_by_area() {
+    let triangle = Triangle::new(
+        Vector3D::new(-1.0, 2.0, -1.0),
+        Vector3D::new(1.0, 2.0, -1.0),
+        Vector3D::new(0.0, 2.0, 1.0),
+        1
+    );
+    let light = AreaLight::new(Primitive::Triangle(triangle), 0, Vector3D::new_as_one()).unwrap();
+
+    assert!((light.get_area() - 2.0).abs() < 1e-5);
+    check_area_light_pdf(&light, &Vector3D::new(0.3, 0.0, 0.2), None);
+}
+
+/**
+ * Black environment, a floor, and an emissive sphere above it.
+ */
+fn new_area_light_world() -> World {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new(Vector3D::new(0.8, 0.8, 0.8), Vector3D::new_as_zero(), 1.0, 0.0),
+        Material::new_light(Vector3D::new(4.0, 4.0, 4.0))
+    ];
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new(0.0, 2.0, 0.0), 0.5, 1)));
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new(2.0, 2.0, 0.0), 0.5, 0)));
+
+    return world;
+}
+
+#[test]
+fn emissive_objects_become_area_lights() {
+    let mut world = new_area_light_world();
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new(0.0, 10.0, 0.0), 1)));
+    world.add_object(Primitive::Triangle(Triangle::new(
+        Vector3D::new(-1.0, 3.0, -1.0),
+        Vector3D::new(1.0, 3.0, -1.0),
+        Vector3D::new(0.0, 3.0, 1.0),
+        1
+    )));
+    world.build();
+
+    // The sphere and the triangle, the emissive plane is infinite.
+    assert_eq!(world.area_lights.len(), 2);
+    assert_eq!(world.area_lights[0].primitive_index, 1);
+    assert_eq!(world.area_lights[1].primitive_index, 4);
+
+    assert!(world.get_area_light(0).is_none());
+    assert!(world.get_area_light(3).is_none());
+
+    let (_, sphere_probability) = world.get_area_light(1).unwrap();
+    let (_, triangle_probability) = world.get_area_light(4).unwrap();
+    assert!((sphere_probability + triangle_probability - 1.0).abs() < 1e-5);
+
+    // Chosen by power, the sphere has a larger area.
+    assert!(sphere_probability > triangle_probability);
+}
+
+/**
+ * Mean and variance of the direct light on the floor below the light.
+ */
+fn floor_direct_light(world: &World, paths: u32) -> (f32, f32) {
+    let line = Line::new(Vector3D::new(0.0, 1.0, 3.0), vec_normalize(&Vector3D::new(0.0, -1.0, -3.0)));
+
+    let mut sum = 0.0;
+    let mut sum2 = 0.0;
+    for _ in 0..paths {
+        let (color, _) = trace(world, &line, 1);
+
+        sum += color.x;
+        sum2 += color.x * color.x;
+    }
+
+    let mean = sum / paths as f32;
+
+    return (mean, sum2 / paths as f32 - mean * mean);
+}
+
+#[test]
+fn area_light_sampling_converges_faster_to_the_same_light() {
+    // Without the build the light is only found by the BSDF samples.
+    let unregistered = new_area_light_world();
+    let mut registered = new_area_light_world();
+    registered.build();
+
+    let (bsdf_mean, bsdf_variance) = floor_direct_light(&unregistered, 100000);
+    let (mis_mean, mis_variance) = floor_direct_light(&registered, 10000);
+
+    assert!(mis_mean > 0.0);
+    assert!((bsdf_mean - mis_mean).abs() < 0.05 * mis_mean, "{} {}", bsdf_mean, mis_mean);
+    assert!(mis_variance * 4.0 < bsdf_variance, "{} {}", mis_variance, bsdf_variance);
+}
@@ -0,0 +1,464 @@
+extern crate raytracer;
+
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::fs;
+use std::path::PathBuf;
+
+use raytracer::loaders::{load_obj, load_obj_file, load_mtl, LoadErrorReason};
+use raytracer::geometry::{Mesh, Triangle, Line, Hit, Intersect};
+use raytracer::color::Material;
+use raytracer::texture::{Texture, SceneTexture};
+use raytracer::vector3d::{Vector3D, vec_sum, vec_sub, vec_multiplication, vec_cross, vec_dot, vec_normalize, vec_get_length};
+
+fn get_fixture_path(name: &str) -> PathBuf {
+    return [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "obj", name].iter().collect();
+}
+
+fn load_fixture(name: &str) -> Mesh {
+    return load_obj(fs::read_to_string(get_fixture_path(name)).unwrap(), 1).unwrap();
+}
+
+fn get_area(triangle: &Triangle) -> f32 {
+    let ab = vec_sub(&triangle.b, &triangle.a);
+    let ac = vec_sub(&triangle.c, &triangle.a);
+
+    return vec_get_length(&vec_cross(&ab, &ac)) * 0.5;
+}
+
+fn get_total_area(mesh: &Mesh) -> f32 {
+    return mesh.triangles.iter().map(get_area).sum();
+}
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+}
+
+fn assert_vec_close(a: &Vector3D, b: &Vector3D) {
+    assert!(vec_get_length(&vec_sub(a, b)) < 1e-4, "{} != {}", a.to_string(), b.to_string());
+}
+
+#[test]
+fn single_triangle() {
+    let mesh = load_fixture("triangle.obj");
+
+    assert_eq!(mesh.triangles.len(), 1);
+    assert_close(get_total_area(&mesh), 0.5);
+
+    // Counter clockwise faces look at the viewer.
+    assert_vec_close(&mesh.triangles[0].inner_plane.normal, &Vector3D::new(0.0, 0.0, 1.0));
+    assert_eq!(mesh.triangles[0].material_index, 1);
+}
+
+#[test]
+fn quad_is_split_in_two_triangles() {
+    let mesh = load_fixture("quad.obj");
+
+    assert_eq!(mesh.triangles.len(), 2);
+    assert_close(get_total_area(&mesh), 1.0);
+
+    for triangle in mesh.triangles.iter() {
+        assert_vec_close(&triangle.inner_plane.normal, &Vector3D::new(0.0, 0.0, 1.0));
+
+        let normals = triangle.normals.unwrap();
+        let uvs = triangle.uvs.unwrap();
+
+        let vertices = [triangle.a, triangle.b, triangle.c];
+        for i in 0..3 {
+            assert_vec_close(&normals[i], &Vector3D::new(0.0, 0.0, 1.0));
+
+            // The quad uvs match the vertex positions.
+            assert_close(uvs[i].0, vertices[i].x);
+            assert_close(uvs[i].1, vertices[i].y);
+        }
+    }
+}
+
+#[test]
+fn concave_polygon_keeps_its_area() {
+    for name in ["concave.obj", "concave_reflex_first.obj"].iter() {
+        let mesh = load_fixture(name);
+
+        assert_eq!(mesh.triangles.len(), 4);
+        assert_close(get_total_area(&mesh), 3.0);
+
+        for triangle in mesh.triangles.iter() {
+            assert_vec_close(&triangle.inner_plane.normal, &Vector3D::new(0.0, 0.0, 1.0));
+        }
+    }
+}
+
+#[test]
+fn tilted_polygon() {
+    let mesh = load_fixture("polygon.obj");
+
+    assert_eq!(mesh.triangles.len(), 6);
+    assert_close(get_total_area(&mesh), 2.0 * 2.0f32.sqrt());
+}
+
+#[test]
+fn negative_indices_are_relative() {
+    let mesh = load_fixture("negative_indices.obj");
+
+    assert_eq!(mesh.triangles.len(), 3);
+    assert_close(get_total_area(&mesh), 1.5);
+
+    assert!(mesh.triangles[0].normals.is_some());
+    assert!(mesh.triangles[2].normals.is_none());
+
+    let last = &mesh.triangles[2];
+    assert_close(last.a.z, 1.0);
+    assert_close(last.b.z, 1.0);
+    assert_close(last.c.z, 1.0);
+}
+
+#[test]
+fn normal_without_texture_indices() {
+    let mesh = load_fixture("normals_only.obj");
+
+    assert_eq!(mesh.triangles.len(), 1);
+    assert!(mesh.triangles[0].normals.is_some());
+    assert!(mesh.triangles[0].uvs.is_none());
+}
+
+#[test]
+fn texture_without_normal_indices() {
+    let mesh = load_fixture("texture_only.obj");
+
+    assert_eq!(mesh.triangles.len(), 1);
+    assert!(mesh.triangles[0].normals.is_none());
+    assert!(mesh.triangles[0].uvs.is_some());
+}
+
+/**
+ * Hits the triangle from above at the point with weights 0.25, 0.25 and 0.5 for the
+ * first, second and third vertex of the face.
+ */
+fn hit_from_above(triangle: &Triangle) -> Hit {
+    let line = Line::new(Vector3D::new(0.25, 0.5, 1.0), Vector3D::new(0.0, 0.0, -1.0));
+
+    return triangle.intersects(&line).unwrap();
+}
+
+fn mix_normals(normals: [Vector3D; 3]) -> Vector3D {
+    return vec_normalize(&vec_sum(
+        &vec_sum(&vec_multiplication(&normals[0], 0.25), &vec_multiplication(&normals[1], 0.25)),
+        &vec_multiplication(&normals[2], 0.5)
+    ));
+}
+
+#[test]
+fn normals_and_uvs_are_interpolated() {
+    let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+        vt 0.2 0.2\nvt 0.8 0.2\nvt 0.2 0.8\n\
+        vn 0 0 1\nvn 1 0 1\nvn 0 1 1\n\
+        f 1/1/1 2/2/2 3/3/3\n";
+
+    let mesh = load_obj(source.to_string(), 0).unwrap();
+    let triangle = &mesh.triangles[0];
+    assert!(triangle.normals.is_some());
+    assert!(triangle.uvs.is_some());
+
+    let hit = hit_from_above(triangle);
+
+    let expected = mix_normals([
+        Vector3D::new(0.0, 0.0, 1.0),
+        vec_normalize(&Vector3D::new(1.0, 0.0, 1.0)),
+        vec_normalize(&Vector3D::new(0.0, 1.0, 1.0))
+    ]);
+    assert_vec_close(&hit.shading_normal, &expected);
+
+    // The geometric normal stays flat.
+    assert_vec_close(&hit.normal, &Vector3D::new(0.0, 0.0, 1.0));
+
+    assert_close(hit.uv.0, 0.25 * 0.2 + 0.25 * 0.8 + 0.5 * 0.2);
+    assert_close(hit.uv.1, 0.25 * 0.2 + 0.25 * 0.2 + 0.5 * 0.8);
+}
+
+#[test]
+fn normals_follow_their_own_indices() {
+    let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+        vn 0 1 1\nvn 0 0 1\nvn 1 0 1\n\
+        f 1//2 2//3 3//1\n";
+
+    let mesh = load_obj(source.to_string(), 0).unwrap();
+    let triangle = &mesh.triangles[0];
+    assert!(triangle.normals.is_some());
+    assert!(triangle.uvs.is_none());
+
+    let hit = hit_from_above(triangle);
+
+    let expected = mix_normals([
+        Vector3D::new(0.0, 0.0, 1.0),
+        vec_normalize(&Vector3D::new(1.0, 0.0, 1.0)),
+        vec_normalize(&Vector3D::new(0.0, 1.0, 1.0))
+    ]);
+    assert_vec_close(&hit.shading_normal, &expected);
+}
+
+#[test]
+fn zero_normals_use_the_face_normal() {
+    let mesh = load_fixture("zero_normals.obj");
+    let triangle = &mesh.triangles[0];
+
+    for normal in triangle.normals.unwrap().iter() {
+        assert!(normal.x.is_finite() && normal.y.is_finite() && normal.z.is_finite());
+        assert_close(vec_get_length(normal), 1.0);
+    }
+
+    let hit = hit_from_above(triangle);
+
+    let face_normal = Vector3D::new(0.0, 0.0, 1.0);
+    let expected = mix_normals([face_normal, vec_normalize(&Vector3D::new(1.0, 0.0, 1.0)), face_normal]);
+    assert_vec_close(&hit.shading_normal, &expected);
+
+    let tangents = triangle.tangents.unwrap();
+    assert!(tangents.iter().all(|tangent| tangent.x.is_finite() && tangent.y.is_finite() && tangent.z.is_finite()));
+}
+
+#[test]
+fn whitespace_comments_and_crlf() {
+    let mesh = load_fixture("messy_whitespace.obj");
+
+    assert_eq!(mesh.triangles.len(), 2);
+    assert_close(get_total_area(&mesh), 4.0);
+}
+
+fn assert_error(source: &str, line: usize, token: &str, reason: LoadErrorReason) {
+    match load_obj(source.to_string(), 1) {
+        Ok(_) => panic!("The source should fail to load"),
+        Err(error) => {
+            assert_eq!(error.line, line);
+            assert_eq!(error.token, token);
+            assert_eq!(error.reason, reason);
+        }
+    }
+}
+
+#[test]
+fn bad_float_is_reported() {
+    assert_error("v 0 0 0\nv 1 zero 0\n", 2, "zero", LoadErrorReason::BadFloat);
+    assert_error("# normals\nvn 0 1 0.5.1\n", 2, "0.5.1", LoadErrorReason::BadFloat);
+}
+
+#[test]
+fn missing_value_is_reported() {
+    assert_error("v 0 0\n", 1, "v", LoadErrorReason::MissingValue);
+}
+
+#[test]
+fn bad_index_is_reported() {
+    assert_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 two 3\n", 4, "two", LoadErrorReason::BadIndex);
+    assert_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n", 4, "0", LoadErrorReason::BadIndex);
+}
+
+#[test]
+fn index_out_of_range_is_reported() {
+    assert_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4, "4", LoadErrorReason::IndexOutOfRange);
+    assert_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n", 4, "-4", LoadErrorReason::IndexOutOfRange);
+    assert_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//2 3//1\n", 5, "2", LoadErrorReason::IndexOutOfRange);
+}
+
+#[test]
+fn too_few_vertices_is_reported() {
+    assert_error("v 0 0 0\nv 1 0 0\n\nf 1 2\n", 4, "f 1 2", LoadErrorReason::TooFewVertices);
+}
+
+#[test]
+fn error_message_has_the_line_number() {
+    let error = load_obj("v 0 0 0\nv 1 x 0\n".to_string(), 1).err().unwrap();
+
+    assert_eq!(error.to_string(), "line 2: 'x' is not a valid number");
+}
+
+#[test]
+fn material_library_is_appended_to_the_materials() {
+    let mut materials = vec![
+        Material::new_light(Vector3D::new_as_zero()),
+        Material::new_base(Vector3D::new_as_one())
+    ];
+
+    let mut textures: Vec<SceneTexture> = Vec::new();
+    let mesh = load_obj_file(&get_fixture_path("two_materials.obj"), &mut materials, &mut textures, 1).unwrap();
+
+    assert_eq!(materials.len(), 5);
+
+    let red = &materials[2];
+    assert_vec_close(&red.base_color, &Vector3D::new(0.8, 0.1, 0.1));
+    assert_close(red.roughness, 0.1414213);
+    assert_close(red.metalic, 0.0);
+
+    let gold = &materials[3];
+    assert_close(gold.roughness, 0.2);
+    assert_close(gold.metalic, 1.0);
+
+    // The diffuse map is decoded from sRGB and multiplied by the diffuse color.
+    assert_eq!(textures.len(), 1);
+    assert_eq!(gold.base_color_texture, Some(0));
+    assert_eq!(red.base_color_texture, None);
+
+    let texel = textures[0].evaluate((0.5, 0.5), &Vector3D::new_as_zero());
+    assert_vec_close(&texel, &Vector3D::new(1.0, 0.57758, 0.04519));
+
+    let lamp = &materials[4];
+    assert_vec_close(&lamp.base_color, &Vector3D::new_as_one());
+    assert_vec_close(&lamp.emision_color, &Vector3D::new(4.0, 4.0, 3.5));
+
+    let material_indices: Vec<usize> = mesh.triangles.iter().map(|triangle| triangle.material_index).collect();
+    assert_eq!(material_indices, vec![1, 2, 2, 3, 4, 1]);
+}
+
+#[test]
+fn missing_material_library_is_reported() {
+    let mut materials: Vec<Material> = Vec::new();
+    let error = load_obj_file(&get_fixture_path("missing_library.obj"), &mut materials, &mut Vec::new(), 0).err().unwrap();
+
+    assert_eq!(error.reason, LoadErrorReason::UnreadableFile);
+    assert!(error.token.ends_with("not_there.mtl"));
+}
+
+#[test]
+fn material_library_errors_point_to_the_library() {
+    let mut materials: Vec<Material> = Vec::new();
+    let error = load_obj_file(&get_fixture_path("broken_library.obj"), &mut materials, &mut Vec::new(), 0).err().unwrap();
+
+    assert_eq!(error.reason, LoadErrorReason::BadFloat);
+    assert_eq!(error.line, 2);
+    assert_eq!(error.token, "half");
+    assert!(error.file.unwrap().ends_with("broken.mtl"));
+}
+
+#[test]
+fn material_library_keeps_the_raw_parameters() {
+    let library = load_mtl(fs::read_to_string(get_fixture_path("two_materials.mtl")).unwrap()).unwrap();
+
+    assert_eq!(library.len(), 3);
+    assert_eq!(library[0].name, "Red Paint");
+    assert_eq!(library[0].optical_density, Some(1.45));
+    assert_eq!(library[1].diffuse_map, Some("gold.png".to_string()));
+}
+
+#[test]
+fn dissolved_materials_are_cut_out() {
+    let source = "newmtl Faded\nKd 1 1 1\nd 0.25\nNi 1.0\n\nnewmtl Hidden\nTr 1.0\n\nnewmtl Paint\nKd 0.5 0.5 0.5\n";
+    let library = load_mtl(source.to_string()).unwrap();
+
+    // The dissolve is the coverage, the surface doesn't refract.
+    let faded = library[0].to_material();
+    assert_close(faded.opacity, 0.25);
+    assert_close(faded.transmission, 0.0);
+
+    let hidden = library[1].to_material();
+    assert_close(hidden.opacity, 0.0);
+    assert_close(hidden.transmission, 0.0);
+
+    let paint = library[2].to_material();
+    assert_close(paint.opacity, 1.0);
+    assert_close(paint.transmission, 0.0);
+}
+
+#[test]
+fn transmission_filter_makes_refracting_materials() {
+    let source = "newmtl Glass\nKd 1 1 1\nTf 1 1 1\nNi 1.5\nillum 7\n\n\
+        newmtl Tinted\nTf 0.9 0.6 0.3\nNi 1.33\nillum 4\n\n\
+        newmtl Exported\nKd 0.5 0.5 0.5\nTf 1 1 1\nNi 1.0\nillum 2\n";
+    let library = load_mtl(source.to_string()).unwrap();
+
+    let glass = library[0].to_material();
+    assert_close(glass.transmission, 1.0);
+    assert_close(glass.ior, 1.5);
+    assert_close(glass.opacity, 1.0);
+
+    let tinted = library[1].to_material();
+    assert_close(tinted.transmission, 0.6);
+    assert_close(tinted.ior, 1.33);
+
+    // Without a refracting illumination model the filter is ignored.
+    let exported = library[2].to_material();
+    assert_close(exported.transmission, 0.0);
+}
+
+#[test]
+fn glass_without_optical_density_refracts() {
+    let library = load_mtl(fs::read_to_string(get_fixture_path("glass.mtl")).unwrap()).unwrap();
+    assert_eq!(library[0].optical_density, None);
+
+    let glass = library[0].to_material();
+    assert_close(glass.transmission, 1.0);
+    assert_close(glass.ior, 1.5);
+}
+
+#[test]
+fn tangents_follow_the_texture_coordinates() {
+    let mesh = load_fixture("quad.obj");
+
+    for triangle in mesh.triangles.iter() {
+        let (dpdu, dpdv) = triangle.get_uv_derivatives().unwrap();
+        assert_vec_close(&dpdu, &Vector3D::new(1.0, 0.0, 0.0));
+        assert_vec_close(&dpdv, &Vector3D::new(0.0, 1.0, 0.0));
+
+        for tangent in triangle.tangents.unwrap().iter() {
+            assert_vec_close(tangent, &Vector3D::new(1.0, 0.0, 0.0));
+        }
+    }
+
+    // Without uvs there's nothing to follow.
+    assert!(load_fixture("normals_only.obj").triangles.iter().all(|triangle| triangle.tangents.is_none()));
+}
+
+#[test]
+fn tangents_are_shared_by_the_faces() {
+    // Two faces folded along the shared edge at x = 1, smooth normals on the edge.
+    let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 2 0 -1\nv 2 1 -1\n\
+        vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvt 2 0\nvt 2 1\n\
+        vn 0 0 1\nvn 0.3827 0 0.9239\nvn 0.7071 0 0.7071\n\
+        f 1/1/1 2/2/2 3/3/2 4/4/1\nf 2/2/2 5/5/3 6/6/3 3/3/2\n";
+
+    let mesh = load_obj(source.to_string(), 0).unwrap();
+    assert_eq!(mesh.triangles.len(), 4);
+
+    // On the edge the tangent is the average, bent around the smooth normal.
+    let edge_tangent = vec_normalize(&Vector3D::new(1.0 + FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2));
+    let mut edge_vertices = 0;
+
+    for triangle in mesh.triangles.iter() {
+        let tangents = triangle.tangents.unwrap();
+        let normals = triangle.normals.unwrap();
+
+        for (i, vertex) in [triangle.a, triangle.b, triangle.c].iter().enumerate() {
+            assert_close(vec_dot(&tangents[i], &normals[i]), 0.0);
+
+            if (vertex.x - 1.0).abs() < 1e-6 {
+                assert_vec_close(&tangents[i], &edge_tangent);
+                edge_vertices += 1;
+            }
+        }
+    }
+
+    assert!(edge_vertices >= 4);
+
+    // Mirrored halves keep their own tangents on the shared vertices.
+    let mirrored = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 2 0 0\nv 2 1 0\nv 0 1 0\n\
+        vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+        f 1/1 2/2 3/3 6/4\nf 2/2 4/1 5/4 3/3\n";
+
+    let mesh = load_obj(mirrored.to_string(), 0).unwrap();
+    for triangle in mesh.triangles.iter() {
+        let expected = if triangle.a.x + triangle.b.x + triangle.c.x > 3.0 { -1.0 } else { 1.0 };
+
+        for tangent in triangle.tangents.unwrap().iter() {
+            assert_vec_close(tangent, &Vector3D::new(expected, 0.0, 0.0));
+        }
+    }
+}
+
+#[test]
+fn material_library_maps_are_loaded() {
+    let library = load_mtl("newmtl Brick\nnorm brick_normal.png\nbump -bm 0.25 brick_height.png\n".to_string()).unwrap();
+
+    assert_eq!(library[0].normal_map, Some("brick_normal.png".to_string()));
+    assert_eq!(library[0].bump_map, Some("brick_height.png".to_string()));
+    assert_close(library[0].bump_multiplier, 0.25);
+
+    let error = load_mtl("newmtl Brick\nbump -bm\n".to_string()).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::MissingValue);
+}
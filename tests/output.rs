@@ -0,0 +1,254 @@
+extern crate raytracer;
+extern crate bmp;
+extern crate png;
+extern crate exr;
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use raytracer::renderer::{Film};
+use raytracer::vector3d::{Vector3D};
+use raytracer::output::{
+    ImageFormat, channel_to_u8, channel_to_u16, color_to_bytes, color_to_u16s,
+    write_bmp, write_png, write_ppm, write_pfm, write_hdr, save_image
+};
+
+const WIDTH: usize = 3;
+const HEIGHT: usize = 2;
+
+/**
+ * Small film with in range, out of range and NaN values.
+ */
+fn new_film() -> Film {
+    let mut film = Film::new(WIDTH, HEIGHT);
+
+    film.set_pixel(0, 0, Vector3D::new(0.0, 0.5, 1.0));
+    film.set_pixel(1, 0, Vector3D::new(-1.0, 2.0, f32::NAN));
+    film.set_pixel(2, 0, Vector3D::new(0.1, 0.2, 0.3));
+    film.set_pixel(0, 1, Vector3D::new(0.999, 0.001, 0.25));
+    film.set_pixel(1, 1, Vector3D::new(1.0, 1.0, 1.0));
+    film.set_pixel(2, 1, Vector3D::new(0.75, 0.0, 0.6));
+
+    return film;
+}
+
+fn expected_bytes(film: &Film) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    for color in film.pixels.iter() {
+        bytes.extend_from_slice(&color_to_bytes(color));
+    }
+
+    return bytes;
+}
+
+fn decode_png(data: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+    let decoder = png::Decoder::new(Cursor::new(data));
+    let mut reader = decoder.read_info().unwrap();
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+    buffer.truncate(info.buffer_size());
+
+    return (info, buffer);
+}
+
+/**
+ * Film with values above one, the float formats must keep them.
+ */
+fn new_hdr_film() -> Film {
+    let mut film = new_film();
+    film.set_pixel(1, 0, Vector3D::new(0.0, 2.0, 40.5));
+
+    return film;
+}
+
+#[test]
+fn low_dynamic_range_output_is_srgb_encoded() {
+    assert_eq!(color_to_bytes(&Vector3D::new(0.0, 1.0, 0.5)), [0, 255, 188]);
+    assert_eq!(color_to_bytes(&Vector3D::new(-1.0, 5.0, f32::NAN)), [0, 255, 0]);
+    assert_eq!(color_to_u16s(&Vector3D::new(0.0, 1.0, 8.0)), [0, 65535, 65535]);
+}
+
+#[test]
+fn channels_are_rounded_and_clamped() {
+    assert_eq!(channel_to_u8(0.0), 0);
+    assert_eq!(channel_to_u8(1.0), 255);
+    assert_eq!(channel_to_u8(0.5), 128);
+    assert_eq!(channel_to_u8(0.999), 255);
+    assert_eq!(channel_to_u8(0.001), 0);
+    assert_eq!(channel_to_u8(-1.0), 0);
+    assert_eq!(channel_to_u8(2.0), 255);
+    assert_eq!(channel_to_u8(f32::NAN), 0);
+
+    assert_eq!(channel_to_u16(1.0), 65535);
+    assert_eq!(channel_to_u16(0.5), 32768);
+    assert_eq!(channel_to_u16(2.0), 65535);
+    assert_eq!(channel_to_u16(f32::NAN), 0);
+}
+
+#[test]
+fn format_comes_from_the_extension() {
+    assert_eq!(ImageFormat::from_path(Path::new("a.bmp")), Some(ImageFormat::Bmp));
+    assert_eq!(ImageFormat::from_path(Path::new("a.PNG")), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::from_path(Path::new("dir/a.ppm")), Some(ImageFormat::Ppm));
+    assert_eq!(ImageFormat::from_path(Path::new("a.pfm")), Some(ImageFormat::Pfm));
+    assert_eq!(ImageFormat::from_path(Path::new("a.exr")), Some(ImageFormat::Exr));
+    assert_eq!(ImageFormat::from_path(Path::new("a.hdr")), Some(ImageFormat::Hdr));
+    assert_eq!(ImageFormat::from_path(Path::new("a.jpg")), None);
+    assert_eq!(ImageFormat::from_path(Path::new("result")), None);
+}
+
+#[test]
+fn png_8_bit_round_trip() {
+    let film = new_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_png(&film, &mut data, false).unwrap();
+
+    let (info, pixels) = decode_png(&data);
+    assert_eq!(info.width as usize, WIDTH);
+    assert_eq!(info.height as usize, HEIGHT);
+    assert_eq!(info.bit_depth, png::BitDepth::Eight);
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+
+    assert_eq!(pixels, expected_bytes(&film));
+}
+
+#[test]
+fn png_16_bit_round_trip() {
+    let film = new_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_png(&film, &mut data, true).unwrap();
+
+    let (info, pixels) = decode_png(&data);
+    assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+
+    for (index, color) in film.pixels.iter().enumerate() {
+        let expected = color_to_u16s(color);
+
+        for (c, channel) in expected.iter().enumerate() {
+            let offset = (index * 3 + c) * 2;
+            let value = u16::from_be_bytes([pixels[offset], pixels[offset + 1]]);
+
+            assert_eq!(value, *channel);
+        }
+    }
+}
+
+#[test]
+fn ppm_round_trip() {
+    let film = new_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_ppm(&film, &mut data).unwrap();
+
+    let header = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT);
+    assert!(data.starts_with(header.as_bytes()));
+    assert_eq!(&data[header.len()..], &expected_bytes(&film)[..]);
+}
+
+#[test]
+fn pfm_round_trip_keeps_the_values() {
+    let film = new_hdr_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_pfm(&film, &mut data).unwrap();
+
+    let header = format!("PF\n{} {}\n-1.0\n", WIDTH, HEIGHT);
+    assert!(data.starts_with(header.as_bytes()));
+
+    let floats: Vec<f32> = data[header.len()..]
+        .chunks(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    assert_eq!(floats.len(), WIDTH * HEIGHT * 3);
+
+    // The rows are stored from the bottom.
+    for j in 0..HEIGHT {
+        for i in 0..WIDTH {
+            let offset = ((HEIGHT - 1 - j) * WIDTH + i) * 3;
+            let color = film.get_pixel(i, j);
+
+            assert_eq!(floats[offset], color.x);
+            assert_eq!(floats[offset + 1], color.y);
+            assert_eq!(floats[offset + 2], color.z);
+        }
+    }
+}
+
+#[test]
+fn bmp_round_trip() {
+    let film = new_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_bmp(&film, &mut data).unwrap();
+
+    let image = bmp::from_reader(&mut Cursor::new(data)).unwrap();
+    assert_eq!(image.get_width() as usize, WIDTH);
+    assert_eq!(image.get_height() as usize, HEIGHT);
+
+    let expected = expected_bytes(&film);
+    for j in 0..HEIGHT {
+        for i in 0..WIDTH {
+            let pixel = image.get_pixel(i as u32, j as u32);
+            let offset = (j * WIDTH + i) * 3;
+
+            assert_eq!([pixel.r, pixel.g, pixel.b], [expected[offset], expected[offset + 1], expected[offset + 2]]);
+        }
+    }
+}
+
+#[test]
+fn hdr_round_trip_keeps_the_values() {
+    let film = new_hdr_film();
+
+    let mut data: Vec<u8> = Vec::new();
+    write_hdr(&film, &mut data).unwrap();
+
+    let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", HEIGHT, WIDTH);
+    assert!(data.starts_with(header.as_bytes()));
+
+    let pixels = &data[header.len()..];
+    assert_eq!(pixels.len(), WIDTH * HEIGHT * 4);
+
+    for (index, color) in film.pixels.iter().enumerate() {
+        let rgbe = &pixels[index * 4..index * 4 + 4];
+
+        let scale = if rgbe[3] == 0 { 0.0 } else { 2.0f32.powi(rgbe[3] as i32 - 136) };
+        let decoded = [rgbe[0] as f32 * scale, rgbe[1] as f32 * scale, rgbe[2] as f32 * scale];
+
+        // The shared exponent keeps 8 bits relative to the largest channel.
+        let max = color.x.max(color.y).max(color.z);
+        for (value, expected) in decoded.iter().zip([color.x, color.y, color.z].iter()) {
+            assert!((value - expected.max(0.0)).abs() <= max / 128.0);
+        }
+    }
+}
+
+#[test]
+fn exr_round_trip_keeps_the_values() {
+    let film = new_hdr_film();
+
+    let path = env::temp_dir().join(format!("raytracer_output_{}.exr", std::process::id()));
+    save_image(&film, &path).unwrap();
+
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        &path,
+        |resolution, _| vec![(0.0f32, 0.0f32, 0.0f32); resolution.width() * resolution.height()],
+        |pixels, position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * WIDTH + position.x()] = (r, g, b);
+        }
+    );
+    fs::remove_file(&path).unwrap();
+
+    let pixels = image.unwrap().layer_data.channel_data.pixels;
+    assert_eq!(pixels.len(), WIDTH * HEIGHT);
+
+    for (pixel, color) in pixels.iter().zip(film.pixels.iter()) {
+        assert_eq!(*pixel, (color.x, color.y, color.z));
+    }
+}
@@ -0,0 +1,80 @@
+extern crate raytracer;
+
+use raytracer::{World, PointLight, SceneLight, RenderSettings};
+use raytracer::geometry::{Sphere, Primitive};
+use raytracer::vector3d::{Vector3D};
+use raytracer::color::Material;
+use raytracer::environment::Environment;
+use raytracer::camera::Camera;
+use raytracer::renderer::{Renderer};
+
+fn new_renderer(width: usize, height: usize) -> Renderer {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new_base(Vector3D::new(0.8, 0.2, 0.2))
+    ];
+    world.environment = Environment::Constant(Vector3D::new(0.1, 0.1, 0.15));
+
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 0)));
+    world.lights.push(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 5.0, 5.0), Vector3D::new_as_one(), 20.0)));
+    world.build();
+
+    let camera = Camera::new(Vector3D::new(0.0, 0.0, 6.0), Vector3D::new_as_zero(), 2.0);
+
+    let mut settings = RenderSettings::new(width, height, 2, 4);
+    settings.threads = 2;
+    settings.tile_size = 8;
+
+    return Renderer::new(world, camera, settings);
+}
+
+#[test]
+fn every_tile_is_reported_once() {
+    let renderer = new_renderer(20, 12);
+
+    let mut covered = vec![0; 20 * 12];
+    let mut calls = 0;
+    let mut last_progress = None;
+
+    let (film, stats) = renderer.render(|tile, progress| {
+        assert_eq!(tile.pixels.len(), tile.width * tile.height);
+
+        for j in tile.y..tile.y + tile.height {
+            for i in tile.x..tile.x + tile.width {
+                covered[j * 20 + i] += 1;
+            }
+        }
+
+        calls += 1;
+        last_progress = Some(*progress);
+    });
+
+    // 3 x 2 tiles of 8 pixels, the last ones are cut by the image border.
+    let progress = last_progress.unwrap();
+    assert_eq!(calls, 6);
+    assert_eq!(progress.finished_tiles, 6);
+    assert_eq!(progress.total_tiles, 6);
+
+    assert!(covered.iter().all(|count| *count == 1));
+
+    assert_eq!(film.width, 20);
+    assert_eq!(film.height, 12);
+    assert_eq!(film.pixels.len(), 20 * 12);
+
+    assert!(!stats.cancelled);
+    assert!(stats.rays > 0);
+}
+
+#[test]
+fn cancelled_render_stops_early() {
+    let renderer = new_renderer(64, 64);
+    renderer.get_cancel_handle().cancel();
+
+    let mut calls = 0;
+    let (film, stats) = renderer.render(|_, _| { calls += 1; });
+
+    assert_eq!(calls, 0);
+    assert!(stats.cancelled);
+    assert_eq!(film.pixels.len(), 64 * 64);
+}
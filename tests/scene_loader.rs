@@ -0,0 +1,554 @@
+extern crate raytracer;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use raytracer::SceneLight;
+use raytracer::loaders::{parse_scene, SceneError, LoadErrorReason};
+use raytracer::geometry::Primitive;
+use raytracer::vector3d::Vector3D;
+use raytracer::color::ToneMapOperator;
+use raytracer::integrator::{IntegratorKind, DebugMode, PathTracer, AmbientOcclusion};
+use raytracer::output::{write_hdr};
+use raytracer::renderer::{Film};
+
+fn get_fixture_directory() -> PathBuf {
+    return [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "obj"].iter().collect();
+}
+
+const FULL_SCENE: &str = r#"
+[settings]
+width = 64
+height = 32
+samples = 2
+max_bounces = 3
+
+[camera]
+position = [0.0, 1.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[sky]
+color = [0.2, 0.3, 0.4]
+
+[materials.floor]
+base_color = [0.5, 0.5, 0.5]
+roughness = 0.9
+
+[materials.chrome]
+base_color = [0.9, 0.9, 0.9]
+roughness = 0.05
+metallic = 1.0
+
+[[spheres]]
+center = [0.0, 1.0, 0.0]
+radius = 1.0
+material = "chrome"
+
+[[planes]]
+normal = [0.0, 1.0, 0.0]
+point = [0.0, 0.0, 0.0]
+material = "floor"
+
+[[meshes]]
+path = "two_materials.obj"
+material = "floor"
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+color = [1.0, 1.0, 1.0]
+power = 100.0
+range = 10.0
+"#;
+
+#[test]
+fn full_scene() {
+    let scene = parse_scene(FULL_SCENE, &get_fixture_directory()).unwrap();
+
+    assert_eq!(scene.settings.width, 64);
+    assert_eq!(scene.settings.height, 32);
+    assert_eq!(scene.settings.samples, 2);
+    assert_eq!(scene.settings.max_bounces, 3);
+
+    // Two named materials and the three from the mesh library.
+    assert_eq!(scene.world.materials.len(), 5);
+    assert!((scene.world.environment.get_radiance(&Vector3D::new(0.0, 1.0, 0.0)).z - 0.4).abs() < 1e-6);
+
+    // Sphere, plane and the six triangles of the mesh.
+    assert_eq!(scene.world.get_objects().len(), 8);
+    assert_eq!(scene.world.lights.len(), 1);
+
+    // Named materials are sorted by name: chrome, floor.
+    match scene.world.get_objects()[0] {
+        Primitive::Sphere(sphere) => { assert_eq!(sphere.material_index, 0); },
+        _ => panic!("The first object should be the sphere")
+    }
+
+    match scene.world.get_objects()[1] {
+        Primitive::Plane(plane) => { assert_eq!(plane.material_index, 1); },
+        _ => panic!("The second object should be the plane")
+    }
+}
+
+#[test]
+fn default_settings() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+
+    assert_eq!(scene.settings.width, 512);
+    assert_eq!(scene.settings.samples, 8);
+    assert_eq!(scene.settings.integrator, IntegratorKind::Path(PathTracer));
+    assert!(scene.world.materials.is_empty());
+    assert!(scene.world.environment.is_black());
+}
+
+#[test]
+fn unknown_material_is_reported() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[[spheres]]
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "marble"
+"#;
+
+    match parse_scene(source, &get_fixture_directory()) {
+        Err(SceneError::UnknownMaterial(element, material)) => {
+            assert_eq!(element, "sphere 1");
+            assert_eq!(material, "marble");
+        },
+        _ => panic!("The material should be unknown")
+    }
+}
+
+#[test]
+fn invalid_values_are_reported() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 5.0]
+"#;
+
+    match parse_scene(source, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "camera"); },
+        _ => panic!("The camera should be invalid")
+    }
+}
+
+#[test]
+fn schema_errors_are_reported() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+fov = 45
+"#;
+
+    match parse_scene(source, &get_fixture_directory()) {
+        Err(SceneError::Parse(message)) => { assert!(message.contains("fov")); },
+        _ => panic!("The unknown field should be reported")
+    }
+}
+
+#[test]
+fn mesh_errors_are_reported() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[materials.white]
+
+[[meshes]]
+path = "broken_library.obj"
+material = "white"
+"#;
+
+    match parse_scene(source, &get_fixture_directory()) {
+        Err(SceneError::Mesh(error)) => { assert_eq!(error.token, "half"); },
+        _ => panic!("The mesh error should be reported")
+    }
+}
+
+#[test]
+fn tone_mapping_is_read() {
+    let source = r#"
+[tone_mapping]
+operator = "aces"
+exposure = 1.5
+
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+
+    assert_eq!(scene.settings.tone_mapping.operator, ToneMapOperator::Aces);
+    assert_eq!(scene.settings.tone_mapping.exposure, 1.5);
+    assert_eq!(scene.settings.tone_mapping.white_point, 4.0);
+
+    let unknown_operator = source.replace("aces", "filmic");
+    match parse_scene(&unknown_operator, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "tone mapping"); },
+        _ => panic!("The operator should be unknown")
+    }
+}
+
+#[test]
+fn glass_material_is_read() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[materials.glass]
+transmission = 1.0
+ior = 1.33
+absorption_color = [0.9, 1.0, 0.95]
+
+[[spheres]]
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "glass"
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    let glass = &scene.world.materials[0];
+
+    assert_eq!(glass.transmission, 1.0);
+    assert_eq!(glass.ior, 1.33);
+    assert_eq!(glass.absorption_color.x, 0.9);
+
+    let negative_ior = source.replace("1.33", "-1.0");
+    match parse_scene(&negative_ior, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "material 'glass'"); },
+        _ => panic!("The ior should be invalid")
+    }
+}
+
+#[test]
+fn integrator_is_read() {
+    let source = r#"
+[settings]
+integrator = "normals"
+
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+
+    assert_eq!(scene.settings.integrator, IntegratorKind::Debug(DebugMode::Normals));
+
+    let unknown_integrator = source.replace("normals", "photon_mapping");
+    match parse_scene(&unknown_integrator, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "settings"); },
+        _ => panic!("The integrator should be unknown")
+    }
+}
+
+#[test]
+fn ambient_occlusion_settings_are_read() {
+    let source = r#"
+[settings]
+integrator = "ao"
+ao_distance = 2.5
+ao_samples = 16
+
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    assert_eq!(scene.settings.integrator, IntegratorKind::AmbientOcclusion(AmbientOcclusion::new(2.5, 16)));
+
+    let no_samples = source.replace("ao_samples = 16", "ao_samples = 0");
+    match parse_scene(&no_samples, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "settings"); },
+        _ => panic!("The samples should be invalid")
+    }
+}
+
+#[test]
+fn single_pixel_images_are_invalid() {
+    let source = r#"
+[settings]
+width = 1
+height = 64
+
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+"#;
+
+    match parse_scene(source, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "settings"); },
+        _ => panic!("The width should be invalid")
+    }
+
+    let two_pixels = source.replace("width = 1", "width = 2");
+    assert!(parse_scene(&two_pixels, &get_fixture_directory()).is_ok());
+}
+
+#[test]
+fn light_strength_is_read() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+intensity = 2.0
+"#;
+
+    let get_point_light = |source: &str| {
+        return match parse_scene(source, &get_fixture_directory()).unwrap().world.lights[0] {
+            SceneLight::Point(light) => light,
+            _ => panic!("The light should be a point light")
+        };
+    };
+
+    let light = get_point_light(source);
+    assert_eq!(light.intensity, 2.0);
+    assert_eq!(light.range, None);
+    assert_eq!(light.color.x, 1.0);
+
+    let with_power = get_point_light(&source.replace("intensity = 2.0", "power = 2.0"));
+    assert!((with_power.get_power() - 2.0).abs() < 1e-5);
+
+    for invalid in ["", "intensity = 2.0\npower = 2.0", "intensity = -1.0", "intensity = 2.0\nrange = 0.0"].iter() {
+        let invalid_source = source.replace("intensity = 2.0", invalid);
+
+        match parse_scene(&invalid_source, &get_fixture_directory()) {
+            Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "light 1"); },
+            _ => panic!("'{}' should be invalid", invalid)
+        }
+    }
+}
+
+#[test]
+fn directional_and_spot_lights_are_read() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[[directional_lights]]
+direction = [0.0, -1.0, 0.0]
+irradiance = 3.0
+
+[[spot_lights]]
+position = [0.0, 5.0, 0.0]
+direction = [0.0, -1.0, 0.0]
+intensity = 10.0
+inner_angle = 15.0
+outer_angle = 25.0
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    assert_eq!(scene.world.lights.len(), 2);
+
+    match scene.world.lights[0] {
+        SceneLight::Directional(light) => { assert_eq!(light.irradiance, 3.0); },
+        _ => panic!("The first light should be directional")
+    }
+
+    match scene.world.lights[1] {
+        SceneLight::Spot(light) => {
+            assert!((light.outer_angle - 25.0f32.to_radians()).abs() < 1e-6);
+            assert_eq!(light.falloff, 1.0);
+        },
+        _ => panic!("The second light should be a spot light")
+    }
+
+    let wide_inner_cone = source.replace("inner_angle = 15.0", "inner_angle = 35.0");
+    match parse_scene(&wide_inner_cone, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "spot light 1"); },
+        _ => panic!("The cone angles should be invalid")
+    }
+}
+
+#[test]
+fn environment_is_read() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[environment]
+type = "gradient"
+zenith = [0.2, 0.4, 1.0]
+horizon = [1.0, 1.0, 1.0]
+ground = [0.3, 0.2, 0.1]
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    assert_eq!(scene.world.environment.get_radiance(&Vector3D::new(0.0, -1.0, 0.0)).x, 0.3);
+
+    // Maps are loaded relative to the scene.
+    let mut image = Film::new(4, 2);
+    image.set_pixel(2, 1, Vector3D::new(2.0, 2.0, 2.0));
+
+    let image_name = format!("raytracer_environment_{}.hdr", std::process::id());
+    let mut data: Vec<u8> = Vec::new();
+    write_hdr(&image, &mut data).unwrap();
+    fs::write(env::temp_dir().join(&image_name), &data).unwrap();
+
+    let map_source = source.replace("type = \"gradient\"", &format!("type = \"map\"\npath = \"{}\"\nintensity = 0.5", image_name))
+        .replace("zenith = [0.2, 0.4, 1.0]\nhorizon = [1.0, 1.0, 1.0]\nground = [0.3, 0.2, 0.1]\n", "");
+    let map_scene = parse_scene(&map_source, &env::temp_dir());
+    fs::remove_file(env::temp_dir().join(&image_name)).unwrap();
+
+    let forward = map_scene.ok().unwrap().world.environment.get_radiance(&Vector3D::new(0.0, -0.1, -1.0));
+    assert_eq!(forward.x, 1.0);
+
+    // Missing image.
+    match parse_scene(&map_source, &get_fixture_directory()) {
+        Err(SceneError::Image(error)) => { assert_eq!(error.reason, LoadErrorReason::UnreadableFile); },
+        _ => panic!("The image should be missing")
+    }
+
+    for invalid in ["type = \"cubemap\"", "type = \"map\"", "type = \"constant\""].iter() {
+        let invalid_source = source.replace("type = \"gradient\"", invalid);
+
+        match parse_scene(&invalid_source, &get_fixture_directory()) {
+            Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "environment"); },
+            _ => panic!("'{}' should be invalid", invalid)
+        }
+    }
+
+    let with_sky = format!("{}\n[sky]\ncolor = [1.0, 1.0, 1.0]\n", source);
+    match parse_scene(&with_sky, &get_fixture_directory()) {
+        Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "sky"); },
+        _ => panic!("The sky and the environment should be exclusive")
+    }
+}
+
+#[test]
+fn physical_sky_adds_the_sun() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[environment]
+type = "sky"
+sun_elevation = 30.0
+sun_azimuth = 90.0
+turbidity = 4.0
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    assert_eq!(scene.world.lights.len(), 1);
+
+    match scene.world.lights[0] {
+        SceneLight::Directional(sun) => {
+            // Light travels west, away from the sun in the east.
+            assert!(sun.direction.x < -0.8);
+            assert!((sun.angular_radius - 0.265f32.to_radians()).abs() < 1e-6);
+        },
+        _ => panic!("The sun should be a directional light")
+    }
+
+    let without_sun = parse_scene(&format!("{}sun = false\n", source), &get_fixture_directory()).unwrap();
+    assert!(without_sun.world.lights.is_empty());
+
+    for (valid, invalid) in [("sun_elevation = 30.0", "sun_elevation = -10.0"), ("sun_elevation = 30.0", ""), ("turbidity = 4.0", "turbidity = 1.0")].iter() {
+        let invalid_source = source.replace(valid, invalid);
+
+        match parse_scene(&invalid_source, &get_fixture_directory()) {
+            Err(SceneError::Invalid(element, _)) => { assert_eq!(element, "environment"); },
+            _ => panic!("'{}' should be invalid", invalid)
+        }
+    }
+}
+
+#[test]
+fn textures_are_read() {
+    let source = r#"
+[camera]
+position = [0.0, 0.0, 5.0]
+target = [0.0, 0.0, 0.0]
+
+[textures.gold]
+type = "image"
+path = "gold.png"
+wrap = "clamp"
+
+[textures.tiles]
+type = "checker"
+even = [1.0, 1.0, 1.0]
+odd = [0.1, 0.1, 0.1]
+scale = 4.0
+
+[textures.marble]
+type = "noise"
+low = [0.2, 0.2, 0.2]
+high = [0.9, 0.9, 0.9]
+octaves = 6
+
+[materials.painted]
+base_color_texture = "gold"
+roughness_texture = "tiles"
+roughness = 0.5
+
+[materials.plain]
+base_color = [0.3, 0.3, 0.3]
+bump_texture = "marble"
+bump_scale = 0.02
+"#;
+
+    let scene = parse_scene(source, &get_fixture_directory()).unwrap();
+    let world = &scene.world;
+    assert_eq!(world.textures.len(), 3);
+
+    // Materials are sorted by name, the textures too.
+    let painted = &world.materials[0];
+    assert_eq!(painted.base_color_texture, Some(0));
+    assert_eq!(painted.roughness_texture, Some(2));
+    assert_eq!(painted.metalic_texture, None);
+
+    // Textured values default to one, the others keep their defaults.
+    assert_eq!((painted.base_color.x, painted.roughness, painted.metalic), (1.0, 0.5, 0.0));
+    assert_eq!(world.materials[1].base_color_texture, None);
+
+    let plain = &world.materials[1];
+    assert_eq!((plain.bump_texture, plain.bump_scale), (Some(1), 0.02));
+    assert_eq!((painted.normal_texture, painted.normal_scale), (None, 1.0));
+
+    let unknown = source.replace("roughness_texture = \"tiles\"", "roughness_texture = \"wood\"");
+    match parse_scene(&unknown, &get_fixture_directory()) {
+        Err(SceneError::UnknownTexture(element, texture)) => {
+            assert_eq!(element, "material 'painted'");
+            assert_eq!(texture, "wood");
+        },
+        _ => panic!("The texture should be unknown")
+    }
+
+    let missing = source.replace("gold.png", "silver.png");
+    match parse_scene(&missing, &get_fixture_directory()) {
+        Err(SceneError::Image(error)) => { assert_eq!(error.reason, LoadErrorReason::UnreadableFile); },
+        _ => panic!("The image should be missing")
+    }
+
+    for (valid, invalid) in [("wrap = \"clamp\"", "wrap = \"border\""), ("odd = [0.1, 0.1, 0.1]", ""), ("type = \"noise\"", "type = \"wood\"")].iter() {
+        let invalid_source = source.replace(valid, invalid);
+
+        match parse_scene(&invalid_source, &get_fixture_directory()) {
+            Err(SceneError::Invalid(element, _)) => { assert!(element.starts_with("texture")); },
+            _ => panic!("'{}' should be invalid", invalid)
+        }
+    }
+}
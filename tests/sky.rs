@@ -0,0 +1,205 @@
+extern crate raytracer;
+
+extern crate rand;
+
+use raytracer::{trace, World, SceneLight};
+use raytracer::environment::Environment;
+use raytracer::geometry::{Line, Plane, Primitive};
+use raytracer::lights::{Light, DirectionalLight};
+use raytracer::sky::{PreethamSky, get_sun_direction};
+use raytracer::vector3d::{Vector3D, vec_dot, vec_normalize};
+use raytracer::color::{Material, get_luminance};
+
+const UP: Vector3D = Vector3D { x: 0.0, y: 1.0, z: 0.0 };
+
+#[test]
+fn sun_direction_comes_from_the_angles() {
+    let noon = get_sun_direction(90f32.to_radians(), 0.0);
+    assert!((noon.y - 1.0).abs() < 1e-6);
+
+    // Azimuth 0 is towards -Z, 90 towards +X.
+    let north = get_sun_direction(0.0, 0.0);
+    assert!((north.z + 1.0).abs() < 1e-6);
+
+    let east = get_sun_direction(0.0, 90f32.to_radians());
+    assert!((east.x - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn zenith_matches_the_preetham_luminance() {
+    let turbidity = 3.0;
+    let sky = PreethamSky::new(45f32.to_radians(), 0.0, turbidity);
+
+    let theta_sun = 45f32.to_radians();
+    let chi = (4.0 / 9.0 - turbidity / 120.0) * (std::f32::consts::PI - 2.0 * theta_sun);
+    let zenith_luminance = (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192;
+
+    // Kilocandelas to radiance.
+    let expected = zenith_luminance * 1000.0 / 683.0;
+    let luminance = get_luminance(&sky.get_radiance(&UP));
+
+    assert!((luminance - expected).abs() < 0.01 * expected, "{} {}", luminance, expected);
+
+    // Clear skies are blue at the zenith.
+    let zenith = sky.get_radiance(&UP);
+    assert!(zenith.z > zenith.x);
+}
+
+#[test]
+fn sky_is_brighter_around_the_sun() {
+    let sky = PreethamSky::new(30f32.to_radians(), 0.0, 3.0);
+
+    let near_sun = sky.get_radiance(&get_sun_direction(25f32.to_radians(), 10f32.to_radians()));
+    let away_from_sun = sky.get_radiance(&get_sun_direction(25f32.to_radians(), 180f32.to_radians()));
+
+    assert!(get_luminance(&near_sun) > 2.0 * get_luminance(&away_from_sun));
+
+    // Nothing below the horizon.
+    let below = sky.get_radiance(&Vector3D::new(0.0, -0.2, -1.0));
+    assert_eq!((below.x, below.y, below.z), (0.0, 0.0, 0.0));
+
+    // The intensity scales everything.
+    let half = PreethamSky::new(30f32.to_radians(), 0.0, 3.0).with_intensity(0.5);
+    assert!((get_luminance(&half.get_radiance(&UP)) - 0.5 * get_luminance(&sky.get_radiance(&UP))).abs() < 1e-4);
+    assert!((half.get_average_radiance().y - 0.5 * sky.get_average_radiance().y).abs() < 1e-4);
+}
+
+#[test]
+fn low_sun_is_dimmer_and_redder() {
+    let high = PreethamSky::new(60f32.to_radians(), 0.0, 3.0).get_sun_light(0.0);
+    let low = PreethamSky::new(5f32.to_radians(), 0.0, 3.0).get_sun_light(0.0);
+
+    let high_color = high.color;
+    let low_color = low.color;
+
+    assert!(get_luminance(&low_color) < get_luminance(&high_color));
+    assert!(low_color.x / low_color.z > high_color.x / high_color.z);
+
+    // Haze takes more light.
+    let hazy = PreethamSky::new(60f32.to_radians(), 0.0, 8.0).get_sun_light(0.0);
+    assert!(get_luminance(&hazy.color) < get_luminance(&high_color));
+
+    // The light travels away from the sun.
+    let sky = PreethamSky::new(60f32.to_radians(), 0.0, 3.0);
+    assert!((vec_dot(&high.direction, &sky.sun_direction) + 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn sun_disk_keeps_the_irradiance() {
+    let diameter = 5f32.to_radians();
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 2.0).with_angular_diameter(diameter);
+
+    let position = Vector3D::new_as_zero();
+    let mut rng = rand::thread_rng();
+
+    let samples = 10000;
+    let mut irradiance = 0.0;
+    for _ in 0..samples {
+        let sample = sun.sample(&position, &mut rng).unwrap();
+
+        // Inside the disk.
+        assert!(vec_dot(&sample.direction, &UP) >= (diameter / 2.0).cos() - 1e-6);
+        assert!(!sample.is_delta);
+
+        irradiance += sample.radiance.x * vec_dot(&sample.direction, &UP) / sample.pdf;
+    }
+    irradiance /= samples as f32;
+
+    // Slightly less than facing the light, the disk is a little tilted at the edges.
+    assert!(irradiance < 2.0 && irradiance > 1.99, "{}", irradiance);
+}
+
+#[test]
+fn sky_environment_samples_the_upper_hemisphere() {
+    let environment = Environment::Sky(PreethamSky::new(40f32.to_radians(), 0.0, 3.0));
+    let position = Vector3D::new_as_zero();
+    let mut rng = rand::thread_rng();
+
+    // Estimate of the mean radiance over the sphere, against the precomputed one.
+    let samples = 50000;
+    let mut sum = 0.0;
+    for _ in 0..samples {
+        let sample = environment.sample(&position, &mut rng).unwrap();
+
+        assert!(sample.direction.y >= 0.0);
+        assert_eq!(sample.pdf, environment.pdf(&position, &sample.direction));
+
+        sum += sample.radiance.y / sample.pdf;
+    }
+
+    let average = sum / samples as f32 / (4.0 * std::f32::consts::PI);
+    let expected = environment.get_average_radiance().y;
+
+    assert!((average - expected).abs() < 0.03 * expected, "{} {}", average, expected);
+    assert_eq!(environment.pdf(&position, &Vector3D::new(0.0, -1.0, 0.0)), 0.0);
+}
+
+#[test]
+fn sun_disk_is_seen_inside_its_cone() {
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 2.0).with_angular_diameter(5f32.to_radians());
+    let position = Vector3D::new_as_zero();
+
+    // About 1 and 6 degrees away from the center.
+    let inside = vec_normalize(&Vector3D::new(0.02, 1.0, 0.0));
+    let outside = vec_normalize(&Vector3D::new(0.1, 1.0, 0.0));
+
+    assert!(!sun.is_delta());
+    assert_eq!(sun.pdf(&position, &outside), 0.0);
+    assert_eq!(sun.get_radiance(&outside).x, 0.0);
+
+    // The radiance over the solid angle of the disk gives back the irradiance.
+    let pdf = sun.pdf(&position, &inside);
+    assert!(pdf > 0.0);
+    assert!((sun.get_radiance(&inside).x / pdf - 2.0).abs() < 1e-3);
+
+    let point = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 2.0);
+    assert!(point.is_delta());
+    assert_eq!(point.pdf(&position, &UP), 0.0);
+    assert_eq!(point.get_radiance(&UP).x, 0.0);
+}
+
+/**
+ * Black world with a floor lit by a sun straight above.
+ */
+fn new_sun_world(sun: DirectionalLight) -> World {
+    let mut world = World::new();
+
+    world.materials = vec![Material::new(Vector3D::new(0.8, 0.8, 0.8), Vector3D::new_as_zero(), 1.0, 0.0)];
+    world.environment = Environment::Constant(Vector3D::new_as_zero());
+    world.add_object(Primitive::Plane(Plane::new(UP, Vector3D::new_as_zero(), 0)));
+    world.lights.push(SceneLight::Directional(sun));
+    world.build();
+
+    return world;
+}
+
+#[test]
+fn camera_sees_the_sun_disk() {
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 2.0).with_angular_diameter(5f32.to_radians());
+    let world = new_sun_world(sun);
+
+    let line = Line::new(Vector3D::new(0.0, 1.0, 0.0), UP);
+    let (color, _) = trace(&world, &line, 4);
+
+    assert!((color.x - sun.get_radiance(&UP).x).abs() < 1e-3 * color.x, "{}", color.x);
+}
+
+#[test]
+fn sun_disk_is_not_counted_twice() {
+    let sun = DirectionalLight::new(Vector3D::new(0.0, -1.0, 0.0), Vector3D::new_as_one(), 2.0);
+    let line = Line::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+
+    let average = |world: &World| {
+        let samples = 20000;
+        let sum: f32 = (0..samples).map(|_| trace(world, &line, 1).0.x).sum();
+
+        return sum / samples as f32;
+    };
+
+    // The disk is reached by both the light and the BSDF samples, together they add up to the single direction.
+    let point = average(&new_sun_world(sun));
+    let disk = average(&new_sun_world(sun.with_angular_diameter(5f32.to_radians())));
+
+    assert!(point > 0.0);
+    assert!((disk - point).abs() < 0.03 * point, "{} {}", disk, point);
+}
@@ -0,0 +1,364 @@
+extern crate raytracer;
+
+use raytracer::{trace, World};
+use raytracer::color::Material;
+use raytracer::geometry::{Line, Hit, Intersect, Plane, Sphere, Triangle, Primitive};
+use raytracer::loaders::{parse_png, parse_jpeg, LoadErrorReason};
+use raytracer::output::{write_png};
+use raytracer::renderer::{Film};
+use raytracer::texture::{
+    Texture, SceneTexture, ImageTexture, CheckerTexture, NoiseTexture, GradientTexture,
+    WrapMode, GradientAxis
+};
+use raytracer::vector3d::{Vector3D, vec_sum, vec_sub, vec_multiplication, vec_normalize, vec_get_length};
+
+fn assert_vec_close(a: &Vector3D, b: &Vector3D, tolerance: f32) {
+    assert!(vec_get_length(&vec_sub(a, b)) <= tolerance, "{} != {}", a.to_string(), b.to_string());
+}
+
+/**
+ * Two by two image, black and red on the top row, green and blue on the bottom one.
+ */
+fn new_image() -> Film {
+    let mut image = Film::new(2, 2);
+    image.set_pixel(0, 0, Vector3D::new(0.0, 0.0, 0.0));
+    image.set_pixel(1, 0, Vector3D::new(1.0, 0.0, 0.0));
+    image.set_pixel(0, 1, Vector3D::new(0.0, 1.0, 0.0));
+    image.set_pixel(1, 1, Vector3D::new(0.0, 0.0, 1.0));
+
+    return image;
+}
+
+const ORIGIN: Vector3D = Vector3D { x: 0.0, y: 0.0, z: 0.0 };
+
+// ================================================
+// Images
+// ================================================
+
+#[test]
+fn image_texture_filters_bilinearly() {
+    let texture = ImageTexture::new(new_image(), WrapMode::Clamp);
+
+    // Pixel centers, v goes up from the bottom row.
+    assert_vec_close(&texture.evaluate((0.25, 0.75), &ORIGIN), &Vector3D::new(0.0, 0.0, 0.0), 1e-6);
+    assert_vec_close(&texture.evaluate((0.75, 0.75), &ORIGIN), &Vector3D::new(1.0, 0.0, 0.0), 1e-6);
+    assert_vec_close(&texture.evaluate((0.25, 0.25), &ORIGIN), &Vector3D::new(0.0, 1.0, 0.0), 1e-6);
+
+    // Between the four pixels.
+    assert_vec_close(&texture.evaluate((0.5, 0.5), &ORIGIN), &Vector3D::new(0.25, 0.25, 0.25), 1e-6);
+
+    // Between the two top pixels.
+    assert_vec_close(&texture.evaluate((0.5, 0.75), &ORIGIN), &Vector3D::new(0.5, 0.0, 0.0), 1e-6);
+}
+
+#[test]
+fn wrap_modes_handle_coordinates_outside_the_image() {
+    let repeat = ImageTexture::new(new_image(), WrapMode::Repeat);
+    let clamp = ImageTexture::new(new_image(), WrapMode::Clamp);
+    let mirror = ImageTexture::new(new_image(), WrapMode::Mirror);
+
+    let inside = repeat.evaluate((0.25, 0.75), &ORIGIN);
+    assert_vec_close(&repeat.evaluate((2.25, -0.25), &ORIGIN), &inside, 1e-5);
+
+    // The red pixel stretches to the right.
+    assert_vec_close(&clamp.evaluate((7.0, 0.75), &ORIGIN), &Vector3D::new(1.0, 0.0, 0.0), 1e-6);
+
+    // The copy on the right is flipped, so its first column is the red one.
+    assert_vec_close(&mirror.evaluate((1.25, 0.75), &ORIGIN), &Vector3D::new(1.0, 0.0, 0.0), 1e-5);
+    assert_vec_close(&mirror.evaluate((2.25, 0.75), &ORIGIN), &inside, 1e-5);
+
+    // Repeating wraps the filter around the edge.
+    assert_vec_close(&repeat.evaluate((0.0, 0.75), &ORIGIN), &Vector3D::new(0.5, 0.0, 0.0), 1e-6);
+
+    assert_eq!(WrapMode::from_name("Mirror"), Some(WrapMode::Mirror));
+    assert_eq!(WrapMode::from_name("border"), None);
+}
+
+#[test]
+fn png_images_are_decoded_from_srgb() {
+    let mut film = Film::new(3, 2);
+    for (index, pixel) in film.pixels.iter_mut().enumerate() {
+        let value = index as f32 / 5.0;
+        *pixel = Vector3D::new(value, value * value, 1.0 - value);
+    }
+
+    for sixteen_bit in [false, true].iter() {
+        let mut data: Vec<u8> = Vec::new();
+        write_png(&film, &mut data, *sixteen_bit).unwrap();
+
+        let image = parse_png(&data, true).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+
+        let tolerance = if *sixteen_bit { 1e-4 } else { 1e-2 };
+        for (pixel, color) in image.pixels.iter().zip(film.pixels.iter()) {
+            assert_vec_close(pixel, color, tolerance);
+        }
+
+        // Without the decoding the values stay as they are stored.
+        let raw = parse_png(&data, false).unwrap();
+        assert!(raw.get_pixel(1, 0).x > 0.4);
+    }
+
+    let error = parse_png(b"not a png", true).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::BadImage);
+}
+
+#[test]
+fn jpeg_images_are_read() {
+    // Flat 8 by 8 grey of 192, a single DC coefficient of 32 with a quantization of 16.
+    let mut data: Vec<u8> = vec![0xFF, 0xD8, 0xFF, 0xDB, 0x00, 0x43, 0x00];
+    data.extend_from_slice(&[16; 64]);
+    data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00]);
+
+    // One code for the DC category 6 and one for the end of block.
+    data.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x14, 0x00, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x06]);
+    data.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x14, 0x10, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00]);
+
+    data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+    data.extend_from_slice(&[0x40, 0xFF, 0xD9]);
+
+    let raw = parse_jpeg(&data, false).unwrap();
+    assert_eq!((raw.width, raw.height), (8, 8));
+
+    for pixel in raw.pixels.iter() {
+        assert_vec_close(pixel, &Vector3D::new(192.0 / 255.0, 192.0 / 255.0, 192.0 / 255.0), 1e-2);
+    }
+
+    let decoded = parse_jpeg(&data, true).unwrap();
+    assert_vec_close(&decoded.get_pixel(4, 4), &Vector3D::new(0.527, 0.527, 0.527), 1e-2);
+
+    let error = parse_jpeg(&data[..20], true).err().unwrap();
+    assert_eq!(error.reason, LoadErrorReason::BadImage);
+}
+
+// ================================================
+// Procedural textures
+// ================================================
+
+#[test]
+fn checker_alternates_the_squares() {
+    let even = Vector3D::new(1.0, 1.0, 1.0);
+    let odd = Vector3D::new(0.0, 0.0, 0.0);
+    let checker = CheckerTexture::new(even, odd, 2.0);
+
+    assert_eq!(checker.evaluate((0.1, 0.1), &ORIGIN).x, 1.0);
+    assert_eq!(checker.evaluate((0.6, 0.1), &ORIGIN).x, 0.0);
+    assert_eq!(checker.evaluate((0.6, 0.6), &ORIGIN).x, 1.0);
+
+    // Negative coordinates keep the pattern.
+    assert_eq!(checker.evaluate((-0.1, 0.1), &ORIGIN).x, 0.0);
+    assert_eq!(checker.evaluate((-0.1, -0.1), &ORIGIN).x, 1.0);
+}
+
+#[test]
+fn noise_is_smooth_and_repeatable() {
+    let noise = NoiseTexture::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), 1.0);
+
+    // Perlin noise is zero on the lattice, the middle of the colors.
+    assert_eq!(noise.get_value(&Vector3D::new(3.0, -2.0, 7.0)), 0.5);
+
+    let fbm = noise.clone().with_octaves(5, 0.5);
+    let reseeded = noise.clone().with_octaves(5, 0.5).with_seed(42);
+
+    let mut differences = 0;
+    let mut minimum: f32 = 1.0;
+    let mut maximum: f32 = 0.0;
+    for index in 0..1000 {
+        let t = index as f32 * 0.0137;
+        let position = Vector3D::new(t * 3.1, t * -1.7 + 0.3, t * 0.9 + 0.5);
+
+        let value = fbm.get_value(&position);
+        minimum = minimum.min(value);
+        maximum = maximum.max(value);
+
+        // Close points have close values.
+        let nearby = fbm.get_value(&Vector3D::new(position.x + 1e-3, position.y, position.z));
+        assert!((value - nearby).abs() < 0.05, "{} {}", value, nearby);
+
+        assert_eq!(value, noise.clone().with_octaves(5, 0.5).get_value(&position));
+
+        if (value - reseeded.get_value(&position)).abs() > 1e-3 {
+            differences += 1;
+        }
+
+        let color = fbm.evaluate((0.0, 0.0), &position);
+        assert_eq!((color.x, color.y, color.z), (value, value, value));
+    }
+
+    assert!(minimum >= 0.0 && maximum <= 1.0);
+    assert!(maximum - minimum > 0.3, "{} {}", minimum, maximum);
+    assert!(differences > 900, "{}", differences);
+}
+
+#[test]
+fn gradient_follows_the_axis() {
+    let start = Vector3D::new(1.0, 0.0, 0.0);
+    let end = Vector3D::new(0.0, 0.0, 1.0);
+
+    let horizontal = GradientTexture::new(start, end, GradientAxis::U);
+    assert_vec_close(&horizontal.evaluate((0.25, 0.9), &ORIGIN), &Vector3D::new(0.75, 0.0, 0.25), 1e-6);
+    assert_vec_close(&horizontal.evaluate((3.0, 0.9), &ORIGIN), &end, 1e-6);
+
+    let vertical = GradientTexture::new(start, end, GradientAxis::V);
+    assert_vec_close(&vertical.evaluate((0.25, 0.9), &ORIGIN), &Vector3D::new(0.1, 0.0, 0.9), 1e-6);
+    assert_vec_close(&vertical.evaluate((0.25, -1.0), &ORIGIN), &start, 1e-6);
+}
+
+// ================================================
+// Materials
+// ================================================
+
+#[test]
+fn textures_drive_the_material() {
+    let mut world = World::new();
+    world.textures.push(SceneTexture::Checker(CheckerTexture::new(Vector3D::new(1.0, 0.5, 0.25), Vector3D::new_as_zero(), 1.0)));
+    world.textures.push(SceneTexture::Gradient(GradientTexture::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), GradientAxis::U)));
+
+    let mut material = Material::new(Vector3D::new(0.5, 0.5, 0.5), Vector3D::new(2.0, 2.0, 2.0), 0.8, 1.0);
+    material.base_color_texture = Some(0);
+    material.emission_texture = Some(0);
+    material.roughness_texture = Some(1);
+    material.metalic_texture = Some(1);
+    world.materials.push(material);
+
+    let line = Line::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+    let hit = Hit::new(&line, 1.0, Vector3D::new(0.0, 1.0, 0.0), (0.25, 0.5), 0);
+
+    let surface = world.get_surface_material(&hit);
+    assert_vec_close(&surface.base_color, &Vector3D::new(0.5, 0.25, 0.125), 1e-6);
+    assert_vec_close(&surface.emision_color, &Vector3D::new(2.0, 1.0, 0.5), 1e-6);
+    assert!((surface.roughness - 0.2).abs() < 1e-5);
+    assert!((surface.metalic - 0.25).abs() < 1e-5);
+
+    // The stored material doesn't change.
+    assert_eq!(world.materials[0].base_color.y, 0.5);
+}
+
+#[test]
+fn textured_emission_is_found_by_the_paths() {
+    let mut world = World::new();
+    world.textures.push(SceneTexture::Checker(CheckerTexture::new(Vector3D::new_as_one(), Vector3D::new_as_zero(), 1.0)));
+
+    let mut material = Material::new(Vector3D::new_as_zero(), Vector3D::new(3.0, 3.0, 3.0), 1.0, 0.0);
+    material.emission_texture = Some(0);
+    world.materials.push(material);
+
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+    world.build();
+
+    // It can't be sampled as an area light with a single emission.
+    assert!(world.area_lights.is_empty());
+
+    let bright = trace(&world, &Line::new(Vector3D::new(0.5, 1.0, -0.5), Vector3D::new(0.0, -1.0, 0.0)), 0).0;
+    assert_eq!(bright.x, 3.0);
+
+    let dark = trace(&world, &Line::new(Vector3D::new(0.5, 1.0, 0.5), Vector3D::new(0.0, -1.0, 0.0)), 0).0;
+    assert_eq!(dark.x, 0.0);
+}
+
+// ================================================
+// Normal and bump maps
+// ================================================
+
+/**
+ * World with a floor facing up and a material using the texture as a normal or bump map.
+ */
+fn new_mapped_world(texture: SceneTexture, is_bump: bool, scale: f32) -> World {
+    let mut world = World::new();
+    world.textures.push(texture);
+
+    let mut material = Material::new_base(Vector3D::new_as_one());
+    if is_bump {
+        material.bump_texture = Some(0);
+        material.bump_scale = scale;
+    } else {
+        material.normal_texture = Some(0);
+        material.normal_scale = scale;
+    }
+
+    world.materials.push(material);
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+
+    return world;
+}
+
+fn get_floor_normal(world: &World, x: f32) -> Vector3D {
+    let hit = world.closest_hit(&Line::new(Vector3D::new(x, 1.0, 0.3), Vector3D::new(0.0, -1.0, 0.0))).unwrap();
+
+    return world.get_shading_normal(&hit, &world.materials[0], &Vector3D::new(0.0, 1.0, 0.0));
+}
+
+fn new_flat_texture(color: Vector3D) -> SceneTexture {
+    return SceneTexture::Checker(CheckerTexture::new(color, color, 1.0));
+}
+
+#[test]
+fn uv_derivatives_match_the_mapping() {
+    let sphere = Sphere::new(Vector3D::new(1.0, 2.0, 3.0), 2.0, 0);
+    let direction = vec_normalize(&Vector3D::new(0.3, 0.4, -0.6));
+
+    // From the center outwards, so the mapping can be checked at any point.
+    let get_hit = |direction: &Vector3D| sphere.intersects(&Line::new(sphere.origin, *direction)).unwrap();
+    let hit = get_hit(&direction);
+
+    let step = 1e-3;
+    for (derivative, (du, dv)) in [(hit.dpdu, (step, 0.0)), (hit.dpdv, (0.0, step))].iter() {
+        let moved = vec_sum(&hit.position, &vec_multiplication(derivative, step));
+        let moved_hit = get_hit(&vec_normalize(&vec_sub(&moved, &sphere.origin)));
+
+        assert!((moved_hit.uv.0 - hit.uv.0 - du).abs() < 1e-4, "{} {}", moved_hit.uv.0, hit.uv.0);
+        assert!((moved_hit.uv.1 - hit.uv.1 - dv).abs() < 1e-4, "{} {}", moved_hit.uv.1, hit.uv.1);
+    }
+
+    // Triangles solve them from the vertex uvs.
+    let mut triangle = Triangle::new(Vector3D::new_as_zero(), Vector3D::new(2.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 4.0), 0);
+    triangle.set_uvs([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+
+    let hit = triangle.intersects(&Line::new(Vector3D::new(0.5, 1.0, 0.5), Vector3D::new(0.0, -1.0, 0.0))).unwrap();
+    assert_vec_close(&hit.dpdu, &Vector3D::new(2.0, 0.0, 0.0), 1e-5);
+    assert_vec_close(&hit.dpdv, &Vector3D::new(0.0, 0.0, 4.0), 1e-5);
+
+    let frame = hit.get_tangent_frame();
+    assert_vec_close(&frame.u, &Vector3D::new(1.0, 0.0, 0.0), 1e-5);
+    assert_vec_close(&frame.v, &Vector3D::new(0.0, 0.0, 1.0), 1e-5);
+}
+
+#[test]
+fn normal_map_bends_the_shading_normal() {
+    // The flat color of normal maps keeps the normal.
+    let flat = new_mapped_world(new_flat_texture(Vector3D::new(0.5, 0.5, 1.0)), false, 1.0);
+    assert_vec_close(&get_floor_normal(&flat, 0.2), &Vector3D::new(0.0, 1.0, 0.0), 1e-6);
+
+    // Half way to the tangent, which is -X on this floor.
+    let tilted = new_mapped_world(new_flat_texture(Vector3D::new(0.75, 0.5, 1.0)), false, 1.0);
+    assert_vec_close(&get_floor_normal(&tilted, 0.2), &vec_normalize(&Vector3D::new(-0.5, 1.0, 0.0)), 1e-5);
+
+    // No strength, no change.
+    let weak = new_mapped_world(new_flat_texture(Vector3D::new(0.75, 0.5, 1.0)), false, 0.0);
+    assert_vec_close(&get_floor_normal(&weak, 0.2), &Vector3D::new(0.0, 1.0, 0.0), 1e-6);
+
+    // Looking from the side the normal leans away from, the surface would be hidden.
+    let hit = tilted.closest_hit(&Line::new(Vector3D::new(0.2, 1.0, 0.3), Vector3D::new(0.0, -1.0, 0.0))).unwrap();
+    let grazing = vec_normalize(&Vector3D::new(1.0, 0.1, 0.0));
+    assert_vec_close(&tilted.get_shading_normal(&hit, &tilted.materials[0], &grazing), &hit.shading_normal, 1e-6);
+}
+
+#[test]
+fn bump_map_tilts_against_the_slope() {
+    // The floor u grows towards -X, so the heights grow towards -X too.
+    let ramp = SceneTexture::Gradient(GradientTexture::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), GradientAxis::U));
+    let world = new_mapped_world(ramp, true, 0.5);
+
+    assert_vec_close(&get_floor_normal(&world, -0.5), &vec_normalize(&Vector3D::new(0.5, 1.0, 0.0)), 1e-3);
+
+    // Past the end of the ramp it's flat again.
+    assert_vec_close(&get_floor_normal(&world, -2.0), &Vector3D::new(0.0, 1.0, 0.0), 1e-6);
+
+    // Noise heights come from the position, they bump the surface too.
+    let noise = SceneTexture::Noise(NoiseTexture::new(Vector3D::new_as_zero(), Vector3D::new_as_one(), 4.0));
+    let world = new_mapped_world(noise, true, 0.1);
+
+    let normals: Vec<Vector3D> = (0..10).map(|index| get_floor_normal(&world, index as f32 * 0.13)).collect();
+    assert!(normals.iter().all(|normal| (vec_get_length(normal) - 1.0).abs() < 1e-5));
+    assert!(normals.iter().any(|normal| normal.x.abs() > 0.01));
+}
@@ -0,0 +1,84 @@
+extern crate raytracer;
+
+use raytracer::color::{ToneMapping, ToneMapOperator, linear_color_to_srgb};
+use raytracer::vector3d::Vector3D;
+
+const OPERATORS: [ToneMapOperator; 5] = [
+    ToneMapOperator::Clamp,
+    ToneMapOperator::Reinhard,
+    ToneMapOperator::ExtendedReinhard,
+    ToneMapOperator::Aces,
+    ToneMapOperator::Hable
+];
+
+fn grey(value: f32) -> Vector3D {
+    return Vector3D::new(value, value, value);
+}
+
+#[test]
+fn operators_stay_in_range_and_are_monotonic() {
+    for operator in OPERATORS.iter() {
+        let tone_mapping = ToneMapping::new(*operator, 0.0, 4.0);
+
+        let mut previous = 0.0;
+        for step in 0..200 {
+            let mapped = tone_mapping.apply(&grey(step as f32 * 0.1));
+
+            assert!(mapped.x >= 0.0 && mapped.x <= 1.0, "{:?} out of range", operator);
+            assert!(mapped.x >= previous - 1e-6, "{:?} is not monotonic", operator);
+
+            previous = mapped.x;
+        }
+
+        let black = tone_mapping.apply(&grey(0.0));
+        assert!(black.x.abs() < 1e-3, "{:?} doesn't keep black", operator);
+    }
+}
+
+#[test]
+fn white_point_maps_to_white() {
+    for operator in [ToneMapOperator::ExtendedReinhard, ToneMapOperator::Hable].iter() {
+        let tone_mapping = ToneMapping::new(*operator, 0.0, 6.0);
+
+        assert!((tone_mapping.apply(&grey(6.0)).x - 1.0).abs() < 1e-4);
+        assert!(tone_mapping.apply(&grey(3.0)).x < 1.0);
+    }
+}
+
+#[test]
+fn exposure_doubles_the_radiance() {
+    let tone_mapping = ToneMapping::new(ToneMapOperator::Clamp, 1.0, 4.0);
+
+    assert!((tone_mapping.apply(&grey(0.2)).x - 0.4).abs() < 1e-6);
+
+    let darker = ToneMapping::new(ToneMapOperator::Clamp, -2.0, 4.0);
+    assert!((darker.apply(&grey(0.8)).x - 0.2).abs() < 1e-6);
+}
+
+#[test]
+fn reinhard_keeps_the_hue() {
+    let tone_mapping = ToneMapping::new(ToneMapOperator::Reinhard, 0.0, 4.0);
+    let mapped = tone_mapping.apply(&Vector3D::new(2.0, 1.0, 0.5));
+
+    assert!((mapped.x / mapped.y - 2.0).abs() < 1e-4);
+    assert!((mapped.y / mapped.z - 2.0).abs() < 1e-4);
+}
+
+#[test]
+fn default_clamp_matches_the_plain_srgb_encoding() {
+    let tone_mapping = ToneMapping::new_clamp();
+
+    for value in [0.0, 0.001, 0.2, 0.5, 1.0, 3.0].iter() {
+        let expected = linear_color_to_srgb(&grey(*value));
+
+        assert!((tone_mapping.to_srgb(&grey(*value)).x - expected.x).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn operators_are_found_by_name() {
+    assert_eq!(ToneMapOperator::from_name("ACES"), Some(ToneMapOperator::Aces));
+    assert_eq!(ToneMapOperator::from_name("extended_reinhard"), Some(ToneMapOperator::ExtendedReinhard));
+    assert_eq!(ToneMapOperator::from_name("hable"), Some(ToneMapOperator::Hable));
+    assert_eq!(ToneMapOperator::from_name("filmic"), None);
+}
@@ -0,0 +1,222 @@
+extern crate raytracer;
+
+extern crate rand;
+
+use raytracer::{trace, power_heuristic, World, PointLight, SceneLight};
+use raytracer::bsdf::{Bsdf, MetallicRoughness};
+use raytracer::geometry::{Line, Plane, Sphere, Primitive};
+use raytracer::vector3d::{Vector3D, vec_sub, vec_sum, vec_division, vec_normalize};
+use raytracer::color::Material;
+use raytracer::environment::Environment;
+use raytracer::camera::Camera;
+
+const SIZE: usize = 16;
+
+fn new_world() -> World {
+    let mut world = World::new();
+
+    world.materials = vec![
+        Material::new_base(Vector3D::new(0.8, 0.2, 0.2))
+    ];
+    world.environment = Environment::Constant(Vector3D::new(0.1, 0.1, 0.15));
+
+    world.lights.push(SceneLight::Point(PointLight::new(Vector3D::new(0.0, 5.0, 5.0), Vector3D::new_as_one(), 20.0)));
+
+    return world;
+}
+
+/**
+ * Counts the pixels whose primary ray hits some object.
+ */
+fn count_hit_pixels(world: &World, camera: &Camera) -> usize {
+    let mut hits = 0;
+
+    for j in 0..SIZE {
+        for i in 0..SIZE {
+            let film_point = camera.screen_point_to_projection_plane(i, SIZE, j, SIZE);
+            let direction = vec_normalize(&vec_sub(&film_point, &camera.position));
+
+            let (_, bounces) = trace(world, &Line::new(camera.position, direction), 4);
+
+            if bounces > 0 {
+                hits += 1;
+            }
+        }
+    }
+
+    return hits;
+}
+
+#[test]
+fn sphere_only_world_renders_the_sphere() {
+    let mut world = new_world();
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 0)));
+
+    let camera = Camera::new(Vector3D::new(0.0, 0.0, 6.0), Vector3D::new_as_zero(), 2.0);
+
+    let hits = count_hit_pixels(&world, &camera);
+
+    assert!(hits > 0);
+    assert!(hits < SIZE * SIZE);
+}
+
+#[test]
+fn plane_only_world_renders_the_plane() {
+    let mut world = new_world();
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 1.0, 0.0), Vector3D::new_as_zero(), 0)));
+
+    let camera = Camera::new(Vector3D::new(0.0, 2.0, 6.0), Vector3D::new_as_zero(), 2.0);
+
+    let hits = count_hit_pixels(&world, &camera);
+
+    // The horizon splits the image, the lower half sees the floor.
+    assert!(hits >= SIZE * SIZE / 2);
+    assert!(hits < SIZE * SIZE);
+}
+
+#[test]
+fn empty_world_renders_only_sky() {
+    let world = new_world();
+    let camera = Camera::new(Vector3D::new(0.0, 0.0, 6.0), Vector3D::new_as_zero(), 2.0);
+
+    assert_eq!(count_hit_pixels(&world, &camera), 0);
+}
+
+#[test]
+fn bright_radiance_is_not_clamped() {
+    let mut world = new_world();
+    world.environment = Environment::Constant(Vector3D::new(10.0, 20.0, 40.0));
+
+    let line = Line::new(Vector3D::new_as_zero(), Vector3D::new(0.0, 0.0, -1.0));
+    let (color, bounces) = trace(&world, &line, 4);
+
+    assert_eq!(bounces, 0);
+    assert_eq!((color.x, color.y, color.z), (10.0, 20.0, 40.0));
+}
+
+// ================================================
+// White furnace
+// ================================================
+
+/**
+ * Uniform white environment around a unit sphere with the given material.
+ */
+fn new_furnace(material: Material) -> World {
+    let mut world = World::new();
+
+    world.materials = vec![material];
+    world.environment = Environment::Constant(Vector3D::new_as_one());
+    world.add_object(Primitive::Sphere(Sphere::new(Vector3D::new_as_zero(), 1.0, 0)));
+    world.build();
+
+    return world;
+}
+
+/**
+ * Average radiance of the line through the center of the furnace sphere.
+ */
+fn furnace_radiance(world: &World, paths: u32) -> Vector3D {
+    let line = Line::new(Vector3D::new(0.0, 0.0, 4.0), Vector3D::new(0.0, 0.0, -1.0));
+
+    let mut sum = Vector3D::new_as_zero();
+    for _ in 0..paths {
+        let (color, _) = trace(world, &line, 16);
+        sum = vec_sum(&sum, &color);
+    }
+
+    return vec_division(&sum, paths as f32);
+}
+
+#[test]
+fn white_furnace_clear_glass_is_invisible() {
+    let world = new_furnace(Material::new_dielectric(1.5, Vector3D::new_as_one()));
+
+    let color = furnace_radiance(&world, 2000);
+
+    assert!((color.x - 1.0).abs() < 0.01, "{}", color.x);
+}
+
+#[test]
+fn white_furnace_matches_the_directional_albedo() {
+    let material = Material::new(Vector3D::new_as_one(), Vector3D::new_as_zero(), 0.6, 0.0);
+    let world = new_furnace(material);
+
+    // The sphere is convex, every path leaves it after one bounce and
+    // the light sampling and BSDF sampling must add up to the BSDF albedo.
+    let normal = Vector3D::new(0.0, 0.0, 1.0);
+    let bsdf = MetallicRoughness::from_material(&material);
+    let mut rng = rand::thread_rng();
+
+    let albedo_samples = 200000;
+    let mut albedo = 0.0;
+    for _ in 0..albedo_samples {
+        if let Some(sample) = bsdf.sample(&normal, &normal, &mut rng) {
+            albedo += sample.weight.x;
+        }
+    }
+    albedo /= albedo_samples as f32;
+
+    let color = furnace_radiance(&world, 20000);
+
+    assert!(color.x <= 1.0);
+    assert!((color.x - albedo).abs() < 0.02, "radiance {} albedo {}", color.x, albedo);
+}
+
+#[test]
+fn white_furnace_with_russian_roulette_does_not_gain_energy() {
+    // Camera inside the sphere, every path bounces until the roulette or the limit ends it.
+    let world = new_furnace(Material::new(Vector3D::new(0.5, 0.5, 0.5), Vector3D::new(0.5, 0.5, 0.5), 1.0, 0.0));
+    let line = Line::new(Vector3D::new_as_zero(), Vector3D::new(0.0, 0.0, -1.0));
+
+    let paths = 5000;
+    let mut sum = 0.0;
+    for _ in 0..paths {
+        let (color, _) = trace(&world, &line, 64);
+        sum += color.x;
+    }
+
+    // Without absorption the result would be 0.5 / (1 - 0.5), the BSDF loses some energy.
+    let radiance = sum / paths as f32;
+    assert!(radiance > 0.5 && radiance < 1.02, "{}", radiance);
+}
+
+#[test]
+fn power_heuristic_weights_add_up_to_one() {
+    for &(a, b) in [(1.0, 1.0), (0.2, 3.0), (5.0, 0.0)].iter() {
+        assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-6);
+    }
+
+    assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+}
+
+#[test]
+fn cut_out_surfaces_let_lines_through() {
+    let mut world = World::new();
+
+    let mut leaf = Material::new_base(Vector3D::new_as_one());
+    leaf.opacity = 0.0;
+    world.materials = vec![Material::new_base(Vector3D::new_as_one()), leaf];
+
+    // A cut out card in front of a wall.
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 0.0, 1.0), Vector3D::new(0.0, 0.0, -1.0), 1)));
+    world.add_object(Primitive::Plane(Plane::new(Vector3D::new(0.0, 0.0, 1.0), Vector3D::new(0.0, 0.0, -3.0), 0)));
+    world.build();
+
+    let line = Line::new(Vector3D::new_as_zero(), Vector3D::new(0.0, 0.0, -1.0));
+    let mut rng = rand::thread_rng();
+
+    let hit = world.closest_opaque_hit(&line, &mut rng).unwrap();
+    assert_eq!(hit.material_index, 0);
+    assert!((hit.t - 3.0).abs() < 1e-4);
+
+    assert!(world.any_opaque_hit(&line, 4.0, &mut rng));
+    assert!(!world.any_opaque_hit(&line, 2.0, &mut rng));
+
+    // Half covered, half of the lines stop on the card.
+    world.materials[1].opacity = 0.5;
+
+    let stopped = (0..2000)
+        .filter(|_| world.closest_opaque_hit(&line, &mut rng).unwrap().material_index == 1)
+        .count();
+    assert!(stopped > 900 && stopped < 1100, "{}", stopped);
+}